@@ -0,0 +1,108 @@
+//! Compares the lowerings of `felt.add` and `felt.mul` for every `Reduction`,
+//! through the JIT.
+//!
+//! ```sh
+//! cargo bench --bench reduction
+//! ```
+//!
+//! Each operation is mapped over a buffer of felts with `FeltFn::map`. With
+//! `Reduction::Montgomery`, every element is converted into Montgomery form
+//! and back, which is the worst case for it: the conversions cost two
+//! Montgomery products per element.
+
+use std::time::Instant;
+
+use dialect_rust::{
+    apply_pdl_patterns, caller_location, canonicalize, convert_pdl_to_pdl_interop, convert_to_llvm,
+    dsl::FeltFn,
+    initialize_context,
+    irdl::build_dialect_module,
+    memref::MemRefDescriptor,
+    montgomery::convert_boundaries,
+    pdl::{MODULUS, Reduction, build_reduction_pattern_module},
+    pipeline::PipelineOptions,
+};
+use melior::{Context, ExecutionEngine, ir::Module, utility::load_irdl_dialects};
+
+const LENGTH: usize = 1 << 20;
+const ITERATIONS: u32 = 20;
+
+#[derive(Debug, Clone, Copy)]
+enum Operation {
+    Add,
+    Mul,
+}
+
+fn main() {
+    let context = initialize_context();
+    load_irdl_dialects(&build_dialect_module(&context));
+
+    let xs = (0..LENGTH as u32)
+        .map(|index| index % MODULUS)
+        .collect::<Vec<_>>();
+    let ys = (0..LENGTH as u32)
+        .map(|index| (index * 7 + 3) % MODULUS)
+        .collect::<Vec<_>>();
+    let mut output = vec![0; LENGTH];
+
+    for operation in [Operation::Add, Operation::Mul] {
+        for reduction in [
+            Reduction::Remainder,
+            Reduction::Montgomery,
+            Reduction::Barrett,
+        ] {
+            let module = lower(&context, operation, reduction);
+            let engine = ExecutionEngine::new(&module, 2, &[], false);
+
+            let mut run = || {
+                let mut descriptors = [
+                    MemRefDescriptor::from_slice(&xs),
+                    MemRefDescriptor::from_slice(&ys),
+                    MemRefDescriptor::from_mut_slice(&mut output),
+                ];
+                let mut arguments = descriptors
+                    .iter_mut()
+                    .flat_map(MemRefDescriptor::packed_arguments)
+                    .collect::<Vec<_>>();
+                unsafe { engine.invoke_packed("map", &mut arguments).unwrap() }
+            };
+
+            // The first call also resolves the symbols.
+            run();
+            let start = Instant::now();
+            for _ in 0..ITERATIONS {
+                run();
+            }
+            let elapsed = start.elapsed() / ITERATIONS;
+
+            println!(
+                "{operation:?} {reduction:?}: {elapsed:?} ({:.2} ns/element)",
+                elapsed.as_nanos() as f64 / LENGTH as f64
+            );
+        }
+    }
+}
+
+/// Builds the map function of the operation, and lowers it to LLVM.
+fn lower(context: &Context, operation: Operation, reduction: Reduction) -> Module<'_> {
+    let options = PipelineOptions::default();
+
+    let mut module = Module::new(caller_location(context));
+    module.body().append_operation(
+        FeltFn::map(context, "map", 2, |_, [x, y]| match operation {
+            Operation::Add => x + y,
+            Operation::Mul => x * y,
+        })
+        .into(),
+    );
+    if reduction == Reduction::Montgomery {
+        convert_boundaries(context, &module).unwrap();
+    }
+
+    let mut pattern_module = build_reduction_pattern_module(context, reduction);
+    canonicalize(context, &mut pattern_module, &options);
+    convert_pdl_to_pdl_interop(context, &mut pattern_module, &options);
+    apply_pdl_patterns(&module, &pattern_module);
+    convert_to_llvm(context, &mut module, &options);
+    module
+}
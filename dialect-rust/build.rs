@@ -0,0 +1,26 @@
+//! Compiles the C++ shim of the `native` module against the MLIR headers.
+
+use std::{env, path::PathBuf, process::Command};
+
+fn main() {
+    println!("cargo:rerun-if-changed=src/native.cpp");
+    println!("cargo:rerun-if-env-changed=MLIR_SYS_190_PREFIX");
+
+    // Like mlir-sys, we find LLVM with `llvm-config`, in the prefix given by
+    // `MLIR_SYS_190_PREFIX` if it's set.
+    let llvm_config = env::var("MLIR_SYS_190_PREFIX")
+        .map(|prefix| PathBuf::from(prefix).join("bin/llvm-config"))
+        .unwrap_or_else(|_| "llvm-config".into());
+    let output = Command::new(&llvm_config)
+        .arg("--cxxflags")
+        .output()
+        .unwrap_or_else(|error| panic!("failed to run {}: {error}", llvm_config.display()));
+    let flags = String::from_utf8(output.stdout).unwrap();
+
+    let mut build = cc::Build::new();
+    build.cpp(true).file("src/native.cpp");
+    for flag in flags.split_whitespace() {
+        build.flag(flag);
+    }
+    build.compile("dialect_rust_native");
+}
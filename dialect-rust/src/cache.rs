@@ -0,0 +1,305 @@
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    process::{self, Command},
+};
+
+use libloading::{Library, Symbol};
+use melior::ir::Module;
+
+use crate::{
+    ENTRYPOINT_TYPE,
+    emit::{HostTarget, write_object},
+    entrypoint_type,
+    error::Error,
+};
+
+/// Identifies a compilation, by hashing everything that affects its output.
+///
+/// Two compilations with the same key are expected to produce the same shared
+/// library, so the result of the first one can be reused.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct CacheKey(u128);
+
+impl CacheKey {
+    /// Hashes the textual form of the given modules (usually the input module,
+    /// and the IRDL and PDL modules used to lower it), together with a
+    /// description of the lowering pipeline.
+    ///
+    /// Libraries are compiled for the host (see `write_object`), so its
+    /// target is part of the key too.
+    pub fn new(modules: &[&Module], pipeline: &str) -> Self {
+        let host = HostTarget::get();
+        let mut hasher = Fnv1a::new();
+        hasher.write(env!("CARGO_PKG_VERSION").as_bytes());
+        hasher.write(host.triple.as_bytes());
+        hasher.write(host.cpu.as_bytes());
+        hasher.write(host.features.as_bytes());
+        hasher.write(pipeline.as_bytes());
+        for module in modules {
+            hasher.write(module.as_operation().to_string().as_bytes());
+        }
+        CacheKey(hasher.finish())
+    }
+}
+
+impl std::fmt::Display for CacheKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:032x}", self.0)
+    }
+}
+
+/// An on-disk cache of compiled modules.
+///
+/// Each entry is a shared library, named after the key of the compilation
+/// that produced it, and the type of its lowered `entrypoint` (see
+/// `ENTRYPOINT_TYPE`), which the library itself doesn't record.
+pub struct CompilationCache {
+    directory: PathBuf,
+}
+
+impl CompilationCache {
+    pub fn new(directory: impl Into<PathBuf>) -> Result<Self, Error> {
+        let directory = directory.into();
+        fs::create_dir_all(&directory)?;
+        Ok(CompilationCache { directory })
+    }
+
+    /// Loads the library compiled for the given key, if there is one.
+    ///
+    /// Fails if the library or its entrypoint type can't be loaded, or if the
+    /// library has no `entrypoint`, for example if the cache entry is stale or
+    /// corrupt. Such an entry is replaced by the next `store`.
+    pub fn load(&self, key: &CacheKey) -> Result<Option<CachedLibrary>, Error> {
+        let path = self.library_path(key);
+        if !path.exists() {
+            return Ok(None);
+        }
+        CachedLibrary::open(&path, &self.signature_path(key)).map(Some)
+    }
+
+    /// Compiles a module, already converted to the `llvm` dialect, and stores
+    /// the resulting library under the given key.
+    pub fn store(&self, key: &CacheKey, module: &Module) -> Result<CachedLibrary, Error> {
+        // Concurrent runs may compile the same module at the same time, so we
+        // build the library under a unique name, and then atomically move it
+        // into place.
+        let unique = format!("{key}.{}", process::id());
+        let object_path = self.directory.join(format!("{unique}.o"));
+        let temporary_path = self.directory.join(format!("{unique}.so"));
+        let temporary_signature_path = self.directory.join(format!("{unique}.signature"));
+
+        write_object(module, &object_path)?;
+        let output = Command::new(env::var("CC").unwrap_or_else(|_| "cc".to_string()))
+            .arg("-shared")
+            .arg(&object_path)
+            .arg("-o")
+            .arg(&temporary_path)
+            .output()?;
+        fs::remove_file(&object_path)?;
+        if !output.status.success() {
+            return Err(Error::Link(
+                String::from_utf8_lossy(&output.stderr).into_owned(),
+            ));
+        }
+
+        // The library is moved last, so that a concurrent `load` never finds
+        // it without its entrypoint type.
+        fs::write(
+            &temporary_signature_path,
+            entrypoint_type(module).unwrap_or_default(),
+        )?;
+        let signature_path = self.signature_path(key);
+        fs::rename(&temporary_signature_path, &signature_path)?;
+        let path = self.library_path(key);
+        fs::rename(&temporary_path, &path)?;
+
+        CachedLibrary::open(&path, &signature_path)
+    }
+
+    fn library_path(&self, key: &CacheKey) -> PathBuf {
+        self.directory.join(format!("{key}.so"))
+    }
+
+    fn signature_path(&self, key: &CacheKey) -> PathBuf {
+        self.directory.join(format!("{key}.signature"))
+    }
+}
+
+/// A compiled module, loaded from the cache.
+pub struct CachedLibrary {
+    library: Library,
+    entrypoint_type: String,
+}
+
+impl CachedLibrary {
+    fn open(path: &Path, signature_path: &Path) -> Result<Self, Error> {
+        let library =
+            unsafe { Library::new(path) }.map_err(|error| Error::Link(error.to_string()))?;
+        let library = CachedLibrary {
+            library,
+            entrypoint_type: fs::read_to_string(signature_path)?,
+        };
+        library.entrypoint()?;
+        Ok(library)
+    }
+
+    fn entrypoint(&self) -> Result<Symbol<'_, unsafe extern "C" fn(u32, u32) -> u32>, Error> {
+        unsafe { self.library.get(b"entrypoint") }.map_err(|error| Error::Link(error.to_string()))
+    }
+
+    /// Equivalent to `execute_entrypoint`, but calling the compiled function
+    /// directly instead of going through the JIT.
+    ///
+    /// Fails if the lowered `entrypoint` didn't have the type it's called
+    /// with, like `check_entrypoint_signature`.
+    pub fn execute_entrypoint(&self, a: u32, b: u32) -> Result<u32, Error> {
+        if self.entrypoint_type != ENTRYPOINT_TYPE {
+            return Err(Error::Entrypoint(format!(
+                "the cached @entrypoint has type {}, instead of {ENTRYPOINT_TYPE}",
+                self.entrypoint_type
+            )));
+        }
+        let entrypoint = self.entrypoint()?;
+        Ok(unsafe { entrypoint(a, b) })
+    }
+}
+
+/// The 128-bit variant of the FNV-1a hash.
+///
+/// Unlike `std::hash::DefaultHasher`, its output is stable across Rust
+/// versions, which is required for keys that are persisted to disk.
+struct Fnv1a(u128);
+
+impl Fnv1a {
+    const OFFSET_BASIS: u128 = 0x6c62272e07bb014262b821756295c58d;
+    const PRIME: u128 = 0x0000000001000000000000000000013b;
+
+    fn new() -> Self {
+        Fnv1a(Self::OFFSET_BASIS)
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 ^= byte as u128;
+            self.0 = self.0.wrapping_mul(Self::PRIME);
+        }
+        // We also hash the length, so that consecutive writes can't be
+        // shifted into each other (e.g. "ab" + "c" and "a" + "bc").
+        for byte in bytes.len().to_le_bytes() {
+            self.0 ^= byte as u128;
+            self.0 = self.0.wrapping_mul(Self::PRIME);
+        }
+    }
+
+    fn finish(&self) -> u128 {
+        self.0
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{env, fs, process};
+
+    use melior::{ir::Module, utility::load_irdl_dialects};
+
+    use crate::{
+        LLVM_PIPELINE, apply_pdl_patterns,
+        cache::{CacheKey, CompilationCache},
+        canonicalize, convert_pdl_to_pdl_interop, convert_to_llvm,
+        core::build_core_module,
+        error::Error,
+        initialize_context,
+        irdl::build_dialect_module,
+        pdl::build_pattern_module,
+        pipeline::PipelineOptions,
+    };
+
+    #[test]
+    fn store_and_load() {
+        let context = initialize_context();
+        let options = PipelineOptions::default();
+        let dialect_module = build_dialect_module(&context);
+        load_irdl_dialects(&dialect_module);
+
+        let mut core_module = build_core_module(&context);
+        let mut pattern_module = build_pattern_module(&context);
+        canonicalize(&context, &mut pattern_module, &options);
+
+        let key = CacheKey::new(
+            &[&dialect_module, &core_module, &pattern_module],
+            LLVM_PIPELINE,
+        );
+        let directory = env::temp_dir().join(format!("dialect-rust-cache-{}", process::id()));
+        let cache = CompilationCache::new(&directory).unwrap();
+        assert!(cache.load(&key).unwrap().is_none());
+
+        convert_pdl_to_pdl_interop(&context, &mut pattern_module, &options);
+        apply_pdl_patterns(&core_module, &pattern_module);
+        convert_to_llvm(&context, &mut core_module, &options);
+        cache.store(&key, &core_module).unwrap();
+
+        let library = cache.load(&key).unwrap().unwrap();
+        assert_eq!(library.execute_entrypoint(10, 7).unwrap(), 4);
+
+        fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn corrupt_library() {
+        let context = initialize_context();
+        let dialect_module = build_dialect_module(&context);
+
+        let key = CacheKey::new(&[&dialect_module], LLVM_PIPELINE);
+        let directory =
+            env::temp_dir().join(format!("dialect-rust-corrupt-cache-{}", process::id()));
+        let cache = CompilationCache::new(&directory).unwrap();
+        fs::write(cache.library_path(&key), b"not a library").unwrap();
+        assert!(cache.load(&key).is_err());
+
+        fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn wrong_entrypoint_type() {
+        let context = initialize_context();
+        let mut module = Module::parse(
+            &context,
+            r#"
+            module {
+              func.func @entrypoint(%a: i64, %b: i64) -> i64 {
+                %0 = arith.addi %a, %b : i64
+                return %0 : i64
+              }
+            }"#,
+        )
+        .unwrap();
+        convert_to_llvm(&context, &mut module, &PipelineOptions::default());
+
+        let key = CacheKey::new(&[&module], LLVM_PIPELINE);
+        let directory =
+            env::temp_dir().join(format!("dialect-rust-entrypoint-cache-{}", process::id()));
+        let cache = CompilationCache::new(&directory).unwrap();
+        cache.store(&key, &module).unwrap();
+
+        // The library has an `entrypoint` symbol, but calling it with `i32`s
+        // would be undefined behavior.
+        let library = cache.load(&key).unwrap().unwrap();
+        assert!(matches!(
+            library.execute_entrypoint(10, 7),
+            Err(Error::Entrypoint(_))
+        ));
+
+        fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn key_depends_on_pipeline() {
+        let context = initialize_context();
+        let dialect_module = build_dialect_module(&context);
+
+        let key = CacheKey::new(&[&dialect_module], LLVM_PIPELINE);
+        assert_eq!(key, CacheKey::new(&[&dialect_module], LLVM_PIPELINE));
+        assert_ne!(key, CacheKey::new(&[&dialect_module], "builtin.module()"));
+    }
+}
@@ -1,17 +1,26 @@
+use std::collections::HashMap;
+
 use melior::{
     Context,
     dialect::func,
     helpers::BuiltinBlockExt,
     ir::{
-        Attribute, Block, BlockLike, Identifier, Location, Module, Region, Type,
-        attribute::{StringAttribute, TypeAttribute},
-        operation::OperationBuilder,
+        Attribute, Block, BlockLike, BlockRef, Identifier, Module, Region, RegionLike, Type, Value,
+        ValueLike,
+        attribute::{DenseI32ArrayAttribute, StringAttribute, TypeAttribute},
+        operation::{Operation, OperationBuilder, OperationLike, OperationRef},
         r#type::{FunctionType, IntegerType},
     },
 };
+use mlir_sys::{
+    MlirOperation, MlirValue, mlirOperationClone, mlirOperationDestroy, mlirOperationSetOperand,
+    mlirShapedTypeGetElementType, mlirValueReplaceAllUsesOfWith,
+};
+
+use crate::{caller_location, parse_module, walk::walk};
 
 pub fn load_core_module(ctx: &'_ Context) -> Module<'_> {
-    Module::parse(
+    parse_module(
         ctx,
         r#"
         module {
@@ -20,31 +29,39 @@ pub fn load_core_module(ctx: &'_ Context) -> Module<'_> {
             return %0 : i32
           }
         }"#,
+        "load_core_module",
     )
     .unwrap()
 }
 
+/// Builds the core module.
+///
+/// Each operation is located at the line that builds it, so that diagnostics
+/// (and debug info) point back to this function.
 pub fn build_core_module(ctx: &'_ Context) -> Module<'_> {
-    let location = Location::unknown(ctx);
-    let module = Module::new(location);
+    let module = Module::new(caller_location(ctx));
 
     let u32_type: Type<'_> = IntegerType::new(ctx, 32).into();
 
+    let function_location = caller_location(ctx);
     module.body().append_operation(func::func(
         ctx,
         StringAttribute::new(ctx, "entrypoint"),
         TypeAttribute::new(FunctionType::new(ctx, &[u32_type, u32_type], &[u32_type]).into()),
         {
             let region = Region::new();
-            let block =
-                region.append_block(Block::new(&[(u32_type, location), (u32_type, location)]));
+            let argument_location = caller_location(ctx);
+            let block = region.append_block(Block::new(&[
+                (u32_type, argument_location),
+                (u32_type, argument_location),
+            ]));
 
             let v1 = block.arg(0).unwrap();
             let v2 = block.arg(1).unwrap();
 
             let result = block
                 .append_op_result(
-                    OperationBuilder::new("felt.add", location)
+                    OperationBuilder::new("felt.add", caller_location(ctx))
                         .add_operands(&[v1, v2])
                         .add_results(&[u32_type])
                         .build()
@@ -52,7 +69,7 @@ pub fn build_core_module(ctx: &'_ Context) -> Module<'_> {
                 )
                 .unwrap();
 
-            block.append_operation(func::r#return(&[result], location));
+            block.append_operation(func::r#return(&[result], caller_location(ctx)));
 
             region
         },
@@ -60,20 +77,307 @@ pub fn build_core_module(ctx: &'_ Context) -> Module<'_> {
             Identifier::new(ctx, "llvm.emit_c_interface"),
             Attribute::unit(ctx),
         )],
-        location,
+        function_location,
     ));
 
     module
 }
 
+/// Builds a `felt.reduce`, which folds `values` into `init`. `body` appends
+/// the operations of the body to its block, from the accumulator and a value,
+/// and returns the next accumulator.
+///
+/// IRDL has no traits in MLIR 19, so felt operations can't be terminators.
+/// Instead, the body ends with a `gpu.yield`, which accepts any parent. The
+/// GPU dialect is only borrowed for its terminator: `lower_reductions` removes
+/// it along with the `felt.reduce`.
+pub fn build_reduce<'c, 'v>(
+    ctx: &'c Context,
+    init: Value<'c, 'v>,
+    values: &[Value<'c, 'v>],
+    body: impl for<'a> FnOnce(&'a Block<'c>, Value<'c, 'a>, Value<'c, 'a>) -> Value<'c, 'a>,
+) -> Operation<'c> {
+    let u32_type: Type<'_> = IntegerType::new(ctx, 32).into();
+
+    OperationBuilder::new("felt.reduce", caller_location(ctx))
+        .add_operands(&[init])
+        .add_operands(values)
+        .add_results(&[u32_type])
+        .add_regions([{
+            let region = Region::new();
+            let argument_location = caller_location(ctx);
+            let block = region.append_block(Block::new(&[
+                (u32_type, argument_location),
+                (u32_type, argument_location),
+            ]));
+
+            let accumulator = body(&block, block.arg(0).unwrap(), block.arg(1).unwrap());
+            block.append_operation(
+                OperationBuilder::new("gpu.yield", caller_location(ctx))
+                    .add_operands(&[accumulator])
+                    .build()
+                    .unwrap(),
+            );
+
+            region
+        }])
+        .build()
+        .unwrap()
+}
+
+/// Lowers every `felt.reduce` of the module by unrolling it.
+///
+/// The operands are known when the module is built, so no loop is needed:
+/// the body is cloned before the `felt.reduce` for each value, with the
+/// accumulator and the value in place of its arguments, and the operand of
+/// its `gpu.yield` is the next accumulator. The felt operations of the clones
+/// are then lowered by the patterns, like any other. PDL patterns can't clone
+/// regions, so this must run before they are applied.
+pub fn lower_reductions(module: &Module) {
+    let mut reductions = Vec::new();
+    walk(module.as_operation(), &mut |operation| {
+        if operation.name().as_string_ref().as_str() == Ok("felt.reduce") {
+            reductions.push(operation.to_raw());
+        }
+    });
+
+    // The walk is in pre-order, so nested reductions are unrolled before the
+    // bodies that contain them are cloned.
+    for reduction in reductions.into_iter().rev() {
+        let reduction = unsafe { OperationRef::from_raw(reduction) };
+        let block = reduction.block().unwrap();
+        let body = reduction.region(0).unwrap().first_block().unwrap();
+
+        let mut accumulator = reduction.operand(0).unwrap().to_raw();
+        for index in 1..reduction.operand_count() {
+            let values = HashMap::from([
+                (body.argument(0).unwrap().to_raw().ptr as usize, accumulator),
+                (
+                    body.argument(1).unwrap().to_raw().ptr as usize,
+                    reduction.operand(index).unwrap().to_raw(),
+                ),
+            ]);
+            accumulator = clone_body(body, values, |clone| {
+                block.insert_operation_before(reduction, clone).to_raw()
+            });
+        }
+
+        unsafe {
+            mlirValueReplaceAllUsesOfWith(reduction.result(0).unwrap().to_raw(), accumulator);
+            mlirOperationDestroy(reduction.to_raw());
+        }
+    }
+}
+
+/// Builds a `felt.map`, which applies its body element-wise over `inputs`,
+/// and writes the results to `output`. Every buffer must be a memref of the
+/// same type. `body` appends the operations of the body to its block, from an
+/// element of every input, and returns the output element.
+///
+/// Like `build_reduce`, the body ends with a `gpu.yield`.
+pub fn build_map<'c, 'v>(
+    ctx: &'c Context,
+    inputs: &[Value<'c, 'v>],
+    output: Value<'c, 'v>,
+    body: impl for<'a> FnOnce(&'a Block<'c>, &[Value<'c, 'a>]) -> Value<'c, 'a>,
+) -> Operation<'c> {
+    let u32_type: Type<'_> = IntegerType::new(ctx, 32).into();
+
+    OperationBuilder::new("felt.map", caller_location(ctx))
+        .add_operands(inputs)
+        .add_operands(&[output])
+        .add_regions([{
+            let region = Region::new();
+            let argument_location = caller_location(ctx);
+            let block = region.append_block(Block::new(&vec![
+                (u32_type, argument_location);
+                inputs.len()
+            ]));
+
+            let elements = (0..inputs.len())
+                .map(|index| block.argument(index).unwrap().into())
+                .collect::<Vec<_>>();
+            let element = body(&block, &elements);
+            block.append_operation(
+                OperationBuilder::new("gpu.yield", caller_location(ctx))
+                    .add_operands(&[element])
+                    .build()
+                    .unwrap(),
+            );
+
+            region
+        }])
+        .build()
+        .unwrap()
+}
+
+/// Lowers every `felt.map` of the module to a `linalg.generic`, like the ones
+/// built by `dsl::FeltFn::map`.
+///
+/// The body is cloned into the payload of the `linalg.generic`, which also
+/// receives the element of the output, and yields the value of the
+/// `gpu.yield` with a `linalg.yield`. Like `lower_reductions`, this must run
+/// before the patterns are applied, which then lower the felt operations of
+/// the payload. A `felt.map` whose body doesn't take an element of every input
+/// is left in the module, for the legality checks to report.
+pub fn lower_maps(module: &Module) {
+    let context = module.context();
+
+    let mut maps = Vec::new();
+    walk(module.as_operation(), &mut |operation| {
+        if operation.name().as_string_ref().as_str() == Ok("felt.map") {
+            maps.push(operation.to_raw());
+        }
+    });
+
+    for map in maps.into_iter().rev() {
+        let map = unsafe { OperationRef::from_raw(map) };
+        let body = map.region(0).unwrap().first_block().unwrap();
+        let input_count = map.operand_count() - 1;
+        if body.argument_count() != input_count {
+            continue;
+        }
+
+        let location = map.location();
+        let output_type = map.operand(input_count).unwrap().r#type();
+        let element_type =
+            unsafe { Type::from_raw(mlirShapedTypeGetElementType(output_type.to_raw())) };
+        let arguments = (0..input_count)
+            .map(|index| body.argument(index).unwrap().r#type())
+            .chain([element_type])
+            .map(|r#type| (r#type, location))
+            .collect::<Vec<_>>();
+
+        let payload_region = Region::new();
+        let payload_block = payload_region.append_block(Block::new(&arguments));
+        let values = (0..input_count)
+            .map(|index| {
+                (
+                    body.argument(index).unwrap().to_raw().ptr as usize,
+                    payload_block.argument(index).unwrap().to_raw(),
+                )
+            })
+            .collect();
+        let element = clone_body(body, values, |clone| {
+            payload_block.append_operation(clone).to_raw()
+        });
+        payload_block.append_operation(
+            OperationBuilder::new("linalg.yield", location)
+                .add_operands(&[unsafe { Value::from_raw(element) }])
+                .build()
+                .unwrap(),
+        );
+
+        let buffers = (0..map.operand_count())
+            .map(|index| map.operand(index).unwrap())
+            .collect::<Vec<_>>();
+        let generic = OperationBuilder::new("linalg.generic", location)
+            .add_operands(&buffers)
+            .add_attributes(&[
+                (
+                    Identifier::new(&context, "indexing_maps"),
+                    Attribute::parse(
+                        &context,
+                        &format!(
+                            "[{}]",
+                            vec!["affine_map<(d0) -> (d0)>"; input_count + 1].join(", ")
+                        ),
+                    )
+                    .unwrap(),
+                ),
+                (
+                    Identifier::new(&context, "iterator_types"),
+                    Attribute::parse(&context, "[#linalg.iterator_type<parallel>]").unwrap(),
+                ),
+                (
+                    Identifier::new(&context, "operandSegmentSizes"),
+                    DenseI32ArrayAttribute::new(&context, &[input_count as i32, 1]).into(),
+                ),
+            ])
+            .add_regions([payload_region])
+            .build()
+            .unwrap();
+
+        map.block().unwrap().insert_operation_before(map, generic);
+        unsafe { mlirOperationDestroy(map.to_raw()) };
+    }
+}
+
+/// Clones the operations of a `felt.reduce` or `felt.map` body, except its
+/// `gpu.yield`, with `insert`. `values` maps the arguments of the body to the
+/// values that replace them in the clones. Returns the value that replaces the
+/// operand of the `gpu.yield`.
+fn clone_body<'c>(
+    body: BlockRef<'c, '_>,
+    mut values: HashMap<usize, MlirValue>,
+    mut insert: impl FnMut(Operation<'c>) -> MlirOperation,
+) -> MlirValue {
+    let mapped = |values: &HashMap<usize, MlirValue>, value: MlirValue| {
+        values.get(&(value.ptr as usize)).copied().unwrap_or(value)
+    };
+
+    let mut operation = body.first_operation();
+    while let Some(current) = operation {
+        if current.next_in_block().is_none() {
+            return mapped(&values, current.operand(0).unwrap().to_raw());
+        }
+
+        let clone = unsafe {
+            OperationRef::from_raw(insert(Operation::from_raw(mlirOperationClone(
+                current.to_raw(),
+            ))))
+        };
+        // Operations nested in the clone can also use the values of the body.
+        walk(clone, &mut |nested| {
+            for operand in 0..nested.operand_count() {
+                let value = nested.operand(operand).unwrap().to_raw();
+                let replacement = mapped(&values, value);
+                if replacement.ptr != value.ptr {
+                    unsafe {
+                        mlirOperationSetOperand(nested.to_raw(), operand as isize, replacement)
+                    };
+                }
+            }
+        });
+        for result in 0..current.result_count() {
+            values.insert(
+                current.result(result).unwrap().to_raw().ptr as usize,
+                clone.result(result).unwrap().to_raw(),
+            );
+        }
+
+        operation = current.next_in_block();
+    }
+
+    panic!("the body doesn't end with a gpu.yield")
+}
+
 #[cfg(test)]
 mod test {
-    use melior::utility::load_irdl_dialects;
+    use melior::{
+        dialect::func,
+        helpers::BuiltinBlockExt,
+        ir::{
+            Block, BlockLike, Module, Region, Type,
+            attribute::{StringAttribute, TypeAttribute},
+            operation::{OperationBuilder, OperationLike, OperationPrintingFlags},
+            r#type::{FunctionType, IntegerType},
+        },
+        utility::load_irdl_dialects,
+    };
 
     use crate::{
-        core::{build_core_module, load_core_module},
-        initialize_context,
+        caller_location,
+        core::{
+            build_core_module, build_map, build_reduce, load_core_module, lower_maps,
+            lower_reductions,
+        },
+        execute_entrypoint, execute_map, initialize_context,
         irdl::build_dialect_module,
+        parse_module,
+        pdl::build_pattern_module,
+        pipeline::PipelineOptions,
+        testing::{felt_context, lower},
     };
 
     #[test]
@@ -90,4 +394,228 @@ mod test {
             loaded_module.as_operation().to_string()
         )
     }
+
+    #[test]
+    fn source_locations() {
+        let context = initialize_context();
+        load_irdl_dialects(&build_dialect_module(&context));
+
+        let flags = OperationPrintingFlags::new().enable_debug_info(true, false);
+
+        let builded_module = build_core_module(&context);
+        let builded_source = builded_module
+            .as_operation()
+            .to_string_with_flags(flags)
+            .unwrap();
+        assert!(builded_source.contains(&format!("loc(\"{}\":", file!())));
+
+        let loaded_module = load_core_module(&context);
+        let loaded_source = loaded_module
+            .as_operation()
+            .to_string_with_flags(flags)
+            .unwrap();
+        assert!(loaded_source.contains("loc(\"load_core_module\":4:13)"));
+    }
+
+    #[test]
+    fn reduce_operation() {
+        let context = initialize_context();
+        load_irdl_dialects(&build_dialect_module(&context));
+
+        let u32_type: Type<'_> = IntegerType::new(&context, 32).into();
+        let module = Module::new(caller_location(&context));
+        module.body().append_operation(func::func(
+            &context,
+            StringAttribute::new(&context, "sum_of_squares"),
+            TypeAttribute::new(
+                FunctionType::new(&context, &[u32_type, u32_type], &[u32_type]).into(),
+            ),
+            {
+                let region = Region::new();
+                let location = caller_location(&context);
+                let block =
+                    region.append_block(Block::new(&[(u32_type, location), (u32_type, location)]));
+
+                let a = block.arg(0).unwrap();
+                let b = block.arg(1).unwrap();
+                let result = block
+                    .append_op_result(build_reduce(
+                        &context,
+                        a,
+                        &[a, b],
+                        |block, accumulator, value| {
+                            let felt = |name, operands: &[_]| {
+                                block
+                                    .append_op_result(
+                                        OperationBuilder::new(name, caller_location(&context))
+                                            .add_operands(operands)
+                                            .add_results(&[u32_type])
+                                            .build()
+                                            .unwrap(),
+                                    )
+                                    .unwrap()
+                            };
+                            let square = felt("felt.mul", &[value, value]);
+                            felt("felt.add", &[accumulator, square])
+                        },
+                    ))
+                    .unwrap();
+                block.append_operation(func::r#return(&[result], caller_location(&context)));
+
+                region
+            },
+            &[],
+            caller_location(&context),
+        ));
+
+        assert!(module.as_operation().verify());
+        let source = module.as_operation().to_string();
+        assert!(
+            source.contains(r#""felt.reduce"(%arg0, %arg0, %arg1)"#),
+            "{source}"
+        );
+        assert!(source.contains("gpu.yield"), "{source}");
+    }
+    #[test]
+    fn unroll_reductions() {
+        let context = felt_context();
+
+        let mut module = parse_module(
+            &context,
+            r#"
+            module {
+              func.func @entrypoint(%a: i32, %b: i32) -> i32 attributes { llvm.emit_c_interface } {
+                %0 = "felt.reduce"(%a, %a, %b) ({
+                ^bb0(%acc: i32, %x: i32):
+                  %1 = "felt.mul"(%x, %x) : (i32, i32) -> i32
+                  %2 = "felt.reduce"(%acc, %1, %x) ({
+                  ^bb0(%inner: i32, %y: i32):
+                    %3 = "felt.add"(%inner, %y) : (i32, i32) -> i32
+                    %4 = "felt.add"(%3, %x) : (i32, i32) -> i32
+                    gpu.yield %4 : i32
+                  }) : (i32, i32, i32) -> i32
+                  gpu.yield %2 : i32
+                }) : (i32, i32, i32) -> i32
+                return %0 : i32
+              }
+            }"#,
+            "unroll_reductions",
+        )
+        .unwrap();
+
+        lower_reductions(&module);
+        assert!(module.as_operation().verify());
+        let source = module.as_operation().to_string();
+        assert!(!source.contains("felt.reduce"), "{source}");
+        assert!(!source.contains("gpu.yield"), "{source}");
+
+        lower(
+            &context,
+            &mut module,
+            build_pattern_module(&context),
+            &PipelineOptions::default(),
+        );
+
+        // Each value adds x^2 + 3x, so 4 + 16 + 12 + 81 + 27 = 140 = 10.
+        assert_eq!(execute_entrypoint(&module, 4, 9), 10);
+    }
+
+    #[test]
+    fn map_operation() {
+        let context = felt_context();
+
+        let u32_type: Type<'_> = IntegerType::new(&context, 32).into();
+        let memref_type = Type::parse(&context, "memref<?xi32>").unwrap();
+        let module = Module::new(caller_location(&context));
+        module.body().append_operation(func::func(
+            &context,
+            StringAttribute::new(&context, "squares"),
+            TypeAttribute::new(
+                FunctionType::new(&context, &[memref_type, memref_type], &[]).into(),
+            ),
+            {
+                let region = Region::new();
+                let location = caller_location(&context);
+                let block = region.append_block(Block::new(&[
+                    (memref_type, location),
+                    (memref_type, location),
+                ]));
+
+                block.append_operation(build_map(
+                    &context,
+                    &[block.arg(0).unwrap()],
+                    block.arg(1).unwrap(),
+                    |block, elements| {
+                        block
+                            .append_op_result(
+                                OperationBuilder::new("felt.mul", caller_location(&context))
+                                    .add_operands(&[elements[0], elements[0]])
+                                    .add_results(&[u32_type])
+                                    .build()
+                                    .unwrap(),
+                            )
+                            .unwrap()
+                    },
+                ));
+                block.append_operation(func::r#return(&[], caller_location(&context)));
+
+                region
+            },
+            &[],
+            caller_location(&context),
+        ));
+
+        assert!(module.as_operation().verify());
+        let source = module.as_operation().to_string();
+        assert!(source.contains(r#""felt.map"(%arg0, %arg1)"#), "{source}");
+        assert!(source.contains("gpu.yield"), "{source}");
+    }
+
+    #[test]
+    fn lower_maps_to_linalg() {
+        let context = felt_context();
+
+        let mut module = parse_module(
+            &context,
+            r#"
+            module {
+              func.func @square_add(%x: memref<?xi32>, %y: memref<?xi32>, %out: memref<?xi32>)
+                  attributes { llvm.emit_c_interface } {
+                "felt.map"(%x, %y, %out) ({
+                ^bb0(%a: i32, %b: i32):
+                  %0 = "felt.mul"(%a, %a) : (i32, i32) -> i32
+                  %1 = "felt.add"(%0, %b) : (i32, i32) -> i32
+                  gpu.yield %1 : i32
+                }) : (memref<?xi32>, memref<?xi32>, memref<?xi32>) -> ()
+                return
+              }
+            }"#,
+            "lower_maps_to_linalg",
+        )
+        .unwrap();
+
+        lower_maps(&module);
+        assert!(module.as_operation().verify());
+        let source = module.as_operation().to_string();
+        assert!(!source.contains("felt.map"), "{source}");
+        assert!(!source.contains("gpu.yield"), "{source}");
+        assert!(source.contains("linalg.generic"), "{source}");
+
+        lower(
+            &context,
+            &mut module,
+            build_pattern_module(&context),
+            &PipelineOptions::default(),
+        );
+
+        // 1 + 4, 4 + 5 and 9 + 6 = 15 = 2.
+        let mut output = [0; 3];
+        execute_map(
+            &module,
+            "square_add",
+            &[&[1, 2, 3], &[4, 5, 6]],
+            &mut output,
+        );
+        assert_eq!(output, [5, 9, 2]);
+    }
 }
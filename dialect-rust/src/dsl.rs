@@ -0,0 +1,735 @@
+//! A Rust-embedded DSL for building felt functions.
+//!
+//! ```ignore
+//! let f = FeltFn::new(ctx, "entrypoint", 2, |b, [x, y]| x * y + b.constant(5));
+//! module.body().append_operation(f.into());
+//! ```
+//!
+//! Each arithmetic operator appends the corresponding felt operation to the
+//! function body, located at the expression that used it.
+
+use std::{
+    array,
+    marker::PhantomData,
+    ops::{Add, Div, Mul, Neg, Sub},
+};
+
+use melior::{
+    Context,
+    dialect::{
+        arith::{self, CmpiPredicate},
+        func, scf,
+    },
+    ir::{
+        Attribute, Block, BlockLike, BlockRef, Identifier, Location, Region, Type, Value,
+        ValueLike,
+        attribute::{DenseI32ArrayAttribute, IntegerAttribute, StringAttribute, TypeAttribute},
+        operation::{Operation, OperationBuilder, OperationLike},
+        r#type::{FunctionType, IntegerType},
+    },
+};
+use mlir_sys::MlirBlock;
+
+use crate::{caller_location, pdl::MODULUS};
+
+/// A `func.func` over felt values, built from a Rust closure.
+pub struct FeltFn<'c> {
+    operation: Operation<'c>,
+}
+
+impl<'c> FeltFn<'c> {
+    /// Builds a function with `parameter_count` felt parameters, and a single
+    /// felt result. The closure receives the parameters, and returns the
+    /// result.
+    ///
+    /// Like the core module, the function has the `llvm.emit_c_interface`
+    /// attribute.
+    ///
+    /// # Panics
+    ///
+    /// If `parameter_count` doesn't match the number of parameters taken by
+    /// the closure.
+    #[track_caller]
+    pub fn new<const N: usize, F>(
+        ctx: &'c Context,
+        name: &str,
+        parameter_count: usize,
+        body: F,
+    ) -> Self
+    where
+        F: for<'b> FnOnce(&'b FeltBuilder<'c, 'c>, [FeltValue<'c, 'b>; N]) -> FeltValue<'c, 'b>,
+    {
+        assert_eq!(
+            parameter_count, N,
+            "the closure of {name} takes {N} parameters"
+        );
+
+        let location = caller_location(ctx);
+        let felt_type: Type<'c> = IntegerType::new(ctx, 32).into();
+
+        let region = Region::new();
+        let block = region.append_block(Block::new(&[(felt_type, location); N]));
+
+        FeltBuilder::build(ctx, &block, felt_type, 0, |builder| {
+            let parameters = array::from_fn(|index| builder.argument(index));
+            let result = body(builder, parameters);
+            builder
+                .scope
+                .append_operation(func::r#return(&[result.value], location));
+        });
+
+        FeltFn::function(ctx, name, &[felt_type; N], &[felt_type], region, location)
+    }
+
+    /// Builds a function that applies the closure element-wise over
+    /// `memref<?xi32>` buffers. The function takes `parameter_count` input
+    /// buffers, followed by the output buffer, which all have the same size.
+    ///
+    /// The body is a `linalg.generic` with parallel iterators, which
+    /// `convert_to_llvm` lowers to a loop. Use `execute_map` to call the
+    /// function on Rust slices.
+    ///
+    /// # Panics
+    ///
+    /// If `parameter_count` doesn't match the number of parameters taken by
+    /// the closure.
+    #[track_caller]
+    pub fn map<const N: usize, F>(
+        ctx: &'c Context,
+        name: &str,
+        parameter_count: usize,
+        body: F,
+    ) -> Self
+    where
+        F: for<'b> FnOnce(&'b FeltBuilder<'c, 'c>, [FeltValue<'c, 'b>; N]) -> FeltValue<'c, 'b>,
+    {
+        assert_eq!(
+            parameter_count, N,
+            "the closure of {name} takes {N} parameters"
+        );
+
+        let location = caller_location(ctx);
+        let felt_type: Type<'c> = IntegerType::new(ctx, 32).into();
+        let memref_type = Type::parse(ctx, "memref<?xi32>").unwrap();
+
+        // The payload of the `linalg.generic` receives an element of every
+        // buffer, including the output, and yields the output element.
+        let payload_region = Region::new();
+        let payload_block =
+            payload_region.append_block(Block::new(&vec![(felt_type, location); N + 1]));
+        FeltBuilder::build(ctx, &payload_block, felt_type, 0, |builder| {
+            let parameters = array::from_fn(|index| builder.argument(index));
+            let result = body(builder, parameters);
+            builder.scope.append_operation(
+                OperationBuilder::new("linalg.yield", location)
+                    .add_operands(&[result.value])
+                    .build()
+                    .unwrap(),
+            );
+        });
+
+        let region = Region::new();
+        let block = region.append_block(Block::new(&vec![(memref_type, location); N + 1]));
+        let buffers = (0..=N)
+            .map(|index| block.argument(index).unwrap().into())
+            .collect::<Vec<Value>>();
+        block.append_operation(
+            OperationBuilder::new("linalg.generic", location)
+                .add_operands(&buffers)
+                .add_attributes(&[
+                    (
+                        Identifier::new(ctx, "indexing_maps"),
+                        Attribute::parse(
+                            ctx,
+                            &format!("[{}]", vec!["affine_map<(d0) -> (d0)>"; N + 1].join(", ")),
+                        )
+                        .unwrap(),
+                    ),
+                    (
+                        Identifier::new(ctx, "iterator_types"),
+                        Attribute::parse(ctx, "[#linalg.iterator_type<parallel>]").unwrap(),
+                    ),
+                    (
+                        Identifier::new(ctx, "operandSegmentSizes"),
+                        DenseI32ArrayAttribute::new(ctx, &[N as i32, 1]).into(),
+                    ),
+                ])
+                .add_regions([payload_region])
+                .build()
+                .unwrap(),
+        );
+        block.append_operation(func::r#return(&[], location));
+
+        FeltFn::function(ctx, name, &vec![memref_type; N + 1], &[], region, location)
+    }
+
+    /// Builds the `func.func`, with the `llvm.emit_c_interface` attribute.
+    fn function(
+        ctx: &'c Context,
+        name: &str,
+        inputs: &[Type<'c>],
+        results: &[Type<'c>],
+        region: Region<'c>,
+        location: Location<'c>,
+    ) -> Self {
+        FeltFn {
+            operation: func::func(
+                ctx,
+                StringAttribute::new(ctx, name),
+                TypeAttribute::new(FunctionType::new(ctx, inputs, results).into()),
+                region,
+                &[(
+                    Identifier::new(ctx, "llvm.emit_c_interface"),
+                    Attribute::unit(ctx),
+                )],
+                location,
+            ),
+        }
+    }
+}
+
+impl<'c> From<FeltFn<'c>> for Operation<'c> {
+    fn from(function: FeltFn<'c>) -> Self {
+        function.operation
+    }
+}
+
+/// Appends operations to a block of a `FeltFn`.
+///
+/// The function body, and every body of the control flow builders
+/// (`for_range`, `while_loop` and `if_else`), get a builder of their own. The
+/// values of a body borrow its builder, so they can't outlive the closure that
+/// builds it, and can't be used outside of the region that defines them. `'a`
+/// is the lifetime of the values of the enclosing body, which can be used in
+/// this one.
+///
+/// The arithmetic operators append to the innermost body of their operands.
+/// Operations over values of an enclosing body are appended to that body,
+/// before the operation whose body is being built.
+pub struct FeltBuilder<'c, 'a> {
+    scope: Scope<'c>,
+    _enclosing: PhantomData<&'a ()>,
+}
+
+/// The block that a `FeltBuilder` appends to.
+struct Scope<'c> {
+    ctx: &'c Context,
+    // The builder only exists while the function is being built, and its
+    // block belongs to the function body, so the block always outlives the
+    // builder.
+    block: MlirBlock,
+    felt_type: Type<'c>,
+    /// The number of bodies enclosing the block.
+    depth: usize,
+}
+
+impl<'c, 'a> FeltBuilder<'c, 'a> {
+    /// Runs `f` with a builder appending to `block`.
+    fn build<R>(
+        ctx: &'c Context,
+        block: &Block<'c>,
+        felt_type: Type<'c>,
+        depth: usize,
+        f: impl FnOnce(&Self) -> R,
+    ) -> R {
+        f(&FeltBuilder {
+            scope: Scope {
+                ctx,
+                block: block.to_raw(),
+                felt_type,
+                depth,
+            },
+            _enclosing: PhantomData,
+        })
+    }
+
+    /// Runs `f` with a builder appending to `block`, the body of an operation
+    /// appended by this builder.
+    fn nested<'s, R>(&'s self, block: &Block<'c>, f: impl FnOnce(&FeltBuilder<'c, 's>) -> R) -> R {
+        FeltBuilder::build(
+            self.scope.ctx,
+            block,
+            self.scope.felt_type,
+            self.scope.depth + 1,
+            f,
+        )
+    }
+
+    pub fn context(&self) -> &'c Context {
+        self.scope.ctx
+    }
+
+    /// The block of the builder, to append operations that the DSL doesn't
+    /// cover.
+    pub fn block(&self) -> BlockRef<'c, '_> {
+        self.scope.block()
+    }
+
+    /// Appends a constant. The value is reduced, so that every value is in
+    /// canonical form.
+    #[track_caller]
+    pub fn constant(&self, value: u32) -> FeltValue<'c, '_> {
+        let operation = arith::constant(
+            self.scope.ctx,
+            IntegerAttribute::new(self.scope.felt_type, (value % MODULUS).into()).into(),
+            caller_location(self.scope.ctx),
+        );
+        self.wrap(self.scope.append_operation(operation)[0])
+    }
+
+    /// Wraps a value built outside of the DSL. It must be a felt value
+    /// defined in the block of the builder, or in an enclosing one.
+    pub fn wrap<'s>(&'s self, value: Value<'c, 's>) -> FeltValue<'c, 's> {
+        FeltValue {
+            scope: &self.scope,
+            value,
+        }
+    }
+
+    /// Builds an `scf.for` loop, iterating from `lower` (inclusive) to
+    /// `upper` (exclusive). The closure receives the builder of the body, the
+    /// induction variable and the current values, and returns the values for
+    /// the next iteration. Returns the values after the last iteration.
+    #[track_caller]
+    pub fn for_range<'s, const N: usize>(
+        &'s self,
+        lower: FeltValue<'c, 's>,
+        upper: FeltValue<'c, 's>,
+        initial: [FeltValue<'c, 's>; N],
+        body: impl for<'b> FnOnce(
+            &'b FeltBuilder<'c, 's>,
+            FeltValue<'c, 'b>,
+            [FeltValue<'c, 'b>; N],
+        ) -> [FeltValue<'c, 'b>; N],
+    ) -> [FeltValue<'c, 's>; N] {
+        let location = caller_location(self.scope.ctx);
+        let index_type = Type::index(self.scope.ctx);
+        let felt_type = self.scope.felt_type;
+
+        // The bounds of an `scf.for` must be indices.
+        let [lower, upper] = [lower, upper].map(|bound| self.scope.cast(bound.value, index_type));
+        let step = self.scope.append_operation(arith::constant(
+            self.scope.ctx,
+            IntegerAttribute::new(index_type, 1).into(),
+            location,
+        ))[0];
+
+        let region = Region::new();
+        let block = region.append_block(Block::new(
+            &[(index_type, location)]
+                .into_iter()
+                .chain([(felt_type, location); N])
+                .collect::<Vec<_>>(),
+        ));
+        self.nested(&block, |builder| {
+            let induction = builder.wrap(builder.scope.cast(builder.argument(0).value, felt_type));
+            let values = array::from_fn(|index| builder.argument(index + 1));
+            let values = body(builder, induction, values);
+            builder
+                .scope
+                .append_operation(scf::r#yield(&values.map(|value| value.value), location));
+        });
+
+        let results = self.scope.append_operation(
+            OperationBuilder::new("scf.for", location)
+                .add_operands(&[lower, upper, step])
+                .add_operands(&initial.map(|value| value.value))
+                .add_results(&[felt_type; N])
+                .add_regions([region])
+                .build()
+                .unwrap(),
+        );
+        array::from_fn(|index| self.wrap(results[index]))
+    }
+
+    /// Builds an `scf.while` loop. Before every iteration, `condition`
+    /// receives the current values, and the loop ends if it is false.
+    /// Otherwise, `body` receives the current values, and returns the values
+    /// for the next iteration. Both closures also receive the builder of
+    /// their body. Returns the values after the last iteration.
+    #[track_caller]
+    pub fn while_loop<'s, const N: usize>(
+        &'s self,
+        initial: [FeltValue<'c, 's>; N],
+        condition: impl for<'b> FnOnce(
+            &'b FeltBuilder<'c, 's>,
+            [FeltValue<'c, 'b>; N],
+        ) -> FeltCondition<'c, 'b>,
+        body: impl for<'b> FnOnce(
+            &'b FeltBuilder<'c, 's>,
+            [FeltValue<'c, 'b>; N],
+        ) -> [FeltValue<'c, 'b>; N],
+    ) -> [FeltValue<'c, 's>; N] {
+        let location = caller_location(self.scope.ctx);
+        let felt_type = self.scope.felt_type;
+
+        let before_region = Region::new();
+        let before_block = before_region.append_block(Block::new(&[(felt_type, location); N]));
+        self.nested(&before_block, |builder| {
+            let values = array::from_fn(|index| builder.argument(index));
+            let condition = condition(builder, values);
+            builder.scope.append_operation(
+                OperationBuilder::new("scf.condition", location)
+                    .add_operands(&[condition.value])
+                    .add_operands(&values.map(|value| value.value))
+                    .build()
+                    .unwrap(),
+            );
+        });
+
+        let after_region = Region::new();
+        let after_block = after_region.append_block(Block::new(&[(felt_type, location); N]));
+        self.nested(&after_block, |builder| {
+            let values = array::from_fn(|index| builder.argument(index));
+            let values = body(builder, values);
+            builder
+                .scope
+                .append_operation(scf::r#yield(&values.map(|value| value.value), location));
+        });
+
+        let results = self.scope.append_operation(
+            OperationBuilder::new("scf.while", location)
+                .add_operands(&initial.map(|value| value.value))
+                .add_results(&[felt_type; N])
+                .add_regions([before_region, after_region])
+                .build()
+                .unwrap(),
+        );
+        array::from_fn(|index| self.wrap(results[index]))
+    }
+
+    /// Builds an `scf.if`. Both branches receive the builder of their body,
+    /// and return the values of the result.
+    #[track_caller]
+    pub fn if_else<'s, const N: usize>(
+        &'s self,
+        condition: FeltCondition<'c, 's>,
+        then_branch: impl for<'b> FnOnce(&'b FeltBuilder<'c, 's>) -> [FeltValue<'c, 'b>; N],
+        else_branch: impl for<'b> FnOnce(&'b FeltBuilder<'c, 's>) -> [FeltValue<'c, 'b>; N],
+    ) -> [FeltValue<'c, 's>; N] {
+        let location = caller_location(self.scope.ctx);
+
+        let then_region = self.branch(location, then_branch);
+        let else_region = self.branch(location, else_branch);
+
+        let results = self.scope.append_operation(
+            OperationBuilder::new("scf.if", location)
+                .add_operands(&[condition.value])
+                .add_results(&[self.scope.felt_type; N])
+                .add_regions([then_region, else_region])
+                .build()
+                .unwrap(),
+        );
+        array::from_fn(|index| self.wrap(results[index]))
+    }
+
+    /// Builds a region with a single block, that yields the values returned
+    /// by `f`.
+    fn branch<'s, const N: usize>(
+        &'s self,
+        location: Location<'c>,
+        f: impl for<'b> FnOnce(&'b FeltBuilder<'c, 's>) -> [FeltValue<'c, 'b>; N],
+    ) -> Region<'c> {
+        let region = Region::new();
+        let block = region.append_block(Block::new(&[]));
+        self.nested(&block, |builder| {
+            let values = f(builder);
+            builder
+                .scope
+                .append_operation(scf::r#yield(&values.map(|value| value.value), location));
+        });
+        region
+    }
+
+    fn argument(&self, index: usize) -> FeltValue<'c, '_> {
+        let argument = self.block().argument(index).unwrap();
+        self.wrap(unsafe { Value::from_raw(argument.to_raw()) })
+    }
+}
+
+impl<'c> Scope<'c> {
+    fn block(&self) -> BlockRef<'c, '_> {
+        unsafe { BlockRef::from_raw(self.block) }
+    }
+
+    /// Returns the innermost of two scopes. The values of both must be
+    /// usable in it, so one encloses the other.
+    fn innermost<'s>(&'s self, other: &'s Self) -> &'s Self {
+        if other.depth > self.depth {
+            other
+        } else {
+            self
+        }
+    }
+
+    /// Appends an operation to the block, and returns its results.
+    fn append_operation(&self, operation: Operation<'c>) -> Vec<Value<'c, '_>> {
+        let operation = self.block().append_operation(operation);
+        (0..operation.result_count())
+            .map(|index| unsafe { Value::from_raw(operation.result(index).unwrap().to_raw()) })
+            .collect()
+    }
+
+    /// Casts between felt values and indices. Felt values are always in
+    /// canonical form, so they are cast as unsigned integers.
+    #[track_caller]
+    fn cast(&self, value: Value<'c, '_>, r#type: Type<'c>) -> Value<'c, '_> {
+        self.append_operation(
+            OperationBuilder::new("arith.index_castui", caller_location(self.ctx))
+                .add_operands(&[value])
+                .add_results(&[r#type])
+                .build()
+                .unwrap(),
+        )[0]
+    }
+
+    #[track_caller]
+    fn append(&self, name: &str, operands: &[Value<'c, '_>], r#type: Type<'c>) -> Value<'c, '_> {
+        self.append_operation(
+            OperationBuilder::new(name, caller_location(self.ctx))
+                .add_operands(operands)
+                .add_results(&[r#type])
+                .build()
+                .unwrap(),
+        )[0]
+    }
+}
+
+/// A felt value in the body of a `FeltFn`. The arithmetic operators append
+/// the corresponding felt operation.
+///
+/// Both operands of an operator must belong to the same function.
+#[derive(Clone, Copy)]
+pub struct FeltValue<'c, 'a> {
+    scope: &'a Scope<'c>,
+    value: Value<'c, 'a>,
+}
+
+impl<'c, 'a> FeltValue<'c, 'a> {
+    pub fn value(&self) -> Value<'c, 'a> {
+        self.value
+    }
+
+    #[track_caller]
+    pub fn equals(self, rhs: Self) -> FeltCondition<'c, 'a> {
+        self.compare(CmpiPredicate::Eq, rhs)
+    }
+
+    #[track_caller]
+    pub fn not_equals(self, rhs: Self) -> FeltCondition<'c, 'a> {
+        self.compare(CmpiPredicate::Ne, rhs)
+    }
+
+    /// Compares the canonical representatives of the values, in `0..p`.
+    #[track_caller]
+    pub fn less_than(self, rhs: Self) -> FeltCondition<'c, 'a> {
+        self.compare(CmpiPredicate::Ult, rhs)
+    }
+
+    #[track_caller]
+    fn compare(self, predicate: CmpiPredicate, rhs: Self) -> FeltCondition<'c, 'a> {
+        let scope = self.scope.innermost(rhs.scope);
+        let operation = arith::cmpi(
+            scope.ctx,
+            predicate,
+            self.value,
+            rhs.value,
+            caller_location(scope.ctx),
+        );
+        FeltCondition {
+            value: scope.append_operation(operation)[0],
+        }
+    }
+}
+
+/// A boolean value in the body of a `FeltFn`, used by the control flow
+/// builders.
+#[derive(Clone, Copy)]
+pub struct FeltCondition<'c, 'a> {
+    value: Value<'c, 'a>,
+}
+
+impl<'c, 'a> FeltCondition<'c, 'a> {
+    pub fn value(&self) -> Value<'c, 'a> {
+        self.value
+    }
+}
+
+macro_rules! binary_operator {
+    ($Trait:ident, $method:ident, $operation:literal) => {
+        impl $Trait for FeltValue<'_, '_> {
+            type Output = Self;
+
+            #[track_caller]
+            fn $method(self, rhs: Self) -> Self {
+                let scope = self.scope.innermost(rhs.scope);
+                FeltValue {
+                    scope,
+                    value: scope.append($operation, &[self.value, rhs.value], scope.felt_type),
+                }
+            }
+        }
+    };
+}
+
+binary_operator!(Add, add, "felt.add");
+binary_operator!(Sub, sub, "felt.sub");
+binary_operator!(Mul, mul, "felt.mul");
+binary_operator!(Div, div, "felt.div");
+
+impl Neg for FeltValue<'_, '_> {
+    type Output = Self;
+
+    #[track_caller]
+    fn neg(self) -> Self {
+        FeltValue {
+            scope: self.scope,
+            value: self
+                .scope
+                .append("felt.neg", &[self.value], self.scope.felt_type),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use melior::{
+        Context,
+        ir::{
+            BlockLike, Module,
+            operation::{OperationLike, OperationPrintingFlags},
+        },
+    };
+
+    use crate::{
+        caller_location, dsl::FeltFn, execute_entrypoint, execute_map, initialize_context,
+        pdl::build_pattern_module, pipeline::PipelineOptions, testing::felt_context,
+    };
+
+    /// Lowers a module with the entrypoint function, and executes it.
+    fn execute(context: &Context, function: FeltFn, a: u32, b: u32) -> u32 {
+        execute_entrypoint(&lower(context, function), a, b)
+    }
+
+    /// Lowers a module with a single function to the LLVM dialect.
+    fn lower<'c>(context: &'c Context, function: FeltFn<'c>) -> Module<'c> {
+        let mut module = Module::new(caller_location(context));
+        module.body().append_operation(function.into());
+        assert!(module.as_operation().verify());
+
+        crate::testing::lower(
+            context,
+            &mut module,
+            build_pattern_module(context),
+            &PipelineOptions::default(),
+        );
+        module
+    }
+
+    #[test]
+    fn build_and_execute() {
+        let context = felt_context();
+
+        let f = FeltFn::new(&context, "entrypoint", 2, |b, [x, y]| x * y + b.constant(5));
+
+        // The operations are located at the expressions that built them.
+        let module = Module::new(caller_location(&context));
+        let source = module
+            .body()
+            .append_operation(f.into())
+            .to_string_with_flags(OperationPrintingFlags::new().enable_debug_info(true, false))
+            .unwrap();
+        assert!(source.contains(&format!("loc(\"{}\":", file!())));
+
+        // 10 * 7 + 5 = 75 = 10.
+        let f = FeltFn::new(&context, "entrypoint", 2, |b, [x, y]| x * y + b.constant(5));
+        assert_eq!(execute(&context, f, 10, 7), 10);
+    }
+
+    #[test]
+    fn map() {
+        let context = felt_context();
+
+        let f = FeltFn::map(&context, "multiply_add", 2, |b, [x, y]| {
+            x * y + b.constant(1)
+        });
+        let module = lower(&context, f);
+
+        let mut output = [0; 5];
+        execute_map(
+            &module,
+            "multiply_add",
+            &[&[1, 2, 3, 4, 5], &[10, 11, 12, 0, 7]],
+            &mut output,
+        );
+        // 2 * 11 + 1 = 23 = 10, 3 * 12 + 1 = 37 = 11 and 5 * 7 + 1 = 36 = 10.
+        assert_eq!(output, [11, 10, 11, 1, 10]);
+    }
+
+    #[test]
+    #[should_panic(expected = "doesn't take 2 one-dimensional memrefs")]
+    fn map_arity_mismatch() {
+        let context = felt_context();
+
+        let f = FeltFn::map(&context, "multiply_add", 2, |b, [x, y]| {
+            x * y + b.constant(1)
+        });
+        let module = lower(&context, f);
+
+        let mut output = [0; 2];
+        execute_map(&module, "multiply_add", &[&[1, 2]], &mut output);
+    }
+
+    #[test]
+    #[should_panic(expected = "takes 2 parameters")]
+    fn parameter_count_mismatch() {
+        let context = initialize_context();
+        FeltFn::new(&context, "entrypoint", 3, |_, [x, y]| x - -y);
+    }
+
+    #[test]
+    fn fibonacci() {
+        let context = felt_context();
+
+        // Computes the n-th Fibonacci number, starting from (0, x).
+        let f = FeltFn::new(&context, "entrypoint", 2, |b, [n, x]| {
+            let [a, _] = b.for_range(b.constant(0), n, [b.constant(0), x], |_, _, [a, b]| {
+                [b, a + b]
+            });
+            a
+        });
+
+        // F(10) = 55 = 3.
+        assert_eq!(execute(&context, f, 10, 1), 3);
+    }
+
+    #[test]
+    fn minimum() {
+        let context = felt_context();
+
+        let f = FeltFn::new(&context, "entrypoint", 2, |b, [x, y]| {
+            let [minimum] = b.if_else(x.less_than(y), |_| [x], |_| [y]);
+            minimum
+        });
+
+        assert_eq!(execute(&context, f, 4, 9), 4);
+    }
+
+    #[test]
+    fn count_down() {
+        let context = felt_context();
+
+        // Multiplies y by itself x times, decrementing x until it is zero.
+        let f = FeltFn::new(&context, "entrypoint", 2, |b, [x, y]| {
+            let [_, power] = b.while_loop(
+                [x, b.constant(1)],
+                |b, [x, _]| x.not_equals(b.constant(0)),
+                |b, [x, power]| [x - b.constant(1), power * y],
+            );
+            power
+        });
+
+        // 2^5 = 32 = 6.
+        assert_eq!(execute(&context, f, 5, 2), 6);
+    }
+}
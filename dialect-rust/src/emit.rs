@@ -0,0 +1,273 @@
+use std::{
+    ffi::{CStr, CString, c_char, c_void},
+    fs,
+    path::Path,
+    ptr, slice,
+};
+
+use llvm_sys::{
+    core::{
+        LLVMContextCreate, LLVMContextDispose, LLVMDisposeMessage, LLVMDisposeModule,
+        LLVMPrintModuleToString, LLVMSetTarget,
+    },
+    prelude::LLVMModuleRef,
+    target::{
+        LLVM_InitializeNativeAsmPrinter, LLVM_InitializeNativeTarget, LLVMDisposeTargetData,
+        LLVMSetModuleDataLayout,
+    },
+    target_machine::{
+        LLVMCodeGenFileType, LLVMCodeGenOptLevel, LLVMCodeModel, LLVMCreateTargetDataLayout,
+        LLVMCreateTargetMachine, LLVMDisposeTargetMachine, LLVMGetDefaultTargetTriple,
+        LLVMGetHostCPUFeatures, LLVMGetHostCPUName, LLVMGetTargetFromTriple, LLVMRelocMode,
+        LLVMTargetMachineEmitToFile,
+    },
+};
+use melior::{
+    Context,
+    ir::{Module, operation::OperationLike},
+};
+use mlir_sys::{
+    MlirStringRef, mlirModuleCreateParse, mlirOperationWriteBytecode, mlirStringRefCreate,
+};
+
+use crate::{error::Error, ffi::mlirTranslateModuleToLLVMIR};
+
+/// Translates a module into textual LLVM IR.
+///
+/// The module must be fully converted to the `llvm` dialect (see
+/// `convert_to_llvm`). This is the same as running `mlir-translate
+/// --mlir-to-llvmir` on the module.
+pub fn translate_to_llvm_ir(module: &Module) -> Result<String, Error> {
+    with_llvm_module(module, |llvm_module| unsafe {
+        let raw_ir = LLVMPrintModuleToString(llvm_module);
+        let ir = CStr::from_ptr(raw_ir).to_string_lossy().into_owned();
+        LLVMDisposeMessage(raw_ir);
+        Ok(ir)
+    })
+}
+
+/// Compiles a module into a native object file, for the host machine.
+///
+/// The code is position independent, so that the object can be linked into a
+/// shared library.
+pub fn write_object(module: &Module, path: impl AsRef<Path>) -> Result<(), Error> {
+    let path = CString::new(path.as_ref().to_string_lossy().as_bytes()).unwrap();
+
+    with_llvm_module(module, |llvm_module| unsafe {
+        LLVM_InitializeNativeTarget();
+        LLVM_InitializeNativeAsmPrinter();
+
+        let host = HostTarget::get();
+
+        let mut target = ptr::null_mut();
+        let mut message = ptr::null_mut();
+        if LLVMGetTargetFromTriple(host.triple.as_ptr(), &mut target, &mut message) != 0 {
+            let error = Error::Codegen(CStr::from_ptr(message).to_string_lossy().into_owned());
+            LLVMDisposeMessage(message);
+            return Err(error);
+        }
+
+        let target_machine = LLVMCreateTargetMachine(
+            target,
+            host.triple.as_ptr(),
+            host.cpu.as_ptr(),
+            host.features.as_ptr(),
+            LLVMCodeGenOptLevel::LLVMCodeGenLevelDefault,
+            LLVMRelocMode::LLVMRelocPIC,
+            LLVMCodeModel::LLVMCodeModelDefault,
+        );
+        LLVMSetTarget(llvm_module, host.triple.as_ptr());
+
+        let data_layout = LLVMCreateTargetDataLayout(target_machine);
+        LLVMSetModuleDataLayout(llvm_module, data_layout);
+        LLVMDisposeTargetData(data_layout);
+
+        let mut message = ptr::null_mut();
+        let failed = LLVMTargetMachineEmitToFile(
+            target_machine,
+            llvm_module,
+            path.as_ptr() as *mut c_char,
+            LLVMCodeGenFileType::LLVMObjectFile,
+            &mut message,
+        ) != 0;
+        LLVMDisposeTargetMachine(target_machine);
+
+        if failed {
+            let error = Error::Codegen(CStr::from_ptr(message).to_string_lossy().into_owned());
+            LLVMDisposeMessage(message);
+            return Err(error);
+        }
+
+        Ok(())
+    })
+}
+
+/// The machine that `write_object` compiles for.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HostTarget {
+    pub triple: CString,
+    pub cpu: CString,
+    pub features: CString,
+}
+
+impl HostTarget {
+    /// Returns the triple, the CPU name and the CPU features of the host.
+    pub fn get() -> Self {
+        unsafe {
+            let take = |message: *mut c_char| {
+                let owned = CStr::from_ptr(message).to_owned();
+                LLVMDisposeMessage(message);
+                owned
+            };
+            HostTarget {
+                triple: take(LLVMGetDefaultTargetTriple()),
+                cpu: take(LLVMGetHostCPUName()),
+                features: take(LLVMGetHostCPUFeatures()),
+            }
+        }
+    }
+}
+
+/// Translates the module to LLVM IR, and calls `f` with the resulting LLVM
+/// module. The LLVM module only lives for the duration of the call.
+fn with_llvm_module<T>(
+    module: &Module,
+    f: impl FnOnce(LLVMModuleRef) -> Result<T, Error>,
+) -> Result<T, Error> {
+    unsafe {
+        let llvm_context = LLVMContextCreate();
+        let llvm_module = mlirTranslateModuleToLLVMIR(module.as_operation().to_raw(), llvm_context);
+        if llvm_module.is_null() {
+            LLVMContextDispose(llvm_context);
+            return Err(Error::Translation);
+        }
+
+        let result = f(llvm_module);
+
+        LLVMDisposeModule(llvm_module);
+        LLVMContextDispose(llvm_context);
+
+        result
+    }
+}
+
+/// Serializes a module into MLIR bytecode.
+///
+/// Unlike the textual form, the bytecode format is stable across MLIR
+/// versions, and is much faster to load.
+pub fn to_bytecode(module: &Module) -> Vec<u8> {
+    // The bytecode writer streams the output in chunks, so we accumulate them
+    // in a buffer passed through the user data pointer.
+    unsafe extern "C" fn append_chunk(chunk: MlirStringRef, buffer: *mut c_void) {
+        let buffer = unsafe { &mut *(buffer as *mut Vec<u8>) };
+        let chunk = unsafe { slice::from_raw_parts(chunk.data as *const u8, chunk.length) };
+        buffer.extend_from_slice(chunk);
+    }
+
+    let mut buffer = Vec::new();
+    unsafe {
+        mlirOperationWriteBytecode(
+            module.as_operation().to_raw(),
+            Some(append_chunk),
+            &mut buffer as *mut Vec<u8> as *mut c_void,
+        )
+    };
+    buffer
+}
+
+/// Parses a module from MLIR bytecode, as produced by `to_bytecode`.
+///
+/// The dialects used by the module must already be loaded in the context.
+pub fn parse_bytecode<'c>(ctx: &'c Context, bytecode: &[u8]) -> Result<Module<'c>, Error> {
+    // The MLIR parser detects the bytecode magic number by itself, but the
+    // bytecode is not valid UTF-8, so we can't go through `Module::parse`.
+    let module = unsafe {
+        mlirModuleCreateParse(
+            ctx.to_raw(),
+            mlirStringRefCreate(bytecode.as_ptr() as *const c_char, bytecode.len()),
+        )
+    };
+    if module.ptr.is_null() {
+        return Err(Error::Parse("bytecode".to_string()));
+    }
+
+    Ok(unsafe { Module::from_raw(module) })
+}
+
+pub fn write_llvm_ir(module: &Module, path: impl AsRef<Path>) -> Result<(), Error> {
+    fs::write(path, translate_to_llvm_ir(module)?)?;
+    Ok(())
+}
+
+pub fn write_bytecode(module: &Module, path: impl AsRef<Path>) -> Result<(), Error> {
+    fs::write(path, to_bytecode(module))?;
+    Ok(())
+}
+
+pub fn load_bytecode<'c>(ctx: &'c Context, path: impl AsRef<Path>) -> Result<Module<'c>, Error> {
+    let path = path.as_ref();
+    parse_bytecode(ctx, &fs::read(path)?).map_err(|_| Error::Parse(path.display().to_string()))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        core::build_core_module,
+        emit::{parse_bytecode, to_bytecode, translate_to_llvm_ir},
+        pdl::build_pattern_module,
+        pipeline::PipelineOptions,
+        testing::{felt_context, lower},
+    };
+
+    #[test]
+    fn bytecode_roundtrip() {
+        let context = felt_context();
+
+        let core_module = build_core_module(&context);
+        let loaded_module = parse_bytecode(&context, &to_bytecode(&core_module)).unwrap();
+        assert_eq!(
+            core_module.as_operation().to_string(),
+            loaded_module.as_operation().to_string()
+        )
+    }
+
+    #[test]
+    fn translate_lowered_module() {
+        let context = felt_context();
+
+        let mut core_module = build_core_module(&context);
+        lower(
+            &context,
+            &mut core_module,
+            build_pattern_module(&context),
+            &PipelineOptions::default(),
+        );
+
+        let ir = translate_to_llvm_ir(&core_module).unwrap();
+        assert!(ir.contains("define i32 @entrypoint(i32 %0, i32 %1)"));
+        assert!(ir.contains("urem i32"));
+    }
+
+    #[test]
+    fn translate_with_debug_info() {
+        let context = felt_context();
+        let options = PipelineOptions {
+            debug_info: true,
+            ..Default::default()
+        };
+
+        let mut core_module = build_core_module(&context);
+        lower(
+            &context,
+            &mut core_module,
+            build_pattern_module(&context),
+            &options,
+        );
+
+        // The core module is built with locations pointing to `core.rs`.
+        let ir = translate_to_llvm_ir(&core_module).unwrap();
+        assert!(ir.contains("!DISubprogram(name: \"entrypoint\""));
+        assert!(ir.contains("!DIFile(filename: \"core.rs\""));
+        assert!(ir.contains("!DILocation("));
+    }
+}
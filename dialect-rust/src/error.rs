@@ -0,0 +1,92 @@
+use std::{fmt, io};
+
+use crate::{lang::Diagnostic, legality::IllegalOperation};
+
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    Melior(melior::Error),
+    /// The module could not be translated to LLVM IR. This usually means that
+    /// it still contains operations outside of the `llvm` dialect.
+    Translation,
+    /// LLVM failed to generate native code for the module.
+    Codegen(String),
+    /// The system linker failed to produce a shared library.
+    Link(String),
+    /// The given source could not be parsed into a module.
+    Parse(String),
+    /// The type cannot be represented across the C interface.
+    UnsupportedType(String),
+    /// A transform script has no entry point, or failed to apply.
+    Transform(String),
+    /// The module uses felts in a way that Montgomery form doesn't preserve.
+    Montgomery(String),
+    /// Operations that should have been rewritten are still present.
+    IllegalOperations(Vec<IllegalOperation>),
+    /// The module has no `entrypoint` that `execute_entrypoint` can call.
+    Entrypoint(String),
+    /// A dialect conversion failed, with the diagnostics it reported.
+    Conversion(Vec<String>),
+    /// A felt program or PDLL patterns have syntax or type errors.
+    Compile {
+        source_name: String,
+        diagnostics: Vec<Diagnostic>,
+    },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(error) => write!(f, "{error}"),
+            Error::Melior(error) => write!(f, "{error}"),
+            Error::Translation => write!(f, "failed to translate module to LLVM IR"),
+            Error::Codegen(message) => write!(f, "failed to generate native code: {message}"),
+            Error::Link(message) => write!(f, "failed to link shared library: {message}"),
+            Error::Parse(source) => write!(f, "failed to parse module from {source}"),
+            Error::UnsupportedType(name) => write!(f, "unsupported C interface type {name}"),
+            Error::Transform(message) => write!(f, "failed to apply transform script: {message}"),
+            Error::Montgomery(message) => {
+                write!(f, "failed to convert to Montgomery form: {message}")
+            }
+            Error::IllegalOperations(operations) => {
+                write!(f, "{} operations were not lowered:", operations.len())?;
+                for operation in operations {
+                    write!(f, "\n  {operation}")?;
+                }
+                Ok(())
+            }
+            Error::Entrypoint(message) => write!(f, "cannot execute @entrypoint: {message}"),
+            Error::Conversion(diagnostics) => {
+                write!(f, "failed to apply dialect conversion:")?;
+                for diagnostic in diagnostics {
+                    write!(f, "\n  {diagnostic}")?;
+                }
+                Ok(())
+            }
+            Error::Compile {
+                source_name,
+                diagnostics,
+            } => {
+                write!(f, "failed to compile {source_name}:")?;
+                for diagnostic in diagnostics {
+                    write!(f, "\n  {source_name}:{diagnostic}")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<io::Error> for Error {
+    fn from(error: io::Error) -> Self {
+        Error::Io(error)
+    }
+}
+
+impl From<melior::Error> for Error {
+    fn from(error: melior::Error) -> Self {
+        Error::Melior(error)
+    }
+}
@@ -0,0 +1,167 @@
+//! Declarations for C API functions that are not exposed by `mlir-sys`.
+
+use std::ffi::c_void;
+
+use llvm_sys::prelude::{LLVMContextRef, LLVMModuleRef};
+use mlir_sys::{
+    MlirContext, MlirFrozenRewritePatternSet, MlirLogicalResult, MlirOperation,
+    MlirPDLPatternModule, MlirPassManager, MlirStringRef,
+};
+
+unsafe extern "C" {
+    /// Translates a module in the `llvm` dialect into an LLVM IR module owned
+    /// by the given LLVM context. Returns a null module on failure.
+    ///
+    /// See `mlir-c/Target/LLVMIR.h`.
+    pub fn mlirTranslateModuleToLLVMIR(
+        module: MlirOperation,
+        context: LLVMContextRef,
+    ) -> LLVMModuleRef;
+}
+
+/// Options of the transform interpreter.
+///
+/// See `mlir-c/Dialect/Transform/Interpreter.h`.
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct MlirTransformOptions {
+    pub ptr: *mut c_void,
+}
+
+unsafe extern "C" {
+    pub fn mlirTransformOptionsCreate() -> MlirTransformOptions;
+
+    pub fn mlirTransformOptionsEnableExpensiveChecks(
+        transform_options: MlirTransformOptions,
+        enable: bool,
+    );
+
+    pub fn mlirTransformOptionsDestroy(transform_options: MlirTransformOptions);
+
+    /// Applies the transform sequence rooted at `transform_root`, nested in
+    /// `transform_module`, to the payload operation.
+    pub fn mlirTransformApplyNamedSequence(
+        payload: MlirOperation,
+        transform_root: MlirOperation,
+        transform_module: MlirOperation,
+        transform_options: MlirTransformOptions,
+    ) -> MlirLogicalResult;
+}
+
+/// A PDL value passed to, or returned from, a native function.
+///
+/// See `native.cpp`.
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct PdlValue {
+    pub kind: PdlValueKind,
+    pub ptr: *const c_void,
+}
+
+#[repr(u32)]
+#[derive(Clone, Copy)]
+pub enum PdlValueKind {
+    Attribute,
+    Operation,
+    Type,
+    Value,
+}
+
+pub type NativeConstraint =
+    unsafe extern "C" fn(data: *const c_void, arguments: *const PdlValue, count: usize) -> bool;
+
+pub type NativeRewrite = unsafe extern "C" fn(
+    data: *const c_void,
+    arguments: *const PdlValue,
+    count: usize,
+    results: *mut c_void,
+) -> bool;
+
+pub type WriteCallback = unsafe extern "C" fn(data: *mut c_void, ptr: *const u8, size: usize);
+
+// The registration of native PDL functions is not part of the C API, so
+// these are defined by the C++ shim in `native.cpp`.
+unsafe extern "C" {
+    /// Registers a native constraint function, called with `data` and the
+    /// arguments of `pdl.apply_native_constraint`.
+    pub fn dialectRustRegisterNativeConstraint(
+        module: MlirPDLPatternModule,
+        name: MlirStringRef,
+        callback: NativeConstraint,
+        data: *const c_void,
+    );
+
+    /// Registers a native rewrite function, called with `data` and the
+    /// arguments of `pdl.apply_native_rewrite`. The results are pushed with
+    /// `dialectRustPushNativeResult`, and the rewrite fails if the callback
+    /// returns false.
+    pub fn dialectRustRegisterNativeRewrite(
+        module: MlirPDLPatternModule,
+        name: MlirStringRef,
+        callback: NativeRewrite,
+        data: *const c_void,
+    );
+
+    pub fn dialectRustPushNativeResult(results: *mut c_void, value: PdlValue);
+
+    /// Enables MLIR's crash reproducers on the pass manager: if a pass fails
+    /// or crashes, the module and the pipeline are written to `output_file`.
+    pub fn dialectRustEnableCrashReproducer(
+        pass_manager: MlirPassManager,
+        output_file: MlirStringRef,
+    );
+
+    pub fn dialectRustIsMultithreadingEnabled(context: MlirContext) -> bool;
+
+    /// Creates a stream that passes everything written to it to `callback`,
+    /// with `data`. It must outlive the pass managers that write to it.
+    pub fn dialectRustCreateStream(callback: WriteCallback, data: *mut c_void) -> *mut c_void;
+
+    pub fn dialectRustDestroyStream(stream: *mut c_void);
+
+    /// Prints the module before every pass, after it, or after it only if it
+    /// changed the module. The context must be single-threaded.
+    pub fn dialectRustEnableIRPrinting(
+        pass_manager: MlirPassManager,
+        before: bool,
+        after: bool,
+        after_only_on_change: bool,
+        stream: *mut c_void,
+    );
+
+    /// Times every pass, including nested ones. The report is written when
+    /// the pass manager is destroyed.
+    pub fn dialectRustEnableTiming(pass_manager: MlirPassManager, stream: *mut c_void);
+
+    /// Collects the statistics of every pass, including nested ones. The
+    /// report is written when the pass manager is destroyed.
+    pub fn dialectRustEnableStatistics(pass_manager: MlirPassManager, stream: *mut c_void);
+
+    /// Applies the patterns to the operation as a partial dialect conversion,
+    /// where the given dialects, and `builtin.module`, are legal or illegal.
+    /// On failure, the conversion is rolled back, and each of its
+    /// diagnostics is passed to `callback`, with `data`.
+    pub fn dialectRustApplyPartialConversion(
+        op: MlirOperation,
+        patterns: MlirFrozenRewritePatternSet,
+        legal_dialects: *const MlirStringRef,
+        legal_count: usize,
+        illegal_dialects: *const MlirStringRef,
+        illegal_count: usize,
+        callback: WriteCallback,
+        data: *mut c_void,
+    ) -> MlirLogicalResult;
+
+    /// Like `dialectRustApplyPartialConversion`, as a full dialect
+    /// conversion.
+    pub fn dialectRustApplyFullConversion(
+        op: MlirOperation,
+        patterns: MlirFrozenRewritePatternSet,
+        legal_dialects: *const MlirStringRef,
+        legal_count: usize,
+        illegal_dialects: *const MlirStringRef,
+        illegal_count: usize,
+        callback: WriteCallback,
+        data: *mut c_void,
+    ) -> MlirLogicalResult;
+}
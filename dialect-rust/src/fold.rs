@@ -0,0 +1,194 @@
+//! Folding of felt operations.
+//!
+//! IRDL operations have no folders, so `canonicalize` leaves them untouched.
+//! Instead, the felt operations are folded with PDL patterns, before they are
+//! lowered:
+//!
+//! - Operations whose operands are all constants are lowered early, and the
+//!   following canonicalization folds the arith operations into a constant.
+//! - `x + 0`, `x * 1`, `x * 0` and `-(-x)` are replaced with `x` or 0.
+
+use melior::{
+    Context,
+    ir::{BlockLike, Module},
+};
+
+use crate::{
+    apply_pdl_patterns, canonicalize, convert_pdl_to_pdl_interop, irdl::OPERATIONS, parse_module,
+    pdl::build_constant_folding_pattern, pipeline::PipelineOptions,
+};
+
+/// Builds the folding patterns.
+pub fn build_fold_pattern_module(ctx: &'_ Context) -> Module<'_> {
+    let module = parse_module(
+        ctx,
+        r#"
+        module {
+          pdl.pattern @add_zero : benefit(1) {
+            %0 = type
+            %1 = operand
+            %2 = attribute = 0 : i32
+            %3 = operation "arith.constant"  {"value" = %2} -> (%0 : !pdl.type)
+            %4 = result 0 of %3
+            %5 = operation "felt.add"(%1, %4 : !pdl.value, !pdl.value)  -> (%0 : !pdl.type)
+            rewrite %5 {
+              replace %5 with(%1 : !pdl.value)
+            }
+          }
+          pdl.pattern @zero_add : benefit(1) {
+            %0 = type
+            %1 = operand
+            %2 = attribute = 0 : i32
+            %3 = operation "arith.constant"  {"value" = %2} -> (%0 : !pdl.type)
+            %4 = result 0 of %3
+            %5 = operation "felt.add"(%4, %1 : !pdl.value, !pdl.value)  -> (%0 : !pdl.type)
+            rewrite %5 {
+              replace %5 with(%1 : !pdl.value)
+            }
+          }
+          pdl.pattern @mul_one : benefit(1) {
+            %0 = type
+            %1 = operand
+            %2 = attribute = 1 : i32
+            %3 = operation "arith.constant"  {"value" = %2} -> (%0 : !pdl.type)
+            %4 = result 0 of %3
+            %5 = operation "felt.mul"(%1, %4 : !pdl.value, !pdl.value)  -> (%0 : !pdl.type)
+            rewrite %5 {
+              replace %5 with(%1 : !pdl.value)
+            }
+          }
+          pdl.pattern @one_mul : benefit(1) {
+            %0 = type
+            %1 = operand
+            %2 = attribute = 1 : i32
+            %3 = operation "arith.constant"  {"value" = %2} -> (%0 : !pdl.type)
+            %4 = result 0 of %3
+            %5 = operation "felt.mul"(%4, %1 : !pdl.value, !pdl.value)  -> (%0 : !pdl.type)
+            rewrite %5 {
+              replace %5 with(%1 : !pdl.value)
+            }
+          }
+          pdl.pattern @mul_zero : benefit(1) {
+            %0 = type
+            %1 = operand
+            %2 = attribute = 0 : i32
+            %3 = operation "arith.constant"  {"value" = %2} -> (%0 : !pdl.type)
+            %4 = result 0 of %3
+            %5 = operation "felt.mul"(%1, %4 : !pdl.value, !pdl.value)  -> (%0 : !pdl.type)
+            rewrite %5 {
+              replace %5 with(%4 : !pdl.value)
+            }
+          }
+          pdl.pattern @zero_mul : benefit(1) {
+            %0 = type
+            %1 = operand
+            %2 = attribute = 0 : i32
+            %3 = operation "arith.constant"  {"value" = %2} -> (%0 : !pdl.type)
+            %4 = result 0 of %3
+            %5 = operation "felt.mul"(%4, %1 : !pdl.value, !pdl.value)  -> (%0 : !pdl.type)
+            rewrite %5 {
+              replace %5 with(%4 : !pdl.value)
+            }
+          }
+          pdl.pattern @neg_neg : benefit(1) {
+            %0 = type
+            %1 = operand
+            %2 = operation "felt.neg"(%1 : !pdl.value)  -> (%0 : !pdl.type)
+            %3 = result 0 of %2
+            %4 = operation "felt.neg"(%3 : !pdl.value)  -> (%0 : !pdl.type)
+            rewrite %4 {
+              replace %4 with(%1 : !pdl.value)
+            }
+          }
+        }"#,
+        "build_fold_pattern_module",
+    )
+    .unwrap();
+
+    for (name, operand_count) in OPERATIONS {
+        module
+            .body()
+            .append_operation(build_constant_folding_pattern(ctx, name, operand_count));
+    }
+
+    module
+}
+
+/// Folds the felt operations of the module, and canonicalizes it. The felt
+/// dialect must already be loaded in the context.
+///
+/// The module must be folded before its remaining felt operations are
+/// lowered, or converted with `montgomery::convert_boundaries`.
+pub fn fold(ctx: &Context, module: &mut Module<'_>, options: &PipelineOptions) {
+    let mut pattern_module = build_fold_pattern_module(ctx);
+    canonicalize(ctx, &mut pattern_module, options);
+    convert_pdl_to_pdl_interop(ctx, &mut pattern_module, options);
+    apply_pdl_patterns(module, &pattern_module);
+    canonicalize(ctx, module, options);
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{fold::fold, parse_module, pipeline::PipelineOptions, testing::felt_context};
+
+    #[test]
+    fn fold_constants() {
+        let context = felt_context();
+
+        let mut module = parse_module(
+            &context,
+            r#"
+            module {
+              func.func @constants() -> i32 {
+                %0 = arith.constant 7 : i32
+                %1 = arith.constant 9 : i32
+                %2 = "felt.mul"(%0, %1) : (i32, i32) -> i32
+                %3 = "felt.neg"(%2) : (i32) -> i32
+                %4 = "felt.div"(%3, %1) : (i32, i32) -> i32
+                return %4 : i32
+              }
+            }"#,
+            "fold_constants",
+        )
+        .unwrap();
+        fold(&context, &mut module, &PipelineOptions::default());
+
+        // 7 * 9 = 63 = 11, -11 = 2, and the inverse of 9 is 3, so the result
+        // is 2 * 3 = 6.
+        let source = module.as_operation().to_string();
+        assert!(!source.contains("felt."), "{source}");
+        assert!(source.contains("arith.constant 6 : i32"), "{source}");
+    }
+
+    #[test]
+    fn fold_identities() {
+        let context = felt_context();
+
+        let mut module = parse_module(
+            &context,
+            r#"
+            module {
+              func.func @identities(%x: i32) -> (i32, i32) {
+                %0 = arith.constant 0 : i32
+                %1 = arith.constant 1 : i32
+                %2 = "felt.add"(%x, %0) : (i32, i32) -> i32
+                %3 = "felt.mul"(%1, %2) : (i32, i32) -> i32
+                %4 = "felt.neg"(%3) : (i32) -> i32
+                %5 = "felt.neg"(%4) : (i32) -> i32
+                %6 = "felt.mul"(%5, %0) : (i32, i32) -> i32
+                return %5, %6 : i32, i32
+              }
+            }"#,
+            "fold_identities",
+        )
+        .unwrap();
+        fold(&context, &mut module, &PipelineOptions::default());
+
+        let source = module.as_operation().to_string();
+        assert!(!source.contains("felt."), "{source}");
+        assert!(
+            source.contains("return %arg0, %c0_i32 : i32, i32"),
+            "{source}"
+        );
+    }
+}
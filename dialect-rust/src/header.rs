@@ -0,0 +1,402 @@
+use std::fmt::Write;
+
+use melior::ir::{
+    Module, Type, TypeLike,
+    attribute::{StringAttribute, TypeAttribute},
+    operation::{OperationLike, OperationRef},
+    r#type::{FunctionType, IntegerType},
+};
+use mlir_sys::{mlirShapedTypeGetElementType, mlirShapedTypeGetRank, mlirTypeIsAMemRef};
+
+use crate::error::Error;
+
+/// A type that can cross the C interface boundary.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum CType {
+    Scalar(ScalarType),
+    /// A ranked memref, passed as a pointer to its descriptor.
+    MemRef {
+        element: ScalarType,
+        rank: usize,
+    },
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum ScalarType {
+    /// A signless integer, treated as unsigned (felts are never negative).
+    Integer(u32),
+    Index,
+    F32,
+    F64,
+}
+
+/// A `func.func` marked with `llvm.emit_c_interface`.
+///
+/// When lowering to LLVM, MLIR generates a `_mlir_ciface_<name>` wrapper for
+/// these functions, with the following calling convention:
+/// - Memref arguments are passed as pointers to their descriptors.
+/// - A single scalar result is returned directly.
+/// - Multiple results, or a memref result, are packed into a struct that is
+///   written to a pointer received as the first argument.
+struct CInterface {
+    name: String,
+    inputs: Vec<CType>,
+    results: Vec<CType>,
+}
+
+impl CInterface {
+    fn returns_struct(&self) -> bool {
+        self.results.len() > 1 || matches!(self.results[..], [CType::MemRef { .. }])
+    }
+}
+
+/// Generates a C header declaring the `_mlir_ciface_*` wrappers of every
+/// function with the `llvm.emit_c_interface` attribute.
+pub fn generate_c_header(module: &Module) -> Result<String, Error> {
+    let interfaces = collect_interfaces(module)?;
+
+    let mut header = String::new();
+    writeln!(header, "#pragma once\n").unwrap();
+    writeln!(header, "#include <stdbool.h>").unwrap();
+    writeln!(header, "#include <stdint.h>\n").unwrap();
+
+    for (element, rank) in memref_descriptors(&interfaces) {
+        let element = c_scalar(element);
+        writeln!(header, "typedef struct {{").unwrap();
+        writeln!(header, "  {element} *allocated;").unwrap();
+        writeln!(header, "  {element} *aligned;").unwrap();
+        writeln!(header, "  int64_t offset;").unwrap();
+        if rank > 0 {
+            writeln!(header, "  int64_t sizes[{rank}];").unwrap();
+            writeln!(header, "  int64_t strides[{rank}];").unwrap();
+        }
+        writeln!(header, "}} {};\n", c_descriptor_name(element, rank)).unwrap();
+    }
+
+    for interface in &interfaces {
+        let mut parameters = Vec::new();
+
+        let return_type = if interface.returns_struct() {
+            let result_name = format!("{}_result", interface.name);
+            writeln!(header, "typedef struct {{").unwrap();
+            for (index, &result) in interface.results.iter().enumerate() {
+                writeln!(header, "  {} result{index};", c_value(result)).unwrap();
+            }
+            writeln!(header, "}} {result_name};\n").unwrap();
+
+            parameters.push(format!("{result_name} *result"));
+            "void".to_string()
+        } else {
+            interface
+                .results
+                .first()
+                .map_or("void".to_string(), |&result| c_value(result))
+        };
+
+        for (index, &input) in interface.inputs.iter().enumerate() {
+            match input {
+                CType::MemRef { .. } => parameters.push(format!("{} *arg{index}", c_value(input))),
+                _ => parameters.push(format!("{} arg{index}", c_value(input))),
+            }
+        }
+        if parameters.is_empty() {
+            parameters.push("void".to_string());
+        }
+
+        writeln!(
+            header,
+            "{return_type} _mlir_ciface_{}({});",
+            interface.name,
+            parameters.join(", ")
+        )
+        .unwrap();
+    }
+
+    Ok(header)
+}
+
+/// Generates the Rust equivalent of `generate_c_header`: `#[repr(C)]`
+/// descriptor structs, and `extern "C"` declarations of the wrappers.
+pub fn generate_rust_bindings(module: &Module) -> Result<String, Error> {
+    let interfaces = collect_interfaces(module)?;
+
+    let mut bindings = String::new();
+
+    for (element, rank) in memref_descriptors(&interfaces) {
+        let element_type = rust_scalar(element);
+        writeln!(bindings, "#[repr(C)]").unwrap();
+        writeln!(
+            bindings,
+            "pub struct {} {{",
+            rust_descriptor_name(element, rank)
+        )
+        .unwrap();
+        writeln!(bindings, "    pub allocated: *mut {element_type},").unwrap();
+        writeln!(bindings, "    pub aligned: *mut {element_type},").unwrap();
+        writeln!(bindings, "    pub offset: i64,").unwrap();
+        if rank > 0 {
+            writeln!(bindings, "    pub sizes: [i64; {rank}],").unwrap();
+            writeln!(bindings, "    pub strides: [i64; {rank}],").unwrap();
+        }
+        writeln!(bindings, "}}\n").unwrap();
+    }
+
+    for interface in interfaces.iter().filter(|i| i.returns_struct()) {
+        writeln!(bindings, "#[repr(C)]").unwrap();
+        writeln!(
+            bindings,
+            "pub struct {} {{",
+            rust_result_name(&interface.name)
+        )
+        .unwrap();
+        for (index, &result) in interface.results.iter().enumerate() {
+            writeln!(bindings, "    pub result{index}: {},", rust_value(result)).unwrap();
+        }
+        writeln!(bindings, "}}\n").unwrap();
+    }
+
+    writeln!(bindings, "unsafe extern \"C\" {{").unwrap();
+    for interface in &interfaces {
+        let mut parameters = Vec::new();
+
+        let return_type = if interface.returns_struct() {
+            parameters.push(format!(
+                "result: *mut {}",
+                rust_result_name(&interface.name)
+            ));
+            String::new()
+        } else {
+            interface.results.first().map_or(String::new(), |&result| {
+                format!(" -> {}", rust_value(result))
+            })
+        };
+
+        for (index, &input) in interface.inputs.iter().enumerate() {
+            match input {
+                CType::MemRef { .. } => {
+                    parameters.push(format!("arg{index}: *mut {}", rust_value(input)))
+                }
+                _ => parameters.push(format!("arg{index}: {}", rust_value(input))),
+            }
+        }
+
+        writeln!(
+            bindings,
+            "    pub fn _mlir_ciface_{}({}){return_type};",
+            interface.name,
+            parameters.join(", ")
+        )
+        .unwrap();
+    }
+    writeln!(bindings, "}}").unwrap();
+
+    Ok(bindings)
+}
+
+fn collect_interfaces(module: &Module) -> Result<Vec<CInterface>, Error> {
+    let mut interfaces = Vec::new();
+
+    let mut operation = module.body().first_operation();
+    while let Some(function) = operation {
+        operation = function.next_in_block();
+
+        if function.name().as_string_ref().as_str() != Ok("func.func")
+            || !function.has_attribute("llvm.emit_c_interface")
+        {
+            continue;
+        }
+
+        interfaces.push(collect_interface(function)?);
+    }
+
+    Ok(interfaces)
+}
+
+fn collect_interface(function: OperationRef) -> Result<CInterface, Error> {
+    let name = StringAttribute::try_from(function.attribute("sym_name")?)?
+        .value()
+        .to_string();
+    let function_type = FunctionType::try_from(
+        TypeAttribute::try_from(function.attribute("function_type")?)?.value(),
+    )?;
+
+    let inputs = (0..function_type.input_count())
+        .map(|index| c_type(function_type.input(index)?))
+        .collect::<Result<_, _>>()?;
+    let results = (0..function_type.result_count())
+        .map(|index| c_type(function_type.result(index)?))
+        .collect::<Result<_, _>>()?;
+
+    Ok(CInterface {
+        name,
+        inputs,
+        results,
+    })
+}
+
+fn c_type(r#type: Type) -> Result<CType, Error> {
+    if unsafe { mlirTypeIsAMemRef(r#type.to_raw()) } {
+        let element = unsafe { Type::from_raw(mlirShapedTypeGetElementType(r#type.to_raw())) };
+        let rank = unsafe { mlirShapedTypeGetRank(r#type.to_raw()) } as usize;
+        return Ok(CType::MemRef {
+            element: scalar_type(element)?,
+            rank,
+        });
+    }
+
+    Ok(CType::Scalar(scalar_type(r#type)?))
+}
+
+fn scalar_type(r#type: Type) -> Result<ScalarType, Error> {
+    if let Ok(integer) = IntegerType::try_from(r#type) {
+        return match integer.width() {
+            width @ (1 | 8 | 16 | 32 | 64) => Ok(ScalarType::Integer(width)),
+            _ => Err(Error::UnsupportedType(r#type.to_string())),
+        };
+    }
+
+    if r#type.is_index() {
+        Ok(ScalarType::Index)
+    } else if r#type.is_f32() {
+        Ok(ScalarType::F32)
+    } else if r#type.is_f64() {
+        Ok(ScalarType::F64)
+    } else {
+        Err(Error::UnsupportedType(r#type.to_string()))
+    }
+}
+
+/// Returns every distinct memref descriptor used by the interfaces, in order
+/// of appearance.
+fn memref_descriptors(interfaces: &[CInterface]) -> Vec<(ScalarType, usize)> {
+    let mut descriptors = Vec::new();
+    for interface in interfaces {
+        for r#type in interface.inputs.iter().chain(&interface.results) {
+            let descriptor = match *r#type {
+                CType::MemRef { element, rank } => (element, rank),
+                CType::Scalar(_) => continue,
+            };
+            if !descriptors.contains(&descriptor) {
+                descriptors.push(descriptor);
+            }
+        }
+    }
+    descriptors
+}
+
+fn c_scalar(r#type: ScalarType) -> &'static str {
+    match r#type {
+        ScalarType::Integer(1) => "bool",
+        ScalarType::Integer(8) => "uint8_t",
+        ScalarType::Integer(16) => "uint16_t",
+        ScalarType::Integer(32) => "uint32_t",
+        ScalarType::Integer(64) => "uint64_t",
+        ScalarType::Integer(_) => unreachable!("unsupported widths are rejected by `scalar_type`"),
+        ScalarType::Index => "intptr_t",
+        ScalarType::F32 => "float",
+        ScalarType::F64 => "double",
+    }
+}
+
+fn c_value(r#type: CType) -> String {
+    match r#type {
+        CType::Scalar(scalar) => c_scalar(scalar).to_string(),
+        CType::MemRef { element, rank } => c_descriptor_name(c_scalar(element), rank),
+    }
+}
+
+fn c_descriptor_name(element: &str, rank: usize) -> String {
+    format!("memref_{}_{rank}d", element.trim_end_matches("_t"))
+}
+
+fn rust_scalar(r#type: ScalarType) -> &'static str {
+    match r#type {
+        ScalarType::Integer(1) => "bool",
+        ScalarType::Integer(8) => "u8",
+        ScalarType::Integer(16) => "u16",
+        ScalarType::Integer(32) => "u32",
+        ScalarType::Integer(64) => "u64",
+        ScalarType::Integer(_) => unreachable!("unsupported widths are rejected by `scalar_type`"),
+        ScalarType::Index => "isize",
+        ScalarType::F32 => "f32",
+        ScalarType::F64 => "f64",
+    }
+}
+
+fn rust_value(r#type: CType) -> String {
+    match r#type {
+        CType::Scalar(scalar) => rust_scalar(scalar).to_string(),
+        CType::MemRef { element, rank } => rust_descriptor_name(element, rank),
+    }
+}
+
+fn rust_descriptor_name(element: ScalarType, rank: usize) -> String {
+    let mut element = rust_scalar(element).to_string();
+    element[..1].make_ascii_uppercase();
+    format!("MemRef{element}D{rank}")
+}
+
+fn rust_result_name(function: &str) -> String {
+    let mut name = String::new();
+    for word in function.split('_').filter(|word| !word.is_empty()) {
+        name.push_str(&word[..1].to_ascii_uppercase());
+        name.push_str(&word[1..]);
+    }
+    name + "Result"
+}
+
+#[cfg(test)]
+mod test {
+    use melior::{ir::Module, utility::load_irdl_dialects};
+
+    use crate::{
+        core::build_core_module,
+        header::{generate_c_header, generate_rust_bindings},
+        initialize_context,
+        irdl::build_dialect_module,
+    };
+
+    #[test]
+    fn core_module_header() {
+        let context = initialize_context();
+        load_irdl_dialects(&build_dialect_module(&context));
+
+        let core_module = build_core_module(&context);
+        let header = generate_c_header(&core_module).unwrap();
+        assert!(header.contains("uint32_t _mlir_ciface_entrypoint(uint32_t arg0, uint32_t arg1);"));
+
+        let bindings = generate_rust_bindings(&core_module).unwrap();
+        assert!(bindings.contains("pub fn _mlir_ciface_entrypoint(arg0: u32, arg1: u32) -> u32;"));
+    }
+
+    #[test]
+    fn memref_header() {
+        let context = initialize_context();
+        let module = Module::parse(
+            &context,
+            r#"
+            module {
+              func.func @scale(%arg0: memref<?xi32>, %arg1: i32) -> memref<?xi32> attributes {llvm.emit_c_interface} {
+                return %arg0 : memref<?xi32>
+              }
+              func.func @internal(%arg0: i32) -> i32 {
+                return %arg0 : i32
+              }
+            }"#,
+        )
+        .unwrap();
+
+        let header = generate_c_header(&module).unwrap();
+        assert!(header.contains("int64_t sizes[1];"));
+        assert!(header.contains(
+            "void _mlir_ciface_scale(scale_result *result, memref_uint32_1d *arg0, uint32_t arg1);"
+        ));
+        assert!(!header.contains("internal"));
+
+        let bindings = generate_rust_bindings(&module).unwrap();
+        assert!(bindings.contains("pub struct MemRefU32D1 {"));
+        assert!(bindings.contains(
+            "pub fn _mlir_ciface_scale(result: *mut ScaleResult, arg0: *mut MemRefU32D1, arg1: u32);"
+        ));
+    }
+}
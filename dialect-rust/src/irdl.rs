@@ -3,131 +3,619 @@ use melior::{
     dialect::ods::irdl,
     helpers::BuiltinBlockExt,
     ir::{
-        Attribute, Block, BlockLike, Location, Module, Region, Type,
-        attribute::{StringAttribute, TypeAttribute},
+        Attribute, Block, BlockLike, Identifier, Module, Region, Type, Value,
+        attribute::{IntegerAttribute, StringAttribute, TypeAttribute},
+        operation::{Operation, OperationBuilder},
         r#type::IntegerType,
     },
 };
 
+use crate::{caller_location, parse_module};
+
 pub fn load_dialect_module(ctx: &'_ Context) -> Module<'_> {
-    Module::parse(
+    parse_module(
         ctx,
         "\
         module {
           irdl.dialect @felt {
+            irdl.type @felt {
+              %0 = irdl.base \"#builtin.integer\"
+              irdl.parameters(%0)
+            }
+            irdl.attribute @modulus {
+              %0 = irdl.base \"#builtin.integer\"
+              irdl.parameters(%0)
+            }
             irdl.operation @add {
               %0 = irdl.is i32
-              irdl.operands(%0, %0)
+              %1 = irdl.is i256
+              %2 = irdl.any_of(%0, %1)
+              irdl.operands(%2, %2)
+              irdl.results(%2)
+            }
+            irdl.operation @sub {
+              %0 = irdl.is i32
+              %1 = irdl.is i256
+              %2 = irdl.any_of(%0, %1)
+              irdl.operands(%2, %2)
+              irdl.results(%2)
+            }
+            irdl.operation @mul {
+              %0 = irdl.is i32
+              %1 = irdl.is i256
+              %2 = irdl.any_of(%0, %1)
+              irdl.operands(%2, %2)
+              irdl.results(%2)
+            }
+            irdl.operation @div {
+              %0 = irdl.is i32
+              %1 = irdl.is i256
+              %2 = irdl.any_of(%0, %1)
+              irdl.operands(%2, %2)
+              irdl.results(%2)
+            }
+            irdl.operation @neg {
+              %0 = irdl.is i32
+              %1 = irdl.is i256
+              %2 = irdl.any_of(%0, %1)
+              irdl.operands(%2)
+              irdl.results(%2)
+            }
+            irdl.operation @sum {
+              %0 = irdl.is i32
+              %1 = irdl.any
+              %2 = irdl.parametric @felt::@felt<%1>
+              %3 = irdl.any_of(%0, %2)
+              irdl.operands(variadic %3)
+              irdl.results(%3)
+            }
+            irdl.operation @reduce {
+              %0 = irdl.is i32
+              %1 = irdl.region(%0, %0) with size 1
+              irdl.operands(%0, variadic %0)
               irdl.results(%0)
+              irdl.regions(%1)
+            }
+            irdl.operation @map {
+              %0 = irdl.base \"!builtin.memref\"
+              %1 = irdl.region with size 1
+              irdl.operands(variadic %0, %0)
+              irdl.results()
+              irdl.regions(%1)
             }
           }
         }",
+        "load_dialect_module",
     )
     .unwrap()
 }
 
+/// The operations of the felt dialect, with their number of operands.
+pub const OPERATIONS: [(&str, usize); 5] =
+    [("add", 2), ("sub", 2), ("mul", 2), ("div", 2), ("neg", 1)];
+
+/// The types of the felt dialect, with their number of parameters.
+///
+/// `!felt.felt<p>` is a field element modulo `p`.
+pub const TYPES: [(&str, usize); 1] = [("felt", 1)];
+
+/// The attributes of the felt dialect, with their number of parameters.
+///
+/// `#felt.modulus<p>` is the modulus of a field.
+pub const ATTRIBUTES: [(&str, usize); 1] = [("modulus", 1)];
+
 /// Builds the dialect module using IRDL.
 ///
 /// The built module should be equal to the one in `load_dialect_module`.
 pub fn build_dialect_module(ctx: &'_ Context) -> Module<'_> {
-    let location = Location::unknown(ctx);
-    let module = Module::new(location);
+    let module = Module::new(caller_location(ctx));
+
+    let types = TYPES
+        .into_iter()
+        .map(|(name, parameter_count)| build_type(ctx, name, parameter_count));
+    let attributes = ATTRIBUTES
+        .into_iter()
+        .map(|(name, parameter_count)| build_attribute(ctx, name, parameter_count));
+    let operations = OPERATIONS
+        .into_iter()
+        .map(|(name, operand_count)| build_operation(ctx, name, operand_count));
+    module.body().append_operation(build_dialect(
+        ctx,
+        "felt",
+        types.chain(attributes).chain(operations).chain([
+            build_sum_operation(ctx),
+            build_reduce_operation(ctx),
+            build_map_operation(ctx),
+        ]),
+    ));
+
+    module
+}
+
+/// Builds an `irdl.dialect` with the given type, attribute and operation
+/// definitions.
+pub fn build_dialect<'c>(
+    ctx: &'c Context,
+    name: &str,
+    definitions: impl IntoIterator<Item = Operation<'c>>,
+) -> Operation<'c> {
+    irdl::dialect(
+        ctx,
+        {
+            let region = Region::new();
+            let block = region.append_block(Block::new(&[]));
+            for definition in definitions {
+                block.append_operation(definition);
+            }
+            region
+        },
+        StringAttribute::new(ctx, name),
+        caller_location(ctx),
+    )
+    .into()
+}
+
+/// A constraint on an attribute or a type, in an IRDL definition.
+///
+/// Each constraint is appended as a value to the body of the definition,
+/// which acts as a constraint variable: every operand, result or parameter
+/// constrained by the same value must be the same attribute or type. Appending
+/// the constraint once for each of them instead constrains them separately.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Constraint<'c> {
+    /// `irdl.is`: exactly the given attribute, or type attribute.
+    Is(Attribute<'c>),
+    /// `irdl.any`: any attribute or type.
+    Any,
+    /// `irdl.any_of`: satisfies at least one of the constraints.
+    AnyOf(Vec<Constraint<'c>>),
+    /// `irdl.all_of`: satisfies every constraint.
+    AllOf(Vec<Constraint<'c>>),
+    /// `irdl.base`: any attribute or type of the given base, with any
+    /// parameters.
+    Base(Base),
+    /// `irdl.parametric`: a type or an attribute defined with IRDL, referred
+    /// to by its symbol (like `@felt::@felt`), with constrained parameters.
+    Parametric(String, Vec<Constraint<'c>>),
+}
+
+/// The base of an `irdl.base` constraint.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Base {
+    /// A type or an attribute defined with IRDL, referred to by its symbol,
+    /// like `@felt::@felt`.
+    Definition(String),
+    /// A type or an attribute defined in C++, referred to by its name, like
+    /// `!builtin.integer`.
+    Name(String),
+}
+
+impl<'c> Constraint<'c> {
+    /// Returns the constraint on the given type.
+    pub fn is_type(r#type: Type<'c>) -> Self {
+        Constraint::Is(TypeAttribute::new(r#type).into())
+    }
+
+    /// Appends the constraint, and the constraints it is made of, to the body
+    /// of an IRDL definition, and returns its constraint variable.
+    pub fn append<'a>(&self, ctx: &'c Context, block: &'a Block<'c>) -> Value<'c, 'a> {
+        let irdl_attribute_type = Type::parse(ctx, "!irdl.attribute").unwrap();
+        let location = caller_location(ctx);
+
+        let operation = match self {
+            Constraint::Is(attribute) => {
+                irdl::is(ctx, irdl_attribute_type, *attribute, location).into()
+            }
+            Constraint::Any => irdl::any(ctx, irdl_attribute_type, location).into(),
+            Constraint::AnyOf(constraints) => irdl::any_of(
+                ctx,
+                irdl_attribute_type,
+                &append_all(ctx, block, constraints),
+                location,
+            )
+            .into(),
+            Constraint::AllOf(constraints) => irdl::all_of(
+                ctx,
+                irdl_attribute_type,
+                &append_all(ctx, block, constraints),
+                location,
+            )
+            .into(),
+            Constraint::Base(base) => {
+                let attribute = match base {
+                    Base::Definition(symbol) => (
+                        Identifier::new(ctx, "base_ref"),
+                        Attribute::parse(ctx, symbol).unwrap(),
+                    ),
+                    Base::Name(name) => (
+                        Identifier::new(ctx, "base_name"),
+                        StringAttribute::new(ctx, name).into(),
+                    ),
+                };
+                OperationBuilder::new("irdl.base", location)
+                    .add_attributes(&[attribute])
+                    .add_results(&[irdl_attribute_type])
+                    .build()
+                    .unwrap()
+            }
+            Constraint::Parametric(symbol, parameters) => {
+                let parameters = append_all(ctx, block, parameters);
+                OperationBuilder::new("irdl.parametric", location)
+                    .add_attributes(&[(
+                        Identifier::new(ctx, "base_type"),
+                        Attribute::parse(ctx, symbol).unwrap(),
+                    )])
+                    .add_operands(&parameters)
+                    .add_results(&[irdl_attribute_type])
+                    .build()
+                    .unwrap()
+            }
+        };
+
+        block.append_op_result(operation).unwrap()
+    }
+}
+
+fn append_all<'c, 'a>(
+    ctx: &'c Context,
+    block: &'a Block<'c>,
+    constraints: &[Constraint<'c>],
+) -> Vec<Value<'c, 'a>> {
+    constraints
+        .iter()
+        .map(|constraint| constraint.append(ctx, block))
+        .collect()
+}
+
+/// How many values an operand or a result definition stands for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Variadicity {
+    /// Exactly one value.
+    Single,
+    /// Zero or one value.
+    Optional,
+    /// Any number of values.
+    Variadic,
+}
+
+/// The definitions of the operands, results and regions of an operation.
+pub struct Definitions<'c, 'a> {
+    /// The constraint variables of the operands, with their variadicity.
+    pub operands: Vec<(Value<'c, 'a>, Variadicity)>,
+    /// The constraint variables of the results, with their variadicity.
+    pub results: Vec<(Value<'c, 'a>, Variadicity)>,
+    /// The region constraints, appended with `append_region_constraint`.
+    pub regions: Vec<Value<'c, 'a>>,
+}
+
+/// Builds an `irdl.operation`. `constraints` appends the constraints to the
+/// body of the definition, and returns the definitions of the operands, of
+/// the results and of the regions.
+///
+/// Without segment sizes, an operation can only tell its values apart if at
+/// most one of its operands, and one of its results, isn't single.
+pub fn build_constrained_operation<'c>(
+    ctx: &'c Context,
+    name: &str,
+    constraints: impl for<'a> FnOnce(&'a Block<'c>) -> Definitions<'c, 'a>,
+) -> Operation<'c> {
+    irdl::_operation(
+        ctx,
+        {
+            let region = Region::new();
+            let block = region.append_block(Block::new(&[]));
+
+            let Definitions {
+                operands,
+                results,
+                regions,
+            } = constraints(&block);
+            let (operands, operand_variadicities): (Vec<_>, Vec<_>) = operands.into_iter().unzip();
+            let (results, result_variadicities): (Vec<_>, Vec<_>) = results.into_iter().unzip();
+            block.append_operation(
+                irdl::operands(
+                    ctx,
+                    &operands,
+                    variadicity_array(ctx, &operand_variadicities),
+                    caller_location(ctx),
+                )
+                .into(),
+            );
+            block.append_operation(
+                irdl::results(
+                    ctx,
+                    &results,
+                    variadicity_array(ctx, &result_variadicities),
+                    caller_location(ctx),
+                )
+                .into(),
+            );
+            if !regions.is_empty() {
+                block.append_operation(irdl::regions(ctx, &regions, caller_location(ctx)).into());
+            }
+
+            region
+        },
+        StringAttribute::new(ctx, name),
+        caller_location(ctx),
+    )
+    .into()
+}
+
+/// Returns the `variadicity_array` attribute of the given values. Like for
+/// `build_operation`, the attribute can only be parsed.
+fn variadicity_array<'c>(ctx: &'c Context, variadicities: &[Variadicity]) -> Attribute<'c> {
+    let variadicities = variadicities
+        .iter()
+        .map(|variadicity| match variadicity {
+            Variadicity::Single => "single",
+            Variadicity::Optional => "optional",
+            Variadicity::Variadic => "variadic",
+        })
+        .collect::<Vec<_>>();
+    Attribute::parse(
+        ctx,
+        &format!("#irdl<variadicity_array[{}]>", variadicities.join(", ")),
+    )
+    .unwrap()
+}
+
+/// Builds `felt.sum`, which adds any number of felts, and returns 0 without
+/// operands. The felts are either u32 values, or `!felt.felt<p>` values of
+/// the same field. Only sums of u32 values are lowered.
+fn build_sum_operation(ctx: &'_ Context) -> Operation<'_> {
+    build_constrained_operation(ctx, "sum", |block| {
+        let felt = Constraint::AnyOf(vec![
+            Constraint::is_type(IntegerType::new(ctx, 32).into()),
+            Constraint::Parametric("@felt::@felt".to_string(), vec![Constraint::Any]),
+        ])
+        .append(ctx, block);
+        Definitions {
+            operands: vec![(felt, Variadicity::Variadic)],
+            results: vec![(felt, Variadicity::Single)],
+            regions: vec![],
+        }
+    })
+}
+
+/// Appends an `irdl.region` to the body of an operation definition, and
+/// returns its constraint. `arguments` constrains the arguments of the entry
+/// block, and `block_count` the number of blocks. Either is unconstrained
+/// when `None`.
+pub fn append_region_constraint<'c, 'a>(
+    ctx: &'c Context,
+    block: &'a Block<'c>,
+    arguments: Option<&[Value<'c, 'a>]>,
+    block_count: Option<u32>,
+) -> Value<'c, 'a> {
+    let mut builder = OperationBuilder::new("irdl.region", caller_location(ctx))
+        .add_results(&[Type::parse(ctx, "!irdl.region").unwrap()]);
+    if let Some(arguments) = arguments {
+        builder = builder.add_operands(arguments).add_attributes(&[(
+            Identifier::new(ctx, "constrainedArguments"),
+            Attribute::unit(ctx),
+        )]);
+    }
+    if let Some(block_count) = block_count {
+        builder = builder.add_attributes(&[(
+            Identifier::new(ctx, "numberOfBlocks"),
+            IntegerAttribute::new(IntegerType::new(ctx, 32).into(), block_count.into()).into(),
+        )]);
+    }
+
+    block.append_op_result(builder.build().unwrap()).unwrap()
+}
+
+/// Builds `felt.reduce`, which folds its variadic operands into its first
+/// one. Its body is a single block, which receives the accumulator and an
+/// operand, and yields the next accumulator (see `core::build_reduce`).
+fn build_reduce_operation(ctx: &'_ Context) -> Operation<'_> {
+    build_constrained_operation(ctx, "reduce", |block| {
+        let is_u32 = Constraint::is_type(IntegerType::new(ctx, 32).into()).append(ctx, block);
+        let body = append_region_constraint(ctx, block, Some(&[is_u32, is_u32]), Some(1));
+        Definitions {
+            operands: vec![
+                (is_u32, Variadicity::Single),
+                (is_u32, Variadicity::Variadic),
+            ],
+            results: vec![(is_u32, Variadicity::Single)],
+            regions: vec![body],
+        }
+    })
+}
+
+/// Builds `felt.map`, which applies its body element-wise over its variadic
+/// inputs, and writes the results to its output. Every operand is a memref of
+/// the same type. Its body is a single block, which receives an element of
+/// every input, and yields the output element (see `core::build_map`). IRDL
+/// can't relate the number of arguments to the number of operands, so the
+/// arguments are unconstrained.
+fn build_map_operation(ctx: &'_ Context) -> Operation<'_> {
+    build_constrained_operation(ctx, "map", |block| {
+        let memref = Constraint::Base(Base::Name("!builtin.memref".to_string())).append(ctx, block);
+        let body = append_region_constraint(ctx, block, None, Some(1));
+        Definitions {
+            operands: vec![
+                (memref, Variadicity::Variadic),
+                (memref, Variadicity::Single),
+            ],
+            results: vec![],
+            regions: vec![body],
+        }
+    })
+}
+
+/// Builds an `irdl.type` with `parameter_count` integer attribute parameters.
+pub fn build_type<'c>(ctx: &'c Context, name: &str, parameter_count: usize) -> Operation<'c> {
+    build_parametric_definition(ctx, "irdl.type", name, parameter_count)
+}
+
+/// Builds an `irdl.attribute` with `parameter_count` integer attribute
+/// parameters.
+pub fn build_attribute<'c>(ctx: &'c Context, name: &str, parameter_count: usize) -> Operation<'c> {
+    build_parametric_definition(ctx, "irdl.attribute", name, parameter_count)
+}
 
+/// Builds an `irdl.type` or an `irdl.attribute`. Both are a symbol with a
+/// body, which constrains their parameters with `irdl.parameters`.
+fn build_parametric_definition<'c>(
+    ctx: &'c Context,
+    operation_name: &str,
+    name: &str,
+    parameter_count: usize,
+) -> Operation<'c> {
+    OperationBuilder::new(operation_name, caller_location(ctx))
+        .add_attributes(&[(
+            Identifier::new(ctx, "sym_name"),
+            StringAttribute::new(ctx, name).into(),
+        )])
+        .add_regions([{
+            let region = Region::new();
+            let block = region.append_block(Block::new(&[]));
+
+            // Parameters are attributes. The felt parameters are moduli, so
+            // only integer attributes are accepted.
+            let parameters = (0..parameter_count)
+                .map(|_| {
+                    Constraint::Base(Base::Name("#builtin.integer".to_string())).append(ctx, &block)
+                })
+                .collect::<Vec<_>>();
+            block.append_operation(irdl::parameters(ctx, &parameters, caller_location(ctx)).into());
+
+            region
+        }])
+        .build()
+        .unwrap()
+}
+
+/// Returns the `!felt.felt<modulus>` type. The felt dialect must be loaded.
+pub fn felt_type(ctx: &Context, modulus: u32) -> Type<'_> {
+    Type::parse(ctx, &format!("!felt.felt<{modulus} : i32>")).unwrap()
+}
+
+/// Returns the `#felt.modulus<modulus>` attribute. The felt dialect must be
+/// loaded.
+pub fn modulus_attribute(ctx: &Context, modulus: u32) -> Attribute<'_> {
+    Attribute::parse(ctx, &format!("#felt.modulus<{modulus} : i32>")).unwrap()
+}
+
+/// Builds an `irdl.operation` that receives `operand_count` felts, and
+/// returns a single felt. Felts are either single-limb `i32` values, or
+/// multi-limb `i256` values (see `montgomery::lower_multi_limb`).
+fn build_operation<'c>(ctx: &'c Context, name: &str, operand_count: usize) -> Operation<'c> {
     let irdl_attribute_type = Type::parse(ctx, "!irdl.attribute").unwrap();
     let u32_type: Type<'_> = IntegerType::new(ctx, 32).into();
     let u32_type_attribute: Attribute<'_> = TypeAttribute::new(u32_type).into();
+    let u256_type: Type<'_> = IntegerType::new(ctx, 256).into();
+    let u256_type_attribute: Attribute<'_> = TypeAttribute::new(u256_type).into();
 
-    module.body().append_operation(
-        irdl::dialect(
-            ctx,
-            {
-                let region = Region::new();
-                let block = region.append_block(Block::new(&[]));
+    irdl::_operation(
+        ctx,
+        {
+            let region = Region::new();
+            let block = region.append_block(Block::new(&[]));
 
-                block.append_operation(
-                    irdl::_operation(
+            // The felt operations should only operate with u32 values, or
+            // with u256 values made of four 64-bit limbs.
+            let is_u32 = block
+                .append_op_result(
+                    irdl::is(
+                        ctx,
+                        irdl_attribute_type,
+                        u32_type_attribute,
+                        caller_location(ctx),
+                    )
+                    .into(),
+                )
+                .unwrap();
+            let is_u256 = block
+                .append_op_result(
+                    irdl::is(
+                        ctx,
+                        irdl_attribute_type,
+                        u256_type_attribute,
+                        caller_location(ctx),
+                    )
+                    .into(),
+                )
+                .unwrap();
+            // The operands and the result all use this constraint variable,
+            // so they must all have the same type.
+            let is_felt = block
+                .append_op_result(
+                    irdl::any_of(
                         ctx,
-                        {
-                            let region = Region::new();
-                            let block = region.append_block(Block::new(&[]));
-
-                            // The felt.add operation should only operate with
-                            // u32 values.
-                            let is_u32 = block
-                                .append_op_result(
-                                    irdl::is(
-                                        ctx,
-                                        irdl_attribute_type,
-                                        u32_type_attribute,
-                                        location,
-                                    )
-                                    .into(),
-                                )
-                                .unwrap();
-
-                            // This operation specifies that the operation
-                            // receives two arguments, each an u32.
-                            //
-                            // The operands may be single, optional,
-                            // or variadic. To specify this, we use the
-                            // `variadicity_array` attribute. There is no
-                            // way to prograamatically  the variadicity_array
-                            // attribute, so we rely on the attribute parsing
-                            // logic.
-                            block.append_operation(
-                                irdl::operands(
-                                    ctx,
-                                    &[is_u32, is_u32],
-                                    Attribute::parse(
-                                        ctx,
-                                        "#irdl<variadicity_array[single, single]>",
-                                    )
-                                    .unwrap(),
-                                    location,
-                                )
-                                .into(),
-                            );
-
-                            // This specifies that the operation returns a
-                            // single u32 value.
-                            //
-                            // Again, the result types may be variadic, so we
-                            // use the `variadicity_array` attribute.
-                            block.append_operation(
-                                irdl::results(
-                                    ctx,
-                                    &[is_u32],
-                                    Attribute::parse(ctx, "#irdl<variadicity_array[single]>")
-                                        .unwrap(),
-                                    location,
-                                )
-                                .into(),
-                            );
-
-                            region
-                        },
-                        StringAttribute::new(ctx, "add"),
-                        location,
+                        irdl_attribute_type,
+                        &[is_u32, is_u256],
+                        caller_location(ctx),
                     )
                     .into(),
-                );
+                )
+                .unwrap();
 
-                region
-            },
-            StringAttribute::new(ctx, "felt"),
-            location,
-        )
-        .into(),
-    );
+            // This operation specifies that the operation receives
+            // `operand_count` arguments, each a felt.
+            //
+            // The operands may be single, optional, or variadic. To specify
+            // this, we use the `variadicity_array` attribute. There is no way
+            // to programmatically build the variadicity_array attribute, so we
+            // rely on the attribute parsing logic.
+            block.append_operation(
+                irdl::operands(
+                    ctx,
+                    &vec![is_felt; operand_count],
+                    Attribute::parse(
+                        ctx,
+                        &format!(
+                            "#irdl<variadicity_array[{}]>",
+                            vec!["single"; operand_count].join(", ")
+                        ),
+                    )
+                    .unwrap(),
+                    caller_location(ctx),
+                )
+                .into(),
+            );
 
-    module
+            // This specifies that the operation returns a single felt.
+            //
+            // Again, the result types may be variadic, so we use the
+            // `variadicity_array` attribute.
+            block.append_operation(
+                irdl::results(
+                    ctx,
+                    &[is_felt],
+                    Attribute::parse(ctx, "#irdl<variadicity_array[single]>").unwrap(),
+                    caller_location(ctx),
+                )
+                .into(),
+            );
+
+            region
+        },
+        StringAttribute::new(ctx, name),
+        caller_location(ctx),
+    )
+    .into()
 }
 
 #[cfg(test)]
 mod test {
+    use melior::{
+        Context,
+        ir::{Attribute, BlockLike, Type, r#type::IntegerType},
+        utility::load_irdl_dialects,
+    };
+
     use crate::{
         initialize_context,
-        irdl::{build_dialect_module, load_dialect_module},
+        irdl::{
+            Base, Constraint, Definitions,
+            Variadicity::{Optional, Single},
+            append_region_constraint, build_constrained_operation, build_dialect,
+            build_dialect_module, felt_type, load_dialect_module, modulus_attribute,
+        },
+        parse_module,
     };
 
     #[test]
@@ -140,4 +628,325 @@ mod test {
             loaded_module.as_operation().to_string()
         )
     }
+
+    #[test]
+    fn types_and_attributes() {
+        let context = initialize_context();
+        load_irdl_dialects(&build_dialect_module(&context));
+
+        assert_eq!(felt_type(&context, 13).to_string(), "!felt.felt<13 : i32>");
+        assert_eq!(
+            modulus_attribute(&context, 13).to_string(),
+            "#felt.modulus<13 : i32>"
+        );
+        // The number and the kind of the parameters are checked.
+        assert!(Type::parse(&context, "!felt.felt<13 : i32, 17 : i32>").is_none());
+        assert!(Type::parse(&context, "!felt.felt<\"13\">").is_none());
+        assert!(Attribute::parse(&context, "#felt.modulus<i32>").is_none());
+
+        let module = parse_module(
+            &context,
+            r#"
+            module {
+              func.func @identity(%x: !felt.felt<13 : i32>) -> !felt.felt<13 : i32>
+                  attributes {felt.modulus = #felt.modulus<13 : i32>} {
+                return %x : !felt.felt<13 : i32>
+              }
+            }"#,
+            "types_and_attributes",
+        )
+        .unwrap();
+        assert!(module.as_operation().verify());
+    }
+
+    /// Loads the felt dialect, and a `typed` dialect with constrained
+    /// operations:
+    ///
+    /// - `add` over `i32` or `i64` values, all of the same type.
+    /// - `mul` over felts, all of the same field.
+    /// - `convert` from any felt, to an `i32` or `i64` integer.
+    /// - `maybe`, with an optional operand and an optional result.
+    /// - `scope`, with an unconstrained region.
+    fn load_typed_dialect(context: &Context) {
+        let integer = || {
+            Constraint::AnyOf(vec![
+                Constraint::is_type(IntegerType::new(context, 32).into()),
+                Constraint::is_type(IntegerType::new(context, 64).into()),
+            ])
+        };
+
+        let module = build_dialect_module(context);
+        module.body().append_operation(build_dialect(
+            context,
+            "typed",
+            [
+                build_constrained_operation(context, "add", |block| {
+                    let integer = integer().append(context, block);
+                    Definitions {
+                        operands: vec![(integer, Single), (integer, Single)],
+                        results: vec![(integer, Single)],
+                        regions: vec![],
+                    }
+                }),
+                build_constrained_operation(context, "mul", |block| {
+                    let felt =
+                        Constraint::Parametric("@felt::@felt".to_string(), vec![Constraint::Any])
+                            .append(context, block);
+                    Definitions {
+                        operands: vec![(felt, Single), (felt, Single)],
+                        results: vec![(felt, Single)],
+                        regions: vec![],
+                    }
+                }),
+                build_constrained_operation(context, "convert", |block| {
+                    let felt = Constraint::Base(Base::Definition("@felt::@felt".to_string()))
+                        .append(context, block);
+                    let integer = Constraint::AllOf(vec![
+                        Constraint::Base(Base::Name("!builtin.integer".to_string())),
+                        integer(),
+                    ])
+                    .append(context, block);
+                    Definitions {
+                        operands: vec![(felt, Single)],
+                        results: vec![(integer, Single)],
+                        regions: vec![],
+                    }
+                }),
+                build_constrained_operation(context, "maybe", |block| {
+                    let integer = integer().append(context, block);
+                    Definitions {
+                        operands: vec![(integer, Optional)],
+                        results: vec![(integer, Optional)],
+                        regions: vec![],
+                    }
+                }),
+                build_constrained_operation(context, "scope", |block| {
+                    let body = append_region_constraint(context, block, None, None);
+                    Definitions {
+                        operands: vec![],
+                        results: vec![],
+                        regions: vec![body],
+                    }
+                }),
+            ],
+        ));
+        load_irdl_dialects(&module);
+    }
+
+    /// Whether a function with the given arguments, and the operation, passes
+    /// the verifier.
+    fn verifies(context: &Context, arguments: &str, operation: &str) -> bool {
+        parse_module(
+            context,
+            &format!("module {{ func.func @f({arguments}) {{ {operation} return }} }}"),
+            "verifies",
+        )
+        .is_some()
+    }
+
+    #[test]
+    fn any_of_constraint() {
+        let context = initialize_context();
+        load_typed_dialect(&context);
+
+        let add =
+            |r#type: &str| format!(r#"%0 = "typed.add"(%a, %b) : ({type}, {type}) -> {type}"#);
+        assert!(verifies(&context, "%a: i32, %b: i32", &add("i32")));
+        assert!(verifies(&context, "%a: i64, %b: i64", &add("i64")));
+        assert!(!verifies(&context, "%a: i16, %b: i16", &add("i16")));
+        // Every operand and result is constrained by the same variable.
+        assert!(!verifies(
+            &context,
+            "%a: i32, %b: i64",
+            r#"%0 = "typed.add"(%a, %b) : (i32, i64) -> i32"#
+        ));
+    }
+
+    #[test]
+    fn multi_limb_felts() {
+        let context = initialize_context();
+        load_irdl_dialects(&build_dialect_module(&context));
+
+        let mul = |r#type: &str| format!(r#"%0 = "felt.mul"(%a, %b) : ({type}, {type}) -> {type}"#);
+        assert!(verifies(&context, "%a: i32, %b: i32", &mul("i32")));
+        assert!(verifies(&context, "%a: i256, %b: i256", &mul("i256")));
+        assert!(!verifies(&context, "%a: i64, %b: i64", &mul("i64")));
+        // Single-limb and multi-limb felts can't be mixed.
+        assert!(!verifies(
+            &context,
+            "%a: i32, %b: i256",
+            r#"%0 = "felt.mul"(%a, %b) : (i32, i256) -> i256"#
+        ));
+    }
+
+    #[test]
+    fn parametric_constraint() {
+        let context = initialize_context();
+        load_typed_dialect(&context);
+
+        assert!(verifies(
+            &context,
+            "%a: !felt.felt<13 : i32>, %b: !felt.felt<13 : i32>",
+            r#"%0 = "typed.mul"(%a, %b) : (!felt.felt<13 : i32>, !felt.felt<13 : i32>) -> !felt.felt<13 : i32>"#
+        ));
+        assert!(!verifies(
+            &context,
+            "%a: !felt.felt<13 : i32>, %b: !felt.felt<17 : i32>",
+            r#"%0 = "typed.mul"(%a, %b) : (!felt.felt<13 : i32>, !felt.felt<17 : i32>) -> !felt.felt<13 : i32>"#
+        ));
+        assert!(!verifies(
+            &context,
+            "%a: i32, %b: i32",
+            r#"%0 = "typed.mul"(%a, %b) : (i32, i32) -> i32"#
+        ));
+    }
+
+    #[test]
+    fn base_and_all_of_constraints() {
+        let context = initialize_context();
+        load_typed_dialect(&context);
+
+        assert!(verifies(
+            &context,
+            "%a: !felt.felt<17 : i32>",
+            r#"%0 = "typed.convert"(%a) : (!felt.felt<17 : i32>) -> i64"#
+        ));
+        assert!(!verifies(
+            &context,
+            "%a: i32",
+            r#"%0 = "typed.convert"(%a) : (i32) -> i64"#
+        ));
+        assert!(!verifies(
+            &context,
+            "%a: !felt.felt<17 : i32>",
+            r#"%0 = "typed.convert"(%a) : (!felt.felt<17 : i32>) -> i16"#
+        ));
+    }
+
+    #[test]
+    fn variadic_and_optional_operands() {
+        let context = initialize_context();
+        load_typed_dialect(&context);
+
+        assert!(verifies(&context, "", r#"%0 = "felt.sum"() : () -> i32"#));
+        assert!(verifies(
+            &context,
+            "%a: i32, %b: i32",
+            r#"%0 = "felt.sum"(%a, %b, %a) : (i32, i32, i32) -> i32"#
+        ));
+        assert!(!verifies(
+            &context,
+            "%a: i32, %b: i64",
+            r#"%0 = "felt.sum"(%a, %b) : (i32, i64) -> i32"#
+        ));
+        // Sums of felts of the same field.
+        assert!(verifies(
+            &context,
+            "%a: !felt.felt<13 : i32>, %b: !felt.felt<13 : i32>",
+            r#"%0 = "felt.sum"(%a, %b) : (!felt.felt<13 : i32>, !felt.felt<13 : i32>) -> !felt.felt<13 : i32>"#
+        ));
+        assert!(!verifies(
+            &context,
+            "%a: !felt.felt<13 : i32>, %b: !felt.felt<17 : i32>",
+            r#"%0 = "felt.sum"(%a, %b) : (!felt.felt<13 : i32>, !felt.felt<17 : i32>) -> !felt.felt<13 : i32>"#
+        ));
+        assert!(!verifies(
+            &context,
+            "%a: !felt.felt<13 : i32>",
+            r#"%0 = "felt.sum"(%a) : (!felt.felt<13 : i32>) -> i32"#
+        ));
+
+        assert!(verifies(&context, "", r#""typed.maybe"() : () -> ()"#));
+        assert!(verifies(
+            &context,
+            "%a: i64",
+            r#"%0 = "typed.maybe"(%a) : (i64) -> i64"#
+        ));
+        assert!(!verifies(
+            &context,
+            "%a: i64",
+            r#"%0 = "typed.maybe"(%a, %a) : (i64, i64) -> i64"#
+        ));
+    }
+
+    #[test]
+    fn region_constraints() {
+        let context = initialize_context();
+        load_typed_dialect(&context);
+
+        let reduce = |arguments: &str, body: &str| {
+            format!(
+                r#"%0 = "felt.reduce"(%a, %b, %a) ({{
+                ^bb0({arguments}):
+                  {body}
+                }}) : (i32, i32, i32) -> i32"#
+            )
+        };
+        let add = r#"%1 = "felt.add"(%acc, %x) : (i32, i32) -> i32
+                  gpu.yield %1 : i32"#;
+        assert!(verifies(
+            &context,
+            "%a: i32, %b: i32",
+            &reduce("%acc: i32, %x: i32", add)
+        ));
+        // The entry block must receive two i32 values.
+        assert!(!verifies(
+            &context,
+            "%a: i32, %b: i32",
+            &reduce("%acc: i32, %x: i64", "gpu.yield %acc : i32")
+        ));
+        assert!(!verifies(
+            &context,
+            "%a: i32, %b: i32",
+            &reduce("%acc: i32", "gpu.yield %acc : i32")
+        ));
+        // The body must be a single block.
+        assert!(!verifies(
+            &context,
+            "%a: i32, %b: i32",
+            &reduce(
+                "%acc: i32, %x: i32",
+                "cf.br ^bb1
+                ^bb1:
+                  gpu.yield %acc : i32"
+            )
+        ));
+
+        let map = |types: &str, body: &str| {
+            format!(
+                r#""felt.map"(%a, %b) ({{
+                ^bb0(%x: i32):
+                  {body}
+                }}) : ({types}) -> ()"#
+            )
+        };
+        let square = r#"%1 = "felt.mul"(%x, %x) : (i32, i32) -> i32
+                  gpu.yield %1 : i32"#;
+        assert!(verifies(
+            &context,
+            "%a: memref<?xi32>, %b: memref<?xi32>",
+            &map("memref<?xi32>, memref<?xi32>", square)
+        ));
+        // The input and the output must have the same type.
+        assert!(!verifies(
+            &context,
+            "%a: memref<?xi32>, %b: memref<4xi32>",
+            &map("memref<?xi32>, memref<4xi32>", square)
+        ));
+        assert!(!verifies(
+            &context,
+            "%a: i32, %b: i32",
+            &map("i32, i32", square)
+        ));
+
+        assert!(verifies(&context, "", r#""typed.scope"() ({}) : () -> ()"#));
+        assert!(verifies(
+            &context,
+            "%a: i64",
+            r#""typed.scope"() ({
+              ^bb0(%x: i64):
+                gpu.yield
+            }) : () -> ()"#
+        ));
+    }
 }
@@ -0,0 +1,206 @@
+//! A small expression language that compiles to the felt dialect.
+//!
+//! A program is a list of functions over field elements:
+//!
+//! ```text
+//! fn f(a: felt, b: felt) -> felt {
+//!     let c = a * b + 3;
+//!     return c / a;
+//! }
+//! ```
+//!
+//! Every function is emitted as a `func.func`, with the arithmetic operators
+//! mapped to `felt.add`, `felt.sub`, `felt.mul`, `felt.div` and `felt.neg`,
+//! literals to `arith.constant`, and calls to `func.call`. The operations are
+//! located at the corresponding source code, so diagnostics and debug info
+//! point back to the program.
+
+use std::{fmt, fs, path::Path};
+
+use melior::{Context, ir::Module};
+
+use crate::error::Error;
+
+pub mod ast;
+mod check;
+mod codegen;
+mod lexer;
+mod parser;
+
+/// A position in the source code. Lines and columns start at 1.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Span {
+    pub line: usize,
+    pub column: usize,
+}
+
+/// A syntax or type error, at a position in the source code.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub span: Span,
+    pub message: String,
+}
+
+impl Diagnostic {
+    pub(crate) fn new(span: Span, message: impl Into<String>) -> Self {
+        Diagnostic {
+            span,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}: {}",
+            self.span.line, self.span.column, self.message
+        )
+    }
+}
+
+/// Parses a program, without checking it. Only the first syntax error is
+/// reported.
+pub fn parse(source: &str) -> Result<ast::Program, Diagnostic> {
+    parser::parse(&lexer::tokenize(source)?)
+}
+
+/// Checks that every name is defined, that calls have the right number of
+/// arguments, and that every function returns a value. All the errors are
+/// reported.
+pub fn check(program: &ast::Program) -> Result<(), Vec<Diagnostic>> {
+    check::check(program)
+}
+
+/// Compiles a program into a module, using `source_name` as the file name of
+/// its locations.
+///
+/// The felt dialect must already be loaded in the context.
+pub fn compile<'c>(ctx: &'c Context, source: &str, source_name: &str) -> Result<Module<'c>, Error> {
+    let error = |diagnostics| Error::Compile {
+        source_name: source_name.to_string(),
+        diagnostics,
+    };
+
+    let program = parse(source).map_err(|diagnostic| error(vec![diagnostic]))?;
+    check(&program).map_err(error)?;
+
+    Ok(codegen::emit_program(ctx, &program, source_name))
+}
+
+/// Compiles the program in the given file.
+pub fn load_program<'c>(ctx: &'c Context, path: impl AsRef<Path>) -> Result<Module<'c>, Error> {
+    let path = path.as_ref();
+    compile(ctx, &fs::read_to_string(path)?, &path.display().to_string())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        error::Error,
+        execute_entrypoint, initialize_context,
+        lang::{Diagnostic, Span, compile, parse},
+        pdl::build_pattern_module,
+        pipeline::PipelineOptions,
+        testing::{felt_context, lower},
+    };
+
+    const PROGRAM: &str = "\
+fn entrypoint(a: felt, b: felt) -> felt {
+    let c = a * b + 3;
+    return c / a;
+}";
+
+    #[test]
+    fn compile_and_execute() {
+        let context = felt_context();
+
+        let mut module = compile(&context, PROGRAM, "program.felt").unwrap();
+        let source = module.as_operation().to_string();
+        assert!(source.contains("felt.mul"));
+        assert!(source.contains("felt.div"));
+
+        lower(
+            &context,
+            &mut module,
+            build_pattern_module(&context),
+            &PipelineOptions::default(),
+        );
+
+        // c = 10 * 7 + 3 = 8, and the inverse of 10 is 4, so c / a = 32 = 6.
+        assert_eq!(execute_entrypoint(&module, 10, 7), 6);
+    }
+
+    #[test]
+    fn every_operator() {
+        let context = felt_context();
+
+        let mut module = compile(
+            &context,
+            "\
+fn square(x: felt) -> felt { return x * x; }
+fn entrypoint(a: felt, b: felt) -> felt {
+    return square(a - b) + -b / (a - 1);
+}",
+            "program.felt",
+        )
+        .unwrap();
+
+        lower(
+            &context,
+            &mut module,
+            build_pattern_module(&context),
+            &PipelineOptions::default(),
+        );
+
+        // (3 - 5)^2 = 4, -5 = 8 and the inverse of 2 is 7, so the result is
+        // 4 + 8 * 7 = 60 = 8.
+        assert_eq!(execute_entrypoint(&module, 3, 5), 8);
+    }
+
+    #[test]
+    fn syntax_error() {
+        assert_eq!(
+            parse("fn f(a: felt) -> felt {\n    return a +;\n}"),
+            Err(Diagnostic {
+                span: Span {
+                    line: 2,
+                    column: 15
+                },
+                message: "expected an expression, found `;`".to_string(),
+            })
+        );
+    }
+
+    #[test]
+    fn type_errors() {
+        let context = initialize_context();
+
+        let Err(Error::Compile { diagnostics, .. }) = compile(
+            &context,
+            "\
+fn f(a: felt, a: u64) -> felt {
+    let b = g(a);
+    return f(c);
+}",
+            "program.felt",
+        ) else {
+            panic!("expected compile errors");
+        };
+        let diagnostics = diagnostics
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            diagnostics,
+            [
+                "1:15: parameter `a` is defined more than once",
+                "1:18: unknown type `u64`",
+                "2:13: unknown function `g`",
+                "3:12: function `f` expects 2 arguments, found 1",
+                "3:14: unknown variable `c`",
+            ]
+        );
+    }
+}
@@ -0,0 +1,77 @@
+use crate::lang::Span;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Program {
+    pub functions: Vec<Function>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Function {
+    pub name: String,
+    pub parameters: Vec<Parameter>,
+    pub return_type: TypeName,
+    pub body: Vec<Statement>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Parameter {
+    pub name: String,
+    pub r#type: TypeName,
+    pub span: Span,
+}
+
+/// A type, as written in the source code. The only known type is `felt`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TypeName {
+    pub name: String,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Statement {
+    Let {
+        name: String,
+        value: Expression,
+        span: Span,
+    },
+    Return {
+        value: Expression,
+        span: Span,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Expression {
+    pub kind: ExpressionKind,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExpressionKind {
+    Literal(u64),
+    Variable(String),
+    Binary(BinaryOperator, Box<Expression>, Box<Expression>),
+    Negate(Box<Expression>),
+    Call(String, Vec<Expression>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryOperator {
+    Add,
+    Sub,
+    Mul,
+    Div,
+}
+
+impl BinaryOperator {
+    /// The felt operation that implements the operator.
+    pub fn operation_name(self) -> &'static str {
+        match self {
+            BinaryOperator::Add => "felt.add",
+            BinaryOperator::Sub => "felt.sub",
+            BinaryOperator::Mul => "felt.mul",
+            BinaryOperator::Div => "felt.div",
+        }
+    }
+}
@@ -0,0 +1,147 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::lang::{
+    Diagnostic, Span,
+    ast::{Expression, ExpressionKind, Function, Program, Statement, TypeName},
+};
+
+/// The types of the language. Every value is a field element, so type checking
+/// is mostly about names and arities.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Type {
+    Felt,
+}
+
+impl Type {
+    fn resolve(name: &TypeName) -> Option<Self> {
+        match name.name.as_str() {
+            "felt" => Some(Type::Felt),
+            _ => None,
+        }
+    }
+}
+
+pub fn check(program: &Program) -> Result<(), Vec<Diagnostic>> {
+    let mut checker = Checker {
+        functions: HashMap::new(),
+        diagnostics: Vec::new(),
+    };
+
+    // Functions can be called before they are defined, so we collect every
+    // signature first.
+    for function in &program.functions {
+        if checker
+            .functions
+            .insert(function.name.as_str(), function.parameters.len())
+            .is_some()
+        {
+            checker.error(
+                function.span,
+                format!("function `{}` is defined more than once", function.name),
+            );
+        }
+    }
+
+    for function in &program.functions {
+        checker.function(function);
+    }
+
+    if checker.diagnostics.is_empty() {
+        Ok(())
+    } else {
+        Err(checker.diagnostics)
+    }
+}
+
+struct Checker<'a> {
+    /// The number of parameters of each function.
+    functions: HashMap<&'a str, usize>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl<'a> Checker<'a> {
+    fn function(&mut self, function: &'a Function) {
+        let mut variables = HashSet::new();
+        for parameter in &function.parameters {
+            if !variables.insert(parameter.name.as_str()) {
+                self.error(
+                    parameter.span,
+                    format!("parameter `{}` is defined more than once", parameter.name),
+                );
+            }
+            self.r#type(&parameter.r#type);
+        }
+        self.r#type(&function.return_type);
+
+        let mut returned = false;
+        for statement in &function.body {
+            if returned {
+                let (Statement::Let { span, .. } | Statement::Return { span, .. }) = statement;
+                self.error(*span, "unreachable statement after `return`");
+                break;
+            }
+
+            match statement {
+                // Variables can be shadowed, so that a value can be rebound
+                // to the same name.
+                Statement::Let { name, value, .. } => {
+                    self.expression(value, &variables);
+                    variables.insert(name.as_str());
+                }
+                Statement::Return { value, .. } => {
+                    self.expression(value, &variables);
+                    returned = true;
+                }
+            }
+        }
+
+        if !returned {
+            self.error(
+                function.span,
+                format!("function `{}` does not return a value", function.name),
+            );
+        }
+    }
+
+    fn r#type(&mut self, name: &TypeName) {
+        if Type::resolve(name).is_none() {
+            self.error(name.span, format!("unknown type `{}`", name.name));
+        }
+    }
+
+    fn expression(&mut self, expression: &Expression, variables: &HashSet<&str>) {
+        match &expression.kind {
+            ExpressionKind::Literal(_) => {}
+            ExpressionKind::Variable(name) => {
+                if !variables.contains(name.as_str()) {
+                    self.error(expression.span, format!("unknown variable `{name}`"));
+                }
+            }
+            ExpressionKind::Binary(_, lhs, rhs) => {
+                self.expression(lhs, variables);
+                self.expression(rhs, variables);
+            }
+            ExpressionKind::Negate(operand) => self.expression(operand, variables),
+            ExpressionKind::Call(name, arguments) => {
+                match self.functions.get(name.as_str()).copied() {
+                    None => self.error(expression.span, format!("unknown function `{name}`")),
+                    Some(count) if count != arguments.len() => self.error(
+                        expression.span,
+                        format!(
+                            "function `{name}` expects {count} arguments, found {}",
+                            arguments.len()
+                        ),
+                    ),
+                    Some(_) => {}
+                }
+                for argument in arguments {
+                    self.expression(argument, variables);
+                }
+            }
+        }
+    }
+
+    fn error(&mut self, span: Span, message: impl Into<String>) {
+        self.diagnostics.push(Diagnostic::new(span, message));
+    }
+}
@@ -0,0 +1,158 @@
+use std::collections::HashMap;
+
+use melior::{
+    Context,
+    dialect::{arith, func},
+    helpers::BuiltinBlockExt,
+    ir::{
+        Attribute, Block, BlockLike, Identifier, Location, Module, Region, Type, Value,
+        attribute::{FlatSymbolRefAttribute, IntegerAttribute, StringAttribute, TypeAttribute},
+        operation::{Operation, OperationBuilder},
+        r#type::{FunctionType, IntegerType},
+    },
+};
+
+use crate::{
+    lang::{
+        Span,
+        ast::{Expression, ExpressionKind, Function, Program, Statement},
+    },
+    pdl::MODULUS,
+};
+
+/// Emits a checked program. Every function is emitted with the
+/// `llvm.emit_c_interface` attribute, like the core module.
+pub fn emit_program<'c>(ctx: &'c Context, program: &Program, source_name: &str) -> Module<'c> {
+    let module = Module::new(Location::new(ctx, source_name, 1, 1));
+    for function in &program.functions {
+        module
+            .body()
+            .append_operation(emit_function(ctx, function, source_name));
+    }
+    module
+}
+
+fn emit_function<'c>(ctx: &'c Context, function: &Function, source_name: &str) -> Operation<'c> {
+    let location = |span: Span| Location::new(ctx, source_name, span.line, span.column);
+    let felt_type: Type<'c> = IntegerType::new(ctx, 32).into();
+
+    let region = Region::new();
+    let block = region.append_block(Block::new(
+        &function
+            .parameters
+            .iter()
+            .map(|parameter| (felt_type, location(parameter.span)))
+            .collect::<Vec<_>>(),
+    ));
+
+    {
+        let mut emitter = FunctionEmitter {
+            ctx,
+            block: &block,
+            source_name,
+            felt_type,
+            variables: HashMap::new(),
+        };
+        for (index, parameter) in function.parameters.iter().enumerate() {
+            let argument = emitter.block.arg(index).unwrap();
+            emitter.variables.insert(parameter.name.as_str(), argument);
+        }
+        for statement in &function.body {
+            emitter.statement(statement);
+        }
+    }
+
+    func::func(
+        ctx,
+        StringAttribute::new(ctx, &function.name),
+        TypeAttribute::new(
+            FunctionType::new(
+                ctx,
+                &vec![felt_type; function.parameters.len()],
+                &[felt_type],
+            )
+            .into(),
+        ),
+        region,
+        &[(
+            Identifier::new(ctx, "llvm.emit_c_interface"),
+            Attribute::unit(ctx),
+        )],
+        location(function.span),
+    )
+}
+
+/// Appends the operations of a function body to its entry block.
+struct FunctionEmitter<'c, 'a> {
+    ctx: &'c Context,
+    block: &'a Block<'c>,
+    source_name: &'a str,
+    felt_type: Type<'c>,
+    variables: HashMap<&'a str, Value<'c, 'a>>,
+}
+
+impl<'c, 'a> FunctionEmitter<'c, 'a> {
+    fn statement(&mut self, statement: &'a Statement) {
+        match statement {
+            Statement::Let { name, value, .. } => {
+                let value = self.expression(value);
+                self.variables.insert(name.as_str(), value);
+            }
+            Statement::Return { value, span } => {
+                let value = self.expression(value);
+                self.block
+                    .append_operation(func::r#return(&[value], self.location(*span)));
+            }
+        }
+    }
+
+    fn expression(&self, expression: &Expression) -> Value<'c, 'a> {
+        let location = self.location(expression.span);
+        let operation = match &expression.kind {
+            // Literals are reduced, so that every value is in canonical form.
+            ExpressionKind::Literal(value) => arith::constant(
+                self.ctx,
+                IntegerAttribute::new(self.felt_type, (value % u64::from(MODULUS)) as i64).into(),
+                location,
+            ),
+            // The checker already verified that every variable is defined.
+            ExpressionKind::Variable(name) => return self.variables[name.as_str()],
+            ExpressionKind::Binary(operator, lhs, rhs) => {
+                let lhs = self.expression(lhs);
+                let rhs = self.expression(rhs);
+                OperationBuilder::new(operator.operation_name(), location)
+                    .add_operands(&[lhs, rhs])
+                    .add_results(&[self.felt_type])
+                    .build()
+                    .unwrap()
+            }
+            ExpressionKind::Negate(operand) => {
+                let operand = self.expression(operand);
+                OperationBuilder::new("felt.neg", location)
+                    .add_operands(&[operand])
+                    .add_results(&[self.felt_type])
+                    .build()
+                    .unwrap()
+            }
+            ExpressionKind::Call(name, arguments) => {
+                let arguments = arguments
+                    .iter()
+                    .map(|argument| self.expression(argument))
+                    .collect::<Vec<_>>();
+                func::call(
+                    self.ctx,
+                    FlatSymbolRefAttribute::new(self.ctx, name),
+                    &arguments,
+                    &[self.felt_type],
+                    location,
+                )
+            }
+        };
+
+        self.block.append_op_result(operation).unwrap()
+    }
+
+    fn location(&self, span: Span) -> Location<'c> {
+        Location::new(self.ctx, self.source_name, span.line, span.column)
+    }
+}
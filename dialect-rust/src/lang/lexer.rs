@@ -0,0 +1,177 @@
+use std::{fmt, iter::Peekable, str::Chars};
+
+use crate::lang::{Diagnostic, Span};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TokenKind {
+    Fn,
+    Let,
+    Return,
+    Identifier(String),
+    Integer(u64),
+    LeftParen,
+    RightParen,
+    LeftBrace,
+    RightBrace,
+    Comma,
+    Colon,
+    Semicolon,
+    Arrow,
+    Equal,
+    Plus,
+    Minus,
+    Star,
+    Slash,
+    Eof,
+}
+
+impl fmt::Display for TokenKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TokenKind::Fn => write!(f, "`fn`"),
+            TokenKind::Let => write!(f, "`let`"),
+            TokenKind::Return => write!(f, "`return`"),
+            TokenKind::Identifier(name) => write!(f, "`{name}`"),
+            TokenKind::Integer(value) => write!(f, "`{value}`"),
+            TokenKind::LeftParen => write!(f, "`(`"),
+            TokenKind::RightParen => write!(f, "`)`"),
+            TokenKind::LeftBrace => write!(f, "`{{`"),
+            TokenKind::RightBrace => write!(f, "`}}`"),
+            TokenKind::Comma => write!(f, "`,`"),
+            TokenKind::Colon => write!(f, "`:`"),
+            TokenKind::Semicolon => write!(f, "`;`"),
+            TokenKind::Arrow => write!(f, "`->`"),
+            TokenKind::Equal => write!(f, "`=`"),
+            TokenKind::Plus => write!(f, "`+`"),
+            TokenKind::Minus => write!(f, "`-`"),
+            TokenKind::Star => write!(f, "`*`"),
+            TokenKind::Slash => write!(f, "`/`"),
+            TokenKind::Eof => write!(f, "end of file"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub span: Span,
+}
+
+/// Splits the source code into tokens. The last token is always `Eof`.
+///
+/// Whitespace and `//` comments are skipped.
+pub fn tokenize(source: &str) -> Result<Vec<Token>, Diagnostic> {
+    let mut tokens = Vec::new();
+    let mut cursor = Cursor {
+        characters: source.chars().peekable(),
+        span: Span { line: 1, column: 1 },
+    };
+
+    while let Some(character) = cursor.peek() {
+        let start = cursor.span;
+
+        let kind = match character {
+            _ if character.is_whitespace() => {
+                cursor.advance();
+                continue;
+            }
+            '/' => {
+                cursor.advance();
+                if cursor.peek() == Some('/') {
+                    while cursor.peek().is_some_and(|c| c != '\n') {
+                        cursor.advance();
+                    }
+                    continue;
+                }
+                TokenKind::Slash
+            }
+            '-' => {
+                cursor.advance();
+                if cursor.peek() == Some('>') {
+                    cursor.advance();
+                    TokenKind::Arrow
+                } else {
+                    TokenKind::Minus
+                }
+            }
+            '0'..='9' => {
+                let digits = cursor.take_while(|c| c.is_ascii_digit());
+                let value = digits.parse().map_err(|_| {
+                    Diagnostic::new(start, format!("integer literal `{digits}` is too large"))
+                })?;
+                TokenKind::Integer(value)
+            }
+            _ if character.is_alphabetic() || character == '_' => {
+                let word = cursor.take_while(|c| c.is_alphanumeric() || c == '_');
+                match word.as_str() {
+                    "fn" => TokenKind::Fn,
+                    "let" => TokenKind::Let,
+                    "return" => TokenKind::Return,
+                    _ => TokenKind::Identifier(word),
+                }
+            }
+            _ => {
+                cursor.advance();
+                match character {
+                    '(' => TokenKind::LeftParen,
+                    ')' => TokenKind::RightParen,
+                    '{' => TokenKind::LeftBrace,
+                    '}' => TokenKind::RightBrace,
+                    ',' => TokenKind::Comma,
+                    ':' => TokenKind::Colon,
+                    ';' => TokenKind::Semicolon,
+                    '=' => TokenKind::Equal,
+                    '+' => TokenKind::Plus,
+                    '*' => TokenKind::Star,
+                    _ => {
+                        return Err(Diagnostic::new(
+                            start,
+                            format!("unexpected character `{character}`"),
+                        ));
+                    }
+                }
+            }
+        };
+
+        tokens.push(Token { kind, span: start });
+    }
+
+    tokens.push(Token {
+        kind: TokenKind::Eof,
+        span: cursor.span,
+    });
+    Ok(tokens)
+}
+
+/// Iterates over the characters of the source code, keeping track of the
+/// position of the next one.
+struct Cursor<'a> {
+    characters: Peekable<Chars<'a>>,
+    span: Span,
+}
+
+impl Cursor<'_> {
+    fn peek(&mut self) -> Option<char> {
+        self.characters.peek().copied()
+    }
+
+    fn advance(&mut self) -> Option<char> {
+        let character = self.characters.next()?;
+        if character == '\n' {
+            self.span.line += 1;
+            self.span.column = 1;
+        } else {
+            self.span.column += 1;
+        }
+        Some(character)
+    }
+
+    fn take_while(&mut self, predicate: impl Fn(char) -> bool) -> String {
+        let mut string = String::new();
+        while let Some(character) = self.peek().filter(|&c| predicate(c)) {
+            string.push(character);
+            self.advance();
+        }
+        string
+    }
+}
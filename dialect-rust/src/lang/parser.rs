@@ -0,0 +1,241 @@
+use crate::lang::{
+    Diagnostic, Span,
+    ast::{
+        BinaryOperator, Expression, ExpressionKind, Function, Parameter, Program, Statement,
+        TypeName,
+    },
+    lexer::{Token, TokenKind},
+};
+
+/// Parses a program with a recursive descent parser. The grammar is:
+///
+/// ```text
+/// program    := function*
+/// function   := "fn" identifier "(" (parameter ("," parameter)* ","?)? ")"
+///               "->" type "{" statement* "}"
+/// parameter  := identifier ":" type
+/// statement  := "let" identifier "=" expression ";" | "return" expression ";"
+/// expression := term (("+" | "-") term)*
+/// term       := unary (("*" | "/") unary)*
+/// unary      := "-" unary | primary
+/// primary    := integer | identifier | identifier "(" arguments ")"
+///             | "(" expression ")"
+/// ```
+pub fn parse(tokens: &[Token]) -> Result<Program, Diagnostic> {
+    let mut parser = Parser {
+        tokens,
+        position: 0,
+    };
+
+    let mut functions = Vec::new();
+    while parser.peek().kind != TokenKind::Eof {
+        functions.push(parser.function()?);
+    }
+
+    Ok(Program { functions })
+}
+
+struct Parser<'a> {
+    tokens: &'a [Token],
+    position: usize,
+}
+
+impl Parser<'_> {
+    fn function(&mut self) -> Result<Function, Diagnostic> {
+        let span = self.expect(TokenKind::Fn)?;
+        let (name, _) = self.identifier()?;
+
+        self.expect(TokenKind::LeftParen)?;
+        let parameters = self.list(TokenKind::RightParen, |parser| {
+            let (name, span) = parser.identifier()?;
+            parser.expect(TokenKind::Colon)?;
+            let r#type = parser.type_name()?;
+            Ok(Parameter { name, r#type, span })
+        })?;
+
+        self.expect(TokenKind::Arrow)?;
+        let return_type = self.type_name()?;
+
+        self.expect(TokenKind::LeftBrace)?;
+        let mut body = Vec::new();
+        while !self.eat(&TokenKind::RightBrace) {
+            body.push(self.statement()?);
+        }
+
+        Ok(Function {
+            name,
+            parameters,
+            return_type,
+            body,
+            span,
+        })
+    }
+
+    fn type_name(&mut self) -> Result<TypeName, Diagnostic> {
+        let (name, span) = self.identifier()?;
+        Ok(TypeName { name, span })
+    }
+
+    fn statement(&mut self) -> Result<Statement, Diagnostic> {
+        let span = self.peek().span;
+        let statement = if self.eat(&TokenKind::Let) {
+            let (name, _) = self.identifier()?;
+            self.expect(TokenKind::Equal)?;
+            let value = self.expression()?;
+            Statement::Let { name, value, span }
+        } else if self.eat(&TokenKind::Return) {
+            let value = self.expression()?;
+            Statement::Return { value, span }
+        } else {
+            return Err(self.unexpected("a statement"));
+        };
+        self.expect(TokenKind::Semicolon)?;
+
+        Ok(statement)
+    }
+
+    fn expression(&mut self) -> Result<Expression, Diagnostic> {
+        let mut lhs = self.term()?;
+        loop {
+            let operator = match self.peek().kind {
+                TokenKind::Plus => BinaryOperator::Add,
+                TokenKind::Minus => BinaryOperator::Sub,
+                _ => return Ok(lhs),
+            };
+            let span = self.bump().span;
+            let rhs = self.term()?;
+            lhs = Expression {
+                kind: ExpressionKind::Binary(operator, Box::new(lhs), Box::new(rhs)),
+                span,
+            };
+        }
+    }
+
+    fn term(&mut self) -> Result<Expression, Diagnostic> {
+        let mut lhs = self.unary()?;
+        loop {
+            let operator = match self.peek().kind {
+                TokenKind::Star => BinaryOperator::Mul,
+                TokenKind::Slash => BinaryOperator::Div,
+                _ => return Ok(lhs),
+            };
+            let span = self.bump().span;
+            let rhs = self.unary()?;
+            lhs = Expression {
+                kind: ExpressionKind::Binary(operator, Box::new(lhs), Box::new(rhs)),
+                span,
+            };
+        }
+    }
+
+    fn unary(&mut self) -> Result<Expression, Diagnostic> {
+        let span = self.peek().span;
+        if self.eat(&TokenKind::Minus) {
+            let operand = self.unary()?;
+            return Ok(Expression {
+                kind: ExpressionKind::Negate(Box::new(operand)),
+                span,
+            });
+        }
+        self.primary()
+    }
+
+    fn primary(&mut self) -> Result<Expression, Diagnostic> {
+        let token = self.peek().clone();
+        let kind = match token.kind {
+            TokenKind::Integer(value) => {
+                self.bump();
+                ExpressionKind::Literal(value)
+            }
+            TokenKind::Identifier(name) => {
+                self.bump();
+                if self.eat(&TokenKind::LeftParen) {
+                    let arguments = self.list(TokenKind::RightParen, Self::expression)?;
+                    ExpressionKind::Call(name, arguments)
+                } else {
+                    ExpressionKind::Variable(name)
+                }
+            }
+            TokenKind::LeftParen => {
+                self.bump();
+                let expression = self.expression()?;
+                self.expect(TokenKind::RightParen)?;
+                return Ok(expression);
+            }
+            _ => return Err(self.unexpected("an expression")),
+        };
+
+        Ok(Expression {
+            kind,
+            span: token.span,
+        })
+    }
+
+    /// Parses a comma separated list, with an optional trailing comma, up to
+    /// the closing token.
+    fn list<T>(
+        &mut self,
+        close: TokenKind,
+        mut element: impl FnMut(&mut Self) -> Result<T, Diagnostic>,
+    ) -> Result<Vec<T>, Diagnostic> {
+        let mut elements = Vec::new();
+        while !self.eat(&close) {
+            elements.push(element(self)?);
+            if !self.eat(&TokenKind::Comma) {
+                if self.eat(&close) {
+                    break;
+                }
+                return Err(self.unexpected(&format!("`,` or {close}")));
+            }
+        }
+        Ok(elements)
+    }
+
+    fn identifier(&mut self) -> Result<(String, Span), Diagnostic> {
+        match &self.peek().kind {
+            TokenKind::Identifier(name) => {
+                let name = name.clone();
+                Ok((name, self.bump().span))
+            }
+            _ => Err(self.unexpected("an identifier")),
+        }
+    }
+
+    fn peek(&self) -> &Token {
+        &self.tokens[self.position]
+    }
+
+    /// Consumes the current token. The `Eof` token is never consumed.
+    fn bump(&mut self) -> &Token {
+        let token = &self.tokens[self.position];
+        if token.kind != TokenKind::Eof {
+            self.position += 1;
+        }
+        token
+    }
+
+    /// Consumes the current token if it is of the given kind.
+    fn eat(&mut self, kind: &TokenKind) -> bool {
+        let matches = &self.peek().kind == kind;
+        if matches {
+            self.bump();
+        }
+        matches
+    }
+
+    fn expect(&mut self, kind: TokenKind) -> Result<Span, Diagnostic> {
+        if self.peek().kind == kind {
+            Ok(self.bump().span)
+        } else {
+            Err(self.unexpected(&kind.to_string()))
+        }
+    }
+
+    fn unexpected(&self, expected: &str) -> Diagnostic {
+        let token = self.peek();
+        Diagnostic::new(
+            token.span,
+            format!("expected {expected}, found {}", token.kind),
+        )
+    }
+}
@@ -0,0 +1,269 @@
+use std::fmt;
+
+use melior::ir::{Module, operation::OperationLike};
+
+use crate::{error::Error, walk::walk};
+
+/// An operation that was expected to be rewritten, but is still present in
+/// the module.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IllegalOperation {
+    pub name: String,
+    pub location: String,
+}
+
+/// Operations that PDL patterns can't lower, with what lowers them instead.
+const LOWERED_OUTSIDE_PATTERNS: [(&str, &str); 2] = [
+    (
+        "felt.reduce",
+        "PDL patterns can't clone its body, unroll it with `core::lower_reductions` first",
+    ),
+    (
+        "felt.map",
+        "PDL patterns can't clone its body, lower it with `core::lower_maps` first, which \
+         requires its body to take an element of every input",
+    ),
+];
+
+impl fmt::Display for IllegalOperation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at {}", self.name, self.location)?;
+        if let Some((_, hint)) = LOWERED_OUTSIDE_PATTERNS
+            .iter()
+            .find(|(name, _)| *name == self.name)
+        {
+            write!(f, " ({hint})")?;
+        }
+        Ok(())
+    }
+}
+
+/// How strictly a `ConversionTarget` is checked, either by MLIR's
+/// `applyPartialConversion` and `applyFullConversion` (see
+/// `apply_pdl_conversion`), or by `ConversionTarget::verify`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConversionMode {
+    /// Only operations from illegal dialects are rejected. Operations from
+    /// unknown dialects are left untouched.
+    Partial,
+    /// Every operation must belong to a legal dialect.
+    Full,
+}
+
+/// The set of dialects that are legal, or illegal, after a conversion.
+#[derive(Debug, Clone, Default)]
+pub struct ConversionTarget {
+    legal_dialects: Vec<String>,
+    illegal_dialects: Vec<String>,
+}
+
+impl ConversionTarget {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_legal_dialect(mut self, dialect: &str) -> Self {
+        self.legal_dialects.push(dialect.to_string());
+        self
+    }
+
+    pub fn add_illegal_dialect(mut self, dialect: &str) -> Self {
+        self.illegal_dialects.push(dialect.to_string());
+        self
+    }
+
+    pub fn legal_dialects(&self) -> &[String] {
+        &self.legal_dialects
+    }
+
+    pub fn illegal_dialects(&self) -> &[String] {
+        &self.illegal_dialects
+    }
+
+    /// Checks every operation nested in the module against the target, and
+    /// reports all of the illegal ones. The module operation itself is always
+    /// considered legal.
+    pub fn verify(&self, module: &Module, mode: ConversionMode) -> Result<(), Error> {
+        let mut operations = Vec::new();
+        walk(module.as_operation(), &mut |operation| {
+            if operation == module.as_operation() {
+                return;
+            }
+
+            let name = operation
+                .name()
+                .as_string_ref()
+                .as_str()
+                .unwrap()
+                .to_string();
+            if !self.is_legal(&name, mode) {
+                operations.push(IllegalOperation {
+                    name,
+                    location: operation.location().to_string(),
+                });
+            }
+        });
+
+        if operations.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::IllegalOperations(operations))
+        }
+    }
+
+    fn is_legal(&self, operation_name: &str, mode: ConversionMode) -> bool {
+        let dialect = operation_name
+            .split_once('.')
+            .map_or(operation_name, |(namespace, _)| namespace);
+        let contains = |dialects: &[String]| dialects.iter().any(|d| d == dialect);
+
+        match mode {
+            ConversionMode::Partial => !contains(&self.illegal_dialects),
+            ConversionMode::Full => {
+                !contains(&self.illegal_dialects) && contains(&self.legal_dialects)
+            }
+        }
+    }
+}
+
+/// Checks that no operation from the given dialect is left in the module.
+///
+/// If a pattern fails to match (e.g. due to unexpected types), the greedy
+/// driver silently leaves the operation untouched, and the error only shows up
+/// much later when converting to LLVM. This check should be run right after
+/// applying the patterns, to report every operation that wasn't rewritten.
+pub fn verify_dialect_lowered(module: &Module, dialect: &str) -> Result<(), Error> {
+    ConversionTarget::new()
+        .add_illegal_dialect(dialect)
+        .verify(module, ConversionMode::Partial)
+}
+
+#[cfg(test)]
+mod test {
+    use melior::{ir::Module, utility::load_irdl_dialects};
+
+    use crate::{
+        apply_pdl_patterns, canonicalize, convert_pdl_to_pdl_interop,
+        core::build_core_module,
+        error::Error,
+        initialize_context,
+        irdl::build_dialect_module,
+        legality::{ConversionMode, ConversionTarget, verify_dialect_lowered},
+        parse_module,
+        pdl::build_pattern_module,
+        pipeline::PipelineOptions,
+        testing::felt_context,
+    };
+
+    #[test]
+    fn lowered_core_module() {
+        let context = initialize_context();
+        let options = PipelineOptions::default();
+        load_irdl_dialects(&build_dialect_module(&context));
+
+        let core_module = build_core_module(&context);
+        assert!(verify_dialect_lowered(&core_module, "felt").is_err());
+
+        let mut pattern_module = build_pattern_module(&context);
+        canonicalize(&context, &mut pattern_module, &options);
+        convert_pdl_to_pdl_interop(&context, &mut pattern_module, &options);
+        apply_pdl_patterns(&core_module, &pattern_module);
+        verify_dialect_lowered(&core_module, "felt").unwrap();
+    }
+
+    #[test]
+    fn full_conversion_target() {
+        let context = initialize_context();
+        let module = Module::parse(
+            &context,
+            r#"
+            module {
+              func.func @entrypoint(%arg0: i32) -> i32 {
+                %0 = arith.addi %arg0, %arg0 : i32
+                %1 = math.absi %0 : i32
+                return %1 : i32
+              }
+            }"#,
+        )
+        .unwrap();
+
+        let target = ConversionTarget::new()
+            .add_illegal_dialect("felt")
+            .add_legal_dialect("func")
+            .add_legal_dialect("arith");
+        target.verify(&module, ConversionMode::Partial).unwrap();
+
+        let Err(Error::IllegalOperations(operations)) =
+            target.verify(&module, ConversionMode::Full)
+        else {
+            panic!("expected illegal operations");
+        };
+        assert_eq!(operations.len(), 1);
+        assert_eq!(operations[0].name, "math.absi");
+    }
+
+    #[test]
+    fn report_every_operation() {
+        let context = initialize_context();
+        context.set_allow_unregistered_dialects(true);
+
+        let module = Module::parse(
+            &context,
+            r#"
+            module {
+              func.func @entrypoint(%arg0: i32) -> i32 {
+                %0 = "felt.add"(%arg0, %arg0) : (i32, i32) -> i32 loc("felt.mlir":3:10)
+                %1 = "felt.mul"(%0, %arg0) : (i32, i32) -> i32 loc("felt.mlir":4:10)
+                %2 = "feltx.add"(%1, %arg0) : (i32, i32) -> i32
+                return %2 : i32
+              }
+            }"#,
+        )
+        .unwrap();
+
+        let Err(Error::IllegalOperations(operations)) = verify_dialect_lowered(&module, "felt")
+        else {
+            panic!("expected illegal operations");
+        };
+        let operations = operations
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            operations,
+            [
+                r#"felt.add at loc("felt.mlir":3:10)"#,
+                r#"felt.mul at loc("felt.mlir":4:10)"#,
+            ]
+        );
+    }
+    #[test]
+    fn report_reductions() {
+        let context = felt_context();
+
+        let module = parse_module(
+            &context,
+            r#"
+            module {
+              func.func @entrypoint(%a: i32) -> i32 {
+                %0 = "felt.reduce"(%a, %a) ({
+                ^bb0(%acc: i32, %x: i32):
+                  gpu.yield %acc : i32
+                }) : (i32, i32) -> i32
+                return %0 : i32
+              }
+            }"#,
+            "report_reductions",
+        )
+        .unwrap();
+
+        let error = verify_dialect_lowered(&module, "felt").unwrap_err();
+        assert!(
+            error.to_string().contains(
+                "felt.reduce at loc(\"report_reductions\":4:17) (PDL patterns can't clone its \
+                 body, unroll it with `core::lower_reductions` first)"
+            ),
+            "{error}"
+        );
+    }
+}
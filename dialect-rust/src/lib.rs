@@ -1,10 +1,14 @@
-use std::ptr;
+use std::{ffi::c_void, fs, path::Path, ptr, slice};
 
 use melior::{
-    Context, ExecutionEngine,
+    Context, ExecutionEngine, LogicalResult, StringRef,
     dialect::DialectRegistry,
-    ir::Module,
-    pass::{self, PassManager},
+    ir::{
+        BlockLike, Location, Module, RegionLike, Type, ValueLike,
+        attribute::StringAttribute,
+        operation::{Operation, OperationLike, OperationRef},
+        r#type::IntegerType,
+    },
     utility::{register_all_dialects, register_all_llvm_translations, register_all_passes},
 };
 
@@ -13,9 +17,37 @@ use mlir_sys::{
     mlirPDLPatternModuleFromModule, mlirRewritePatternSetFromPDLPatternModule,
 };
 
+use crate::{
+    error::Error,
+    legality::{ConversionMode, ConversionTarget},
+    memref::MemRefDescriptor,
+    native::NativeFunctions,
+    pipeline::PipelineOptions,
+};
+
+pub mod cache;
 pub mod core;
+pub mod dsl;
+pub mod emit;
+pub mod error;
+mod ffi;
+pub mod fold;
+pub mod header;
 pub mod irdl;
+pub mod lang;
+pub mod legality;
+pub mod library;
+pub mod memref;
+pub mod montgomery;
+pub mod native;
 pub mod pdl;
+pub mod pdll;
+pub mod pipeline;
+pub mod reproducer;
+#[cfg(test)]
+mod testing;
+pub mod transform;
+mod walk;
 
 pub fn initialize_context() -> Context {
     let context = Context::new();
@@ -30,31 +62,116 @@ pub fn initialize_context() -> Context {
     context
 }
 
+/// Returns a location pointing at the caller's source code.
+///
+/// Functions that build IR on behalf of their callers can be marked with
+/// `#[track_caller]`, so that the location points at the user's code instead.
+#[track_caller]
+pub fn caller_location(ctx: &Context) -> Location<'_> {
+    let caller = std::panic::Location::caller();
+    Location::new(
+        ctx,
+        caller.file(),
+        caller.line() as usize,
+        caller.column() as usize,
+    )
+}
+
+/// Parses a module, using `source_name` as the file name of its locations.
+///
+/// Unlike `Module::parse`, diagnostics and debug info point back to the given
+/// source.
+pub fn parse_module<'c>(ctx: &'c Context, source: &str, source_name: &str) -> Option<Module<'c>> {
+    Module::from_operation(Operation::parse(ctx, source, source_name)?)
+}
+
+/// Loads a module from a file in the textual MLIR format.
+pub fn load_module<'c>(ctx: &'c Context, path: impl AsRef<Path>) -> Result<Module<'c>, Error> {
+    let path = path.as_ref();
+    let source = fs::read_to_string(path)?;
+    parse_module(ctx, &source, &path.display().to_string())
+        .ok_or_else(|| Error::Parse(path.display().to_string()))
+}
+
 // The canonicalization pass can be used to check if the modules are valid.
-pub fn canonicalize(context: &Context, module: &mut Module<'_>) {
-    let pass_manager = PassManager::new(context);
-    pass_manager.enable_verifier(true);
-    pass_manager.add_pass(pass::transform::create_canonicalizer());
-    pass_manager.run(module).unwrap();
+pub fn canonicalize(context: &Context, module: &mut Module<'_>, options: &PipelineOptions) {
+    run_pipeline(context, module, "builtin.module(canonicalize)", options).unwrap();
+}
+
+pub fn convert_pdl_to_pdl_interop(ctx: &Context, module: &mut Module, options: &PipelineOptions) {
+    run_pipeline(
+        ctx,
+        module,
+        "builtin.module(convert-pdl-to-pdl-interp)",
+        options,
+    )
+    .unwrap();
 }
 
-pub fn convert_pdl_to_pdl_interop(ctx: &Context, module: &mut Module) {
-    let pass_manager = PassManager::new(ctx);
-    pass_manager.enable_verifier(true);
-    pass_manager.add_pass(pass::conversion::create_pdl_to_pdl_interp());
-    pass_manager.run(module).unwrap();
+/// The passes run by `convert_to_llvm`, in textual pipeline form.
+///
+/// Linalg operations are first lowered to loops, and structured control flow
+/// (`scf.for`, `scf.while` and `scf.if`) to branches, as `convert-to-llvm`
+/// doesn't handle them. The memref arguments leave casts between descriptor
+/// structs and memrefs, which are removed at the end.
+pub const LLVM_PIPELINE: &str = "builtin.module(canonicalize,convert-linalg-to-loops,\
+    convert-scf-to-cf,convert-to-llvm,reconcile-unrealized-casts)";
+
+/// The passes run by `convert_to_llvm` when debug info is enabled.
+///
+/// Every `llvm.func` gets a `llvm.di_subprogram` derived from its location, and
+/// the locations of the nested operations are translated to `!DILocation`s
+/// within it. Only file/line/column locations produce useful line tables.
+pub const LLVM_DEBUG_INFO_PIPELINE: &str = "builtin.module(canonicalize,convert-linalg-to-loops,\
+    convert-scf-to-cf,convert-to-llvm,reconcile-unrealized-casts,\
+    ensure-debug-info-scope-on-llvm-func)";
+
+/// Returns the pipeline run by `convert_to_llvm` for the given options.
+pub fn llvm_pipeline(options: &PipelineOptions) -> &'static str {
+    if options.debug_info {
+        LLVM_DEBUG_INFO_PIPELINE
+    } else {
+        LLVM_PIPELINE
+    }
+}
+
+pub fn convert_to_llvm(context: &Context, module: &mut Module<'_>, options: &PipelineOptions) {
+    run_pipeline(context, module, llvm_pipeline(options), options).unwrap();
 }
 
-pub fn convert_to_llvm(context: &Context, module: &mut Module<'_>) {
-    let pass_manager = PassManager::new(context);
-    pass_manager.enable_verifier(true);
-    pass_manager.add_pass(pass::transform::create_canonicalizer());
-    pass_manager.add_pass(pass::conversion::create_to_llvm());
-    pass_manager.run(module).unwrap();
+/// Runs a textual pass pipeline on the module. This is equivalent to the
+/// `--pass-pipeline` flag of `mlir-opt`, for example:
+///
+/// ```text
+/// builtin.module(canonicalize,convert-scf-to-cf,convert-to-llvm)
+/// ```
+///
+/// Fails if the pipeline cannot be parsed (e.g. a pass name is unknown), or if
+/// any of the passes fails.
+pub fn run_pipeline(
+    context: &Context,
+    module: &mut Module<'_>,
+    pipeline: &str,
+    options: &PipelineOptions,
+) -> Result<(), Error> {
+    pipeline::run_passes(context, module, pipeline, options)
 }
 
 pub fn apply_pdl_patterns(target_module: &Module, pattern_module: &Module) {
+    apply_pdl_patterns_with(target_module, pattern_module, &NativeFunctions::new());
+}
+
+/// Like `apply_pdl_patterns`, with native functions that the patterns can
+/// call. See the `native` module.
+pub fn apply_pdl_patterns_with(
+    target_module: &Module,
+    pattern_module: &Module,
+    native_functions: &NativeFunctions,
+) {
     let pdl_module = unsafe { mlirPDLPatternModuleFromModule(pattern_module.to_raw()) };
+    // The patterns are only used until the end of this function, so the
+    // functions outlive them.
+    unsafe { native_functions.register(pdl_module) };
     let rewrite_patterns = unsafe { mlirRewritePatternSetFromPDLPatternModule(pdl_module) };
     let frozen_patterns = unsafe { mlirFreezeRewritePattern(rewrite_patterns) };
 
@@ -69,7 +186,91 @@ pub fn apply_pdl_patterns(target_module: &Module, pattern_module: &Module) {
     };
 }
 
+/// Applies the patterns with MLIR's dialect conversion driver: either the
+/// result is legal for the given target, or the conversion is rolled back,
+/// and the module is left untouched. The module operation itself is always
+/// legal.
+///
+/// On failure, the error holds the diagnostics of the conversion, such as the
+/// operations that it failed to legalize.
+pub fn apply_pdl_conversion(
+    target_module: &mut Module,
+    pattern_module: &Module,
+    target: &ConversionTarget,
+    mode: ConversionMode,
+) -> Result<(), Error> {
+    let pdl_module = unsafe { mlirPDLPatternModuleFromModule(pattern_module.to_raw()) };
+    let rewrite_patterns = unsafe { mlirRewritePatternSetFromPDLPatternModule(pdl_module) };
+    let frozen_patterns = unsafe { mlirFreezeRewritePattern(rewrite_patterns) };
+
+    let dialects = |dialects: &[String]| {
+        dialects
+            .iter()
+            .map(|dialect| StringRef::new(dialect).to_raw())
+            .collect::<Vec<_>>()
+    };
+    let legal_dialects = dialects(target.legal_dialects());
+    let illegal_dialects = dialects(target.illegal_dialects());
+    let mut diagnostics = Vec::<String>::new();
+    let data = &mut diagnostics as *mut Vec<String> as *mut c_void;
+
+    let operation = target_module.as_operation().to_raw();
+    let result = LogicalResult::from_raw(unsafe {
+        match mode {
+            ConversionMode::Partial => ffi::dialectRustApplyPartialConversion(
+                operation,
+                frozen_patterns,
+                legal_dialects.as_ptr(),
+                legal_dialects.len(),
+                illegal_dialects.as_ptr(),
+                illegal_dialects.len(),
+                push_diagnostic,
+                data,
+            ),
+            ConversionMode::Full => ffi::dialectRustApplyFullConversion(
+                operation,
+                frozen_patterns,
+                legal_dialects.as_ptr(),
+                legal_dialects.len(),
+                illegal_dialects.as_ptr(),
+                illegal_dialects.len(),
+                push_diagnostic,
+                data,
+            ),
+        }
+    });
+
+    if result.is_failure() {
+        return Err(Error::Conversion(diagnostics));
+    }
+    Ok(())
+}
+
+/// Collects the diagnostics of `apply_pdl_conversion`. It is called from C++,
+/// so it must not panic.
+unsafe extern "C" fn push_diagnostic(data: *mut c_void, ptr: *const u8, size: usize) {
+    let diagnostics = unsafe { &mut *(data as *mut Vec<String>) };
+    let message = unsafe { slice::from_raw_parts(ptr, size) };
+    diagnostics.push(String::from_utf8_lossy(message).into_owned());
+}
+
+/// The type of the lowered `entrypoint` function called by
+/// `execute_entrypoint`.
+pub const ENTRYPOINT_TYPE: &str = "!llvm.func<i32 (i32, i32)>";
+
+/// JIT compiles the module, and calls its `entrypoint` function.
+///
+/// The execution engine registers the compiled code with the GDB JIT
+/// interface, so if the module was lowered with debug info, the JIT code can be
+/// stepped through in gdb (or lldb). The code is not optimized, so that the
+/// line tables stay accurate.
+///
+/// # Panics
+///
+/// If the module has no lowered `entrypoint` taking two `i32`s and returning
+/// an `i32`. See `check_entrypoint_signature`.
 pub fn execute_entrypoint(module: &Module, mut a: u32, mut b: u32) -> u32 {
+    check_entrypoint_signature(module).unwrap_or_else(|error| panic!("{error}"));
     let execution_engine = ExecutionEngine::new(module, 0, &[], false);
 
     let mut result: u32 = 0;
@@ -86,3 +287,266 @@ pub fn execute_entrypoint(module: &Module, mut a: u32, mut b: u32) -> u32 {
 
     result
 }
+
+/// JIT compiles the module, and calls a function built with `FeltFn::map`,
+/// passing each slice as a `memref<?xi32>`. The function writes its results
+/// to `output`.
+///
+/// # Panics
+///
+/// If the slices don't all have the same length, or if the module has no
+/// lowered function `name` taking `inputs.len() + 1` one-dimensional memrefs,
+/// and returning nothing. Pointers are opaque after the lowering, so the
+/// element type of the memrefs can't be checked.
+pub fn execute_map(module: &Module, name: &str, inputs: &[&[u32]], output: &mut [u32]) {
+    assert!(
+        inputs.iter().all(|input| input.len() == output.len()),
+        "the inputs and the output of {name} must have the same length"
+    );
+    check_map_signature(module, name, inputs.len() + 1);
+    let execution_engine = ExecutionEngine::new(module, 0, &[], false);
+
+    let mut descriptors = inputs
+        .iter()
+        .map(|input| MemRefDescriptor::from_slice(input))
+        .chain([MemRefDescriptor::from_mut_slice(output)])
+        .collect::<Vec<_>>();
+    let mut arguments = descriptors
+        .iter_mut()
+        .flat_map(MemRefDescriptor::packed_arguments)
+        .collect::<Vec<_>>();
+    unsafe {
+        execution_engine
+            .invoke_packed(name, &mut arguments)
+            .unwrap()
+    }
+}
+
+/// Checks that the module defines a lowered `entrypoint` function, with the
+/// type that `execute_entrypoint` calls it with.
+pub fn check_entrypoint_signature(module: &Module) -> Result<(), Error> {
+    match entrypoint_type(module) {
+        Some(function_type) if function_type == ENTRYPOINT_TYPE => Ok(()),
+        Some(function_type) => Err(Error::Entrypoint(format!(
+            "@entrypoint has type {function_type}, instead of {ENTRYPOINT_TYPE}"
+        ))),
+        None => Err(Error::Entrypoint(
+            "the module doesn't define a lowered function @entrypoint".to_string(),
+        )),
+    }
+}
+
+/// Returns the type of the lowered `entrypoint` function, if the module
+/// defines one.
+pub(crate) fn entrypoint_type(module: &Module) -> Option<String> {
+    let function = find_lowered_function(module, "entrypoint")?;
+    function.region(0).ok()?.first_block()?;
+    Some(function.attribute("function_type").ok()?.to_string())
+}
+
+fn find_lowered_function<'c, 'a>(
+    module: &'a Module<'c>,
+    name: &str,
+) -> Option<OperationRef<'c, 'a>> {
+    let mut operation = module.body().first_operation();
+    while let Some(current) = operation {
+        let is_function = current.name().as_string_ref().as_str() == Ok("llvm.func")
+            && current
+                .attribute("sym_name")
+                .ok()
+                .and_then(|symbol| StringAttribute::try_from(symbol).ok())
+                .is_some_and(|symbol| symbol.value() == name);
+        if is_function {
+            return Some(current);
+        }
+        operation = current.next_in_block();
+    }
+    None
+}
+
+/// Checks that the arguments packed by `execute_map` match the parameters of
+/// the lowered function: the allocated and aligned pointers, the offset, the
+/// size and the stride of every memref.
+fn check_map_signature(module: &Module, name: &str, memref_count: usize) {
+    let context = module.context();
+    let pointer_type = Type::parse(&context, "!llvm.ptr").unwrap();
+    let index_type: Type = IntegerType::new(&context, 64).into();
+    let descriptor_types = [
+        pointer_type,
+        pointer_type,
+        index_type,
+        index_type,
+        index_type,
+    ];
+
+    let function = find_lowered_function(module, name)
+        .unwrap_or_else(|| panic!("the module has no lowered function @{name}"));
+
+    let function_type = function.attribute("function_type").unwrap().to_string();
+    assert!(
+        function_type.starts_with("!llvm.func<void ("),
+        "@{name} must not return a value, but has type {function_type}"
+    );
+
+    let block = function
+        .region(0)
+        .unwrap()
+        .first_block()
+        .unwrap_or_else(|| panic!("@{name} is only declared"));
+    let argument_types = (0..block.argument_count())
+        .map(|index| block.argument(index).unwrap().r#type())
+        .collect::<Vec<_>>();
+    assert!(
+        argument_types.len() == memref_count * descriptor_types.len()
+            && argument_types
+                .chunks(descriptor_types.len())
+                .all(|types| types == descriptor_types),
+        "@{name} doesn't take {memref_count} one-dimensional memrefs"
+    );
+}
+
+#[cfg(test)]
+mod test {
+    use melior::ir::Module;
+
+    use melior::utility::load_irdl_dialects;
+
+    use crate::{
+        apply_pdl_conversion, canonicalize, check_entrypoint_signature, convert_pdl_to_pdl_interop,
+        convert_to_llvm,
+        core::build_core_module,
+        error::Error,
+        initialize_context,
+        irdl::build_dialect_module,
+        legality::{ConversionMode, ConversionTarget},
+        pdl::build_pattern_module,
+        pipeline::PipelineOptions,
+        run_pipeline,
+    };
+
+    #[test]
+    fn run_makefile_pipeline() {
+        let context = initialize_context();
+        let mut module = Module::parse(
+            &context,
+            r#"
+            module {
+              func.func @sum(%n: index) -> index {
+                %c0 = arith.constant 0 : index
+                %c1 = arith.constant 1 : index
+                %0 = scf.for %i = %c0 to %n step %c1 iter_args(%acc = %c0) -> (index) {
+                  %1 = arith.addi %acc, %i : index
+                  scf.yield %1 : index
+                }
+                return %0 : index
+              }
+            }"#,
+        )
+        .unwrap();
+
+        // Equivalent to `mlir-opt --convert-scf-to-cf --convert-to-llvm`.
+        run_pipeline(
+            &context,
+            &mut module,
+            "builtin.module(convert-scf-to-cf,convert-to-llvm)",
+            &PipelineOptions::default(),
+        )
+        .unwrap();
+
+        let module = module.as_operation().to_string();
+        assert!(module.contains("llvm.func @sum"));
+        assert!(!module.contains("scf.for"));
+    }
+
+    #[test]
+    fn unknown_pass() {
+        let context = initialize_context();
+        let mut module = Module::parse(&context, "module {}").unwrap();
+
+        let error = run_pipeline(
+            &context,
+            &mut module,
+            "builtin.module(felt-to-llvm)",
+            &PipelineOptions::default(),
+        );
+        assert!(matches!(error, Err(Error::Melior(_))));
+        assert!(error.unwrap_err().to_string().contains("felt-to-llvm"));
+    }
+
+    #[test]
+    fn entrypoint_signature() {
+        let context = initialize_context();
+        let options = PipelineOptions::default();
+
+        let mut module = Module::parse(
+            &context,
+            r#"
+            module {
+              func.func @entrypoint(%a: i64, %b: i64) -> i64 {
+                %0 = arith.addi %a, %b : i64
+                return %0 : i64
+              }
+            }"#,
+        )
+        .unwrap();
+        let error = check_entrypoint_signature(&module);
+        assert!(matches!(error, Err(Error::Entrypoint(_))));
+
+        convert_to_llvm(&context, &mut module, &options);
+        let error = check_entrypoint_signature(&module).unwrap_err();
+        assert!(
+            error.to_string().contains("!llvm.func<i64 (i64, i64)>"),
+            "{error}"
+        );
+    }
+
+    #[test]
+    fn all_or_nothing_conversion() {
+        let context = initialize_context();
+        let options = PipelineOptions::default();
+        load_irdl_dialects(&build_dialect_module(&context));
+
+        let mut pattern_module = build_pattern_module(&context);
+        canonicalize(&context, &mut pattern_module, &options);
+        convert_pdl_to_pdl_interop(&context, &mut pattern_module, &options);
+
+        let target = ConversionTarget::new()
+            .add_illegal_dialect("felt")
+            .add_legal_dialect("func")
+            .add_legal_dialect("arith");
+
+        // The patterns lower felt.add to arith, so the conversion succeeds.
+        let mut core_module = build_core_module(&context);
+        apply_pdl_conversion(
+            &mut core_module,
+            &pattern_module,
+            &target,
+            ConversionMode::Full,
+        )
+        .unwrap();
+        assert!(!core_module.as_operation().to_string().contains("felt.add"));
+
+        // If arith is not legal, the conversion fails and is rolled back, and
+        // MLIR reports the operation that it failed to legalize.
+        let target = ConversionTarget::new()
+            .add_illegal_dialect("felt")
+            .add_legal_dialect("func");
+        let mut core_module = build_core_module(&context);
+        let error = apply_pdl_conversion(
+            &mut core_module,
+            &pattern_module,
+            &target,
+            ConversionMode::Full,
+        );
+        let Err(Error::Conversion(diagnostics)) = error else {
+            panic!("expected a failed conversion, got {error:?}");
+        };
+        assert!(
+            diagnostics
+                .iter()
+                .any(|diagnostic| diagnostic.contains("failed to legalize")),
+            "{diagnostics:?}"
+        );
+        assert!(core_module.as_operation().to_string().contains("felt.add"));
+    }
+}
@@ -0,0 +1,363 @@
+//! A library of named lowering patterns, with benefits.
+//!
+//! When several patterns match the same operation, the rewriter applies the
+//! one with the highest benefit. The library names every pattern, so that the
+//! rewrites can be traced: `PatternLibrary::apply_traced` records which
+//! pattern rewrote each operation, which is what we need to tune the
+//! benefits.
+
+use std::cell::RefCell;
+
+use melior::{
+    Context, StringRef,
+    ir::{
+        BlockLike, Identifier, Module,
+        attribute::{IntegerAttribute, StringAttribute},
+        operation::{Operation, OperationBuilder, OperationLike},
+        r#type::IntegerType,
+    },
+};
+use mlir_sys::mlirOperationSetAttributeByName;
+
+use crate::{
+    apply_pdl_patterns_with, caller_location, convert_pdl_to_pdl_interop,
+    irdl::OPERATIONS,
+    native::{NativeFunctions, PdlValue},
+    pdl::{
+        Reduction, build_constant_addition_pattern, build_lowering_pattern, build_sum_patterns,
+        pdl_attribute_type,
+    },
+    pipeline::PipelineOptions,
+};
+
+/// The native rewrite called by traced patterns, with the name of the pattern
+/// and its root operation.
+const RECORD_PATTERN: &str = "record_pattern";
+
+/// A set of `pdl.pattern`s, by name.
+pub struct PatternLibrary<'c> {
+    ctx: &'c Context,
+    patterns: Vec<(String, u16, Operation<'c>)>,
+}
+
+/// A rewrite applied by a pattern of the library.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Firing {
+    /// The name of the pattern.
+    pub pattern: String,
+    /// The name of the rewritten operation.
+    pub operation: String,
+    /// The location of the rewritten operation.
+    pub location: String,
+}
+
+impl<'c> PatternLibrary<'c> {
+    pub fn new(ctx: &'c Context) -> Self {
+        Self {
+            ctx,
+            patterns: Vec::new(),
+        }
+    }
+
+    /// The lowering patterns of the felt dialect, with the remainder
+    /// reduction:
+    ///
+    /// - A pattern for each operation, named after it, with benefit 1.
+    /// - `add_constant`, with benefit 2, which reduces sums with a constant
+    ///   with a conditional subtraction. The constant itself is reduced when
+    ///   the module is canonicalized.
+    /// - `sum`, `sum_single` and `sum_empty`, with benefit 1, which rewrite
+    ///   `felt.sum` with two operands or more, one, or none.
+    pub fn felt(ctx: &'c Context) -> Self {
+        let library = OPERATIONS.into_iter().fold(
+            Self::new(ctx).add("add_constant", 2, build_constant_addition_pattern(ctx)),
+            |library, (name, operand_count)| {
+                library.add(
+                    name,
+                    1,
+                    build_lowering_pattern(ctx, name, operand_count, Reduction::Remainder),
+                )
+            },
+        );
+        ["sum", "sum_single", "sum_empty"]
+            .into_iter()
+            .zip(build_sum_patterns(ctx))
+            .fold(library, |library, (name, pattern)| {
+                library.add(name, 1, pattern)
+            })
+    }
+
+    /// Adds a `pdl.pattern`. The name and the benefit replace its own.
+    pub fn add(mut self, name: &str, benefit: u16, pattern: Operation<'c>) -> Self {
+        assert!(
+            self.patterns.iter().all(|(other, _, _)| other != name),
+            "pattern {name} is already in the library"
+        );
+        self.patterns.push((name.to_string(), benefit, pattern));
+        self
+    }
+
+    /// Returns the names and benefits of the patterns, in the order of the
+    /// pattern module.
+    pub fn patterns(&self) -> Vec<(&str, u16)> {
+        let mut patterns = self
+            .patterns
+            .iter()
+            .map(|(name, benefit, _)| (name.as_str(), *benefit))
+            .collect::<Vec<_>>();
+        patterns.sort_by_key(|(name, benefit)| (u16::MAX - benefit, *name));
+        patterns
+    }
+
+    /// Builds the pattern module.
+    ///
+    /// The patterns are sorted by decreasing benefit, and then by name, so the
+    /// module doesn't depend on the order in which they were added. The
+    /// rewriter breaks ties between matches of equal benefit by their order
+    /// in the module.
+    pub fn build_module(self) -> Module<'c> {
+        self.build(false)
+    }
+
+    /// Applies the patterns, converted to PDL interp, like
+    /// `apply_pdl_patterns`, and returns the rewrites in the order they were
+    /// applied.
+    pub fn apply_traced(self, target_module: &Module, options: &PipelineOptions) -> Vec<Firing> {
+        let ctx = self.ctx;
+        let mut pattern_module = self.build(true);
+        convert_pdl_to_pdl_interop(ctx, &mut pattern_module, options);
+
+        let firings = RefCell::new(Vec::new());
+        let functions = NativeFunctions::new().rewrite(RECORD_PATTERN, |arguments| {
+            // The rewrite is called from C++, so it fails instead of
+            // panicking on unexpected arguments.
+            let [PdlValue::Attribute(pattern), PdlValue::Operation(operation)] = arguments else {
+                return None;
+            };
+            firings.borrow_mut().push(Firing {
+                pattern: StringAttribute::try_from(*pattern)
+                    .ok()?
+                    .value()
+                    .to_string(),
+                operation: operation.name().as_string_ref().as_str().ok()?.to_string(),
+                location: operation.location().to_string(),
+            });
+            Some(Vec::new())
+        });
+        apply_pdl_patterns_with(target_module, &pattern_module, &functions);
+        drop(functions);
+
+        firings.into_inner()
+    }
+
+    fn build(mut self, traced: bool) -> Module<'c> {
+        let ctx = self.ctx;
+        self.patterns
+            .sort_by(|(a, a_benefit, _), (b, b_benefit, _)| {
+                b_benefit.cmp(a_benefit).then_with(|| a.cmp(b))
+            });
+
+        let module = Module::new(caller_location(ctx));
+        for (name, benefit, pattern) in self.patterns {
+            unsafe {
+                mlirOperationSetAttributeByName(
+                    pattern.to_raw(),
+                    StringRef::new("sym_name").to_raw(),
+                    StringAttribute::new(ctx, &name).to_raw(),
+                );
+                mlirOperationSetAttributeByName(
+                    pattern.to_raw(),
+                    StringRef::new("benefit").to_raw(),
+                    IntegerAttribute::new(IntegerType::new(ctx, 16).into(), benefit.into())
+                        .to_raw(),
+                );
+            }
+            if traced {
+                insert_trace(ctx, &pattern, &name);
+            }
+            module.body().append_operation(pattern);
+        }
+        module
+    }
+}
+
+/// Inserts a call to `RECORD_PATTERN` at the start of the rewrite of the
+/// pattern, while its root is still in place.
+fn insert_trace<'c>(ctx: &'c Context, pattern: &Operation<'c>, name: &str) {
+    let rewrite = pattern
+        .region(0)
+        .unwrap()
+        .first_block()
+        .unwrap()
+        .terminator()
+        .unwrap();
+    let root = rewrite.operand(0).unwrap();
+    let block = rewrite.region(0).unwrap().first_block().unwrap();
+
+    let name = block.insert_operation(
+        0,
+        OperationBuilder::new("pdl.attribute", caller_location(ctx))
+            .add_attributes(&[(
+                Identifier::new(ctx, "value"),
+                StringAttribute::new(ctx, name).into(),
+            )])
+            .add_results(&[pdl_attribute_type(ctx)])
+            .build()
+            .unwrap(),
+    );
+    block.insert_operation_after(
+        name,
+        OperationBuilder::new("pdl.apply_native_rewrite", caller_location(ctx))
+            .add_attributes(&[(
+                Identifier::new(ctx, "name"),
+                StringAttribute::new(ctx, RECORD_PATTERN).into(),
+            )])
+            .add_operands(&[name.result(0).unwrap().into(), root])
+            .build()
+            .unwrap(),
+    );
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        convert_to_llvm, execute_entrypoint, initialize_context, legality::verify_dialect_lowered,
+        library::PatternLibrary, parse_module, pipeline::PipelineOptions, testing::felt_context,
+    };
+
+    #[test]
+    fn pattern_order() {
+        let context = initialize_context();
+
+        assert_eq!(
+            PatternLibrary::felt(&context).patterns(),
+            [
+                ("add_constant", 2),
+                ("add", 1),
+                ("div", 1),
+                ("mul", 1),
+                ("neg", 1),
+                ("sub", 1),
+                ("sum", 1),
+                ("sum_empty", 1),
+                ("sum_single", 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn trace_patterns() {
+        let context = felt_context();
+        let options = PipelineOptions::default();
+
+        let mut module = parse_module(
+            &context,
+            r#"
+            module {
+              func.func @entrypoint(%a: i32, %b: i32) -> i32 attributes { llvm.emit_c_interface } {
+                %0 = arith.constant 5 : i32
+                %1 = "felt.mul"(%a, %b) : (i32, i32) -> i32
+                %2 = "felt.add"(%1, %0) : (i32, i32) -> i32
+                %3 = "felt.add"(%2, %b) : (i32, i32) -> i32
+                return %3 : i32
+              }
+            }"#,
+            "trace_patterns",
+        )
+        .unwrap();
+
+        let firings = PatternLibrary::felt(&context).apply_traced(&module, &options);
+        verify_dialect_lowered(&module, "felt").unwrap();
+
+        // Only the sum with the constant is rewritten by `add_constant`.
+        let mut patterns = firings
+            .iter()
+            .map(|firing| (firing.pattern.as_str(), firing.operation.as_str()))
+            .collect::<Vec<_>>();
+        patterns.sort();
+        assert_eq!(
+            patterns,
+            [
+                ("add", "felt.add"),
+                ("add_constant", "felt.add"),
+                ("mul", "felt.mul")
+            ]
+        );
+        let firing = firings
+            .iter()
+            .find(|firing| firing.pattern == "add_constant")
+            .unwrap();
+        assert!(firing.location.contains(":6:"), "{}", firing.location);
+
+        convert_to_llvm(&context, &mut module, &options);
+
+        // 4 * 9 + 5 + 9 = 50 = 11.
+        assert_eq!(execute_entrypoint(&module, 4, 9), 11);
+    }
+
+    #[test]
+    fn trace_sum_patterns() {
+        let context = felt_context();
+        let options = PipelineOptions::default();
+
+        let mut module = parse_module(
+            &context,
+            r#"
+            module {
+              func.func @entrypoint(%a: i32, %b: i32) -> i32 attributes { llvm.emit_c_interface } {
+                %0 = "felt.sum"(%a, %b, %a) : (i32, i32, i32) -> i32
+                %1 = "felt.sum"() : () -> i32
+                %2 = "felt.sum"(%b) : (i32) -> i32
+                %3 = "felt.sum"(%0, %1, %2) : (i32, i32, i32) -> i32
+                return %3 : i32
+              }
+            }"#,
+            "trace_sum_patterns",
+        )
+        .unwrap();
+
+        let firings = PatternLibrary::felt(&context).apply_traced(&module, &options);
+        verify_dialect_lowered(&module, "felt").unwrap();
+
+        let mut patterns = firings
+            .iter()
+            .filter(|firing| firing.operation == "felt.sum")
+            .map(|firing| firing.pattern.as_str())
+            .collect::<Vec<_>>();
+        patterns.sort();
+        patterns.dedup();
+        assert_eq!(patterns, ["sum", "sum_empty", "sum_single"]);
+
+        convert_to_llvm(&context, &mut module, &options);
+        // (3 + 4 + 3) + 0 + 4 = 14 = 1.
+        assert_eq!(execute_entrypoint(&module, 3, 4), 1);
+    }
+
+    #[test]
+    fn add_unreduced_constant() {
+        let context = felt_context();
+        let options = PipelineOptions::default();
+
+        let mut module = parse_module(
+            &context,
+            r#"
+            module {
+              func.func @entrypoint(%a: i32, %b: i32) -> i32 attributes { llvm.emit_c_interface } {
+                %0 = arith.constant 40 : i32
+                %1 = "felt.add"(%a, %0) : (i32, i32) -> i32
+                return %1 : i32
+              }
+            }"#,
+            "add_unreduced_constant",
+        )
+        .unwrap();
+
+        let firings = PatternLibrary::felt(&context).apply_traced(&module, &options);
+        assert_eq!(firings[0].pattern, "add_constant");
+        convert_to_llvm(&context, &mut module, &options);
+
+        // 40 = 1, and 12 + 1 = 13 = 0, while a conditional subtraction from
+        // 12 + 40 = 52 would leave 39.
+        assert_eq!(execute_entrypoint(&module, 12, 0), 0);
+    }
+}
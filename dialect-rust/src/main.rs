@@ -1,53 +1,385 @@
+use std::{cell::RefCell, env, fs, path::PathBuf, process};
+
 use dialect_rust::{
-    apply_pdl_patterns, canonicalize, convert_pdl_to_pdl_interop, convert_to_llvm,
-    core::build_core_module, execute_entrypoint, initialize_context, irdl::build_dialect_module,
-    pdl::build_pattern_module,
+    apply_pdl_conversion, apply_pdl_patterns,
+    cache::{CacheKey, CompilationCache},
+    canonicalize, check_entrypoint_signature, convert_pdl_to_pdl_interop,
+    core::{build_core_module, lower_maps, lower_reductions},
+    emit::{write_bytecode, write_llvm_ir},
+    execute_entrypoint,
+    fold::fold,
+    header::{generate_c_header, generate_rust_bindings},
+    initialize_context,
+    irdl::build_dialect_module,
+    lang::load_program,
+    legality::{ConversionMode, ConversionTarget, verify_dialect_lowered},
+    llvm_pipeline, load_module,
+    montgomery::{Field, convert_boundaries, convert_multi_limb_boundaries, lower_multi_limb},
+    pdl::{Reduction, build_reduction_pattern_module},
+    pdll::load_patterns,
+    pipeline::PipelineOptions,
+    reproducer::Reproducer,
+    run_pipeline,
 };
 use melior::utility::load_irdl_dialects;
 
+const USAGE: &str = "\
+Usage: dialect-rust [OPTIONS]
+
+Options:
+  --input <PATH>          Compile the module at PATH instead of the built one. Files
+                          ending in .felt are compiled as felt programs
+  --emit-llvm <PATH>      Write the translated LLVM IR to PATH
+  --emit-bytecode <PATH>  Write the lowered module as MLIR bytecode to PATH
+  --emit-c-header <PATH>  Write a C header for the entrypoint C interface to PATH
+  --emit-rust-bindings <PATH>
+                          Write Rust bindings for the entrypoint C interface to PATH
+  --patterns <PATH>       Lower with the patterns at PATH instead of the built ones.
+                          Files ending in .pdll are compiled as PDLL
+  --conversion <MODE>     Apply the patterns as a partial or full conversion
+  --fold                  Fold the felt operations before lowering them
+  --reduction <REDUCTION> Reduce results with remainder (the default),
+                          montgomery or barrett. Montgomery can't be combined
+                          with --patterns
+  --modulus <MODULUS>     Compute 256-bit felts modulo the decimal MODULUS instead
+                          of the scalar field of BN254
+  --pass-pipeline <PIPELINE>
+                          Lower to LLVM with PIPELINE instead of the default one
+  --cache-dir <PATH>      Reuse compiled libraries stored in PATH
+  -g, --debug-info        Attach DWARF debug info when lowering to LLVM. Can't
+                          be combined with --pass-pipeline
+  --print-ir-before       Print the module before every pass
+  --print-ir-after        Print the module after every pass
+  --print-ir-after-change Print the module after every pass that changed it
+  --timing                Report the time spent on every pass
+  --pass-statistics       Report the statistics of every pass
+  --reproducer <PATH>     Write a crash reproducer to PATH if a pass fails or crashes
+  --run-reproducer <PATH> Replay the crash reproducer at PATH, and exit
+  --instrumentation-output <PATH>
+                          Write IR dumps and reports to PATH instead of stderr
+  -h, --help              Print this message";
+
+#[derive(Default)]
+struct Arguments {
+    input: Option<PathBuf>,
+    emit_llvm: Option<PathBuf>,
+    emit_bytecode: Option<PathBuf>,
+    emit_c_header: Option<PathBuf>,
+    emit_rust_bindings: Option<PathBuf>,
+    patterns: Option<PathBuf>,
+    conversion: Option<ConversionMode>,
+    fold: bool,
+    reduction: Reduction,
+    modulus: Option<Field>,
+    pass_pipeline: Option<String>,
+    cache_dir: Option<PathBuf>,
+    pipeline_options: PipelineOptions,
+    run_reproducer: Option<PathBuf>,
+}
+
+impl Arguments {
+    fn parse() -> Result<Self, String> {
+        let mut arguments = Arguments::default();
+
+        let mut args = env::args().skip(1);
+        while let Some(arg) = args.next() {
+            let mut value = || {
+                args.next()
+                    .ok_or_else(|| format!("missing value for {arg}"))
+            };
+            match arg.as_str() {
+                "--input" => arguments.input = Some(value()?.into()),
+                "--emit-llvm" => arguments.emit_llvm = Some(value()?.into()),
+                "--emit-bytecode" => arguments.emit_bytecode = Some(value()?.into()),
+                "--emit-c-header" => arguments.emit_c_header = Some(value()?.into()),
+                "--emit-rust-bindings" => arguments.emit_rust_bindings = Some(value()?.into()),
+                "--patterns" => arguments.patterns = Some(value()?.into()),
+                "--conversion" => {
+                    arguments.conversion = Some(match value()?.as_str() {
+                        "partial" => ConversionMode::Partial,
+                        "full" => ConversionMode::Full,
+                        mode => return Err(format!("unknown conversion mode {mode}")),
+                    })
+                }
+                "--fold" => arguments.fold = true,
+                "--reduction" => {
+                    arguments.reduction = match value()?.as_str() {
+                        "remainder" => Reduction::Remainder,
+                        "montgomery" => Reduction::Montgomery,
+                        "barrett" => Reduction::Barrett,
+                        reduction => return Err(format!("unknown reduction {reduction}")),
+                    }
+                }
+                "--modulus" => {
+                    arguments.modulus =
+                        Some(Field::parse(&value()?).map_err(|error| error.to_string())?)
+                }
+                "--pass-pipeline" => arguments.pass_pipeline = Some(value()?),
+                "--cache-dir" => arguments.cache_dir = Some(value()?.into()),
+                "-g" | "--debug-info" => arguments.pipeline_options.debug_info = true,
+                "--print-ir-before" => arguments.pipeline_options.print_ir_before = true,
+                "--print-ir-after" => arguments.pipeline_options.print_ir_after = true,
+                "--print-ir-after-change" => {
+                    arguments.pipeline_options.print_ir_after_change = true
+                }
+                "--timing" => arguments.pipeline_options.timing = true,
+                "--pass-statistics" => arguments.pipeline_options.statistics = true,
+                "--reproducer" => arguments.pipeline_options.reproducer = Some(value()?.into()),
+                "--run-reproducer" => arguments.run_reproducer = Some(value()?.into()),
+                "--instrumentation-output" => {
+                    let file = fs::File::create(value()?).map_err(|error| error.to_string())?;
+                    arguments.pipeline_options.sink = RefCell::new(Box::new(file));
+                }
+                "-h" | "--help" => {
+                    println!("{USAGE}");
+                    process::exit(0);
+                }
+                _ => return Err(format!("unexpected argument {arg}")),
+            }
+        }
+
+        // The module is converted into Montgomery form for the patterns built
+        // with `Reduction::Montgomery`, which patterns from a file don't
+        // expect.
+        if arguments.patterns.is_some() && arguments.reduction == Reduction::Montgomery {
+            return Err("--patterns can't be combined with --reduction montgomery".to_string());
+        }
+        // Debug info is attached by the default pipeline, so it would be
+        // silently dropped with a custom one.
+        if arguments.pass_pipeline.is_some() && arguments.pipeline_options.debug_info {
+            return Err("--debug-info can't be combined with --pass-pipeline".to_string());
+        }
+
+        Ok(arguments)
+    }
+}
+
 fn main() {
+    let arguments = Arguments::parse().unwrap_or_else(|error| {
+        eprintln!("error: {error}\n\n{USAGE}");
+        process::exit(2);
+    });
+
     let context = initialize_context();
+    let options = &arguments.pipeline_options;
 
     // We build the dialect module. This will contain only the dialect
     // definition, and not any conversion logic.
     let mut dialect_module = build_dialect_module(&context);
-    canonicalize(&context, &mut dialect_module);
+    canonicalize(&context, &mut dialect_module, options);
     println!("{}", dialect_module.as_operation());
 
     // We load the dialect into the associated context.
     // This allows use to use the dialect in other modules.
     load_irdl_dialects(&dialect_module);
 
+    // A crash reproducer contains both the module and the pipeline that failed
+    // on it, so we can run it directly.
+    if let Some(path) = &arguments.run_reproducer {
+        let mut reproducer = Reproducer::load(&context, path).unwrap();
+        match reproducer.run(&context, options) {
+            Ok(()) => println!("{}", reproducer.module.as_operation()),
+            Err(error) => {
+                eprintln!("error: {error}");
+                process::exit(1);
+            }
+        }
+        return;
+    }
+
     // We build the core module, using our custom dialect. Note that without
     // loading the IRDL dialects first, this step will fail.
-    let mut core_module = build_core_module(&context);
-    canonicalize(&context, &mut core_module);
+    //
+    // The core module can also be loaded from a file, in which case the
+    // locations of its operations point to that file. The file can either
+    // contain MLIR, or a program in the felt language (see the `lang` module).
+    let mut core_module = match &arguments.input {
+        Some(path) => {
+            let module = if path
+                .extension()
+                .is_some_and(|extension| extension == "felt")
+            {
+                load_program(&context, path)
+            } else {
+                load_module(&context, path)
+            };
+            module.unwrap_or_else(|error| {
+                eprintln!("error: {error}");
+                process::exit(1);
+            })
+        }
+        None => build_core_module(&context),
+    };
+    canonicalize(&context, &mut core_module, options);
+    // IRDL operations have no folders, so the felt operations are folded with
+    // patterns of their own.
+    if arguments.fold {
+        fold(&context, &mut core_module, options);
+    }
     println!("{}", core_module.as_operation());
 
+    // Functions marked with `llvm.emit_c_interface` can be called from C (or
+    // Rust) once compiled. We can generate the declarations of these wrappers
+    // from the function signatures.
+    if let Some(path) = &arguments.emit_c_header {
+        fs::write(path, generate_c_header(&core_module).unwrap()).unwrap();
+    }
+    if let Some(path) = &arguments.emit_rust_bindings {
+        fs::write(path, generate_rust_bindings(&core_module).unwrap()).unwrap();
+    }
+
+    // PDL patterns can't clone regions, so `felt.reduce` is unrolled into the
+    // operations of its body before they are applied, and `felt.map` becomes
+    // a `linalg.generic` with the operations of its body.
+    lower_reductions(&core_module);
+    lower_maps(&core_module);
+
+    // 256-bit felts are always kept in Montgomery form, and their operations
+    // are lowered directly, as the patterns only handle 32-bit felts.
+    let field = arguments.modulus.unwrap_or_else(Field::bn254);
+    convert_multi_limb_boundaries(&context, &core_module, &field).unwrap_or_else(|error| {
+        eprintln!("error: {error}");
+        process::exit(1);
+    });
+    lower_multi_limb(&context, &core_module, &field);
+
+    // The products can also be reduced in Montgomery form, in which case the
+    // values are converted into it when they enter a function, and back when
+    // they leave it.
+    if arguments.reduction == Reduction::Montgomery {
+        convert_boundaries(&context, &core_module).unwrap_or_else(|error| {
+            eprintln!("error: {error}");
+            process::exit(1);
+        });
+    }
+
     // If we try to compile our core module, it will fail because our custom
     // dialect is not convertible into the llvm dialect (or any other dialect,
     // for that matter). To fix it, we need to build a pattern module that
     // declares how our custom dialect is transformed.
-    let mut pattern_module = build_pattern_module(&context);
-    canonicalize(&context, &mut pattern_module);
+    //
+    // The patterns can also be loaded from a file, either in PDL, or in PDLL
+    // (see the `pdll` module).
+    let mut pattern_module = match &arguments.patterns {
+        Some(path) => {
+            let module = if path
+                .extension()
+                .is_some_and(|extension| extension == "pdll")
+            {
+                load_patterns(&context, path)
+            } else {
+                load_module(&context, path)
+            };
+            module.unwrap_or_else(|error| {
+                eprintln!("error: {error}");
+                process::exit(1);
+            })
+        }
+        None => build_reduction_pattern_module(&context, arguments.reduction),
+    };
+    canonicalize(&context, &mut pattern_module, options);
     println!("{}", pattern_module.as_operation());
 
-    // The PDL dialect by itself cannot be applied, and needs to be converted to
-    // the lower-level pdl-interop dialect.
-    convert_pdl_to_pdl_interop(&context, &mut pattern_module);
+    // Compiling the same modules always produces the same code, so we can
+    // cache the compiled library on disk, and skip the compilation if it was
+    // already compiled. The key is computed before the modules are lowered.
+    let cache = arguments
+        .cache_dir
+        .map(|directory| CompilationCache::new(directory).unwrap());
+    let pipeline = arguments
+        .pass_pipeline
+        .as_deref()
+        .unwrap_or(llvm_pipeline(options));
+    let cache_key = CacheKey::new(&[&dialect_module, &core_module, &pattern_module], pipeline);
 
-    // We apply our rewrite patterns to the core module. This will rewrite our
-    // custom operations with operations from known dialects.
-    apply_pdl_patterns(&core_module, &pattern_module);
-    println!("{}", core_module.as_operation());
+    // On a hit, the library is loaded instead of lowering and compiling the
+    // module again, unless the lowered module is emitted. A library that fails
+    // to load, for example because it is corrupt, is compiled again.
+    let cached = cache.as_ref().and_then(|cache| {
+        cache.load(&cache_key).unwrap_or_else(|error| {
+            eprintln!("warning: recompiling cached library {cache_key}: {error}");
+            None
+        })
+    });
+    let emits_lowered_module = arguments.emit_llvm.is_some() || arguments.emit_bytecode.is_some();
+    if cached.is_none() || emits_lowered_module {
+        // The PDL dialect by itself cannot be applied, and needs to be converted to
+        // the lower-level pdl-interop dialect.
+        convert_pdl_to_pdl_interop(&context, &mut pattern_module, options);
+
+        // We apply our rewrite patterns to the core module. This will rewrite our
+        // custom operations with operations from known dialects.
+        //
+        // If a pattern didn't match, the operation is silently left in the module,
+        // and the conversion to LLVM would fail with an obscure error. Instead, we
+        // check that every felt operation was rewritten.
+        //
+        // Alternatively, the patterns can be applied as a dialect conversion,
+        // which either succeeds, or leaves the module untouched.
+        let lowered = match arguments.conversion {
+            Some(mode) => {
+                let target = ConversionTarget::new()
+                    .add_illegal_dialect("felt")
+                    .add_legal_dialect("func")
+                    .add_legal_dialect("arith");
+                apply_pdl_conversion(&mut core_module, &pattern_module, &target, mode)
+            }
+            None => {
+                apply_pdl_patterns(&core_module, &pattern_module);
+                verify_dialect_lowered(&core_module, "felt")
+            }
+        };
+        lowered.unwrap_or_else(|error| {
+            eprintln!("error: {error}");
+            process::exit(1);
+        });
+        println!("{}", core_module.as_operation());
+
+        // Now that we are using known dialects, we can convert it to the LLVM
+        // dialect without errors.
+        //
+        // The conversion pipeline is given in textual form, with the same syntax
+        // as the `--pass-pipeline` flag of `mlir-opt`. By default, this is the
+        // same as calling `convert_to_llvm`.
+        run_pipeline(&context, &mut core_module, pipeline, options).unwrap_or_else(|error| {
+            eprintln!("error: {error}");
+            process::exit(1);
+        });
 
-    // Now that we are using known dialects, we can convert it to the LLVM
-    // dialect without errors.
-    convert_to_llvm(&context, &mut core_module);
+        // The lowered module can be saved for later inspection, either as LLVM IR
+        // (like `mlir-translate --mlir-to-llvmir`), or as MLIR bytecode.
+        if let Some(path) = &arguments.emit_llvm {
+            write_llvm_ir(&core_module, path).unwrap();
+        }
+        if let Some(path) = &arguments.emit_bytecode {
+            write_bytecode(&core_module, path).unwrap();
+        }
+    }
 
     // As a test, we execute the "entrypoint" function from our core module.
+    // If the library is already in the cache, we call it instead of compiling
+    // the module again.
+    //
+    // The function is called with two `i32`s, so we skip it if it was lowered
+    // with another type (e.g. from an `--input` program), instead of calling
+    // it with the wrong arguments.
     let a = 10;
     let b = 7;
-    let result = execute_entrypoint(&core_module, a, b);
-    println!("{a} + {b} = {result} mod 13")
+    match cached {
+        Some(library) => match library.execute_entrypoint(a, b) {
+            Ok(result) => println!("{a} + {b} = {result} mod 13 (cached)"),
+            Err(error) => eprintln!("error: {error}"),
+        },
+        None => {
+            if let Err(error) = check_entrypoint_signature(&core_module) {
+                eprintln!("error: {error}");
+                return;
+            }
+            if let Some(cache) = &cache {
+                cache.store(&cache_key, &core_module).unwrap();
+            }
+            let result = execute_entrypoint(&core_module, a, b);
+            println!("{a} + {b} = {result} mod 13")
+        }
+    }
 }
@@ -0,0 +1,70 @@
+//! Marshaling of Rust slices into memref descriptors, to call lowered
+//! functions that take memref arguments.
+
+use std::marker::PhantomData;
+
+/// The descriptor of a ranked memref, with the layout used by the LLVM lowering
+/// of memrefs.
+#[repr(C)]
+pub struct MemRefDescriptor<'a, T, const N: usize> {
+    pub allocated: *mut T,
+    pub aligned: *mut T,
+    pub offset: i64,
+    pub sizes: [i64; N],
+    pub strides: [i64; N],
+    // The descriptor borrows the buffer it describes.
+    buffer: PhantomData<&'a mut [T]>,
+}
+
+impl<'a, T> MemRefDescriptor<'a, T, 1> {
+    /// Describes a contiguous slice that may be written to.
+    pub fn from_mut_slice(slice: &'a mut [T]) -> Self {
+        let pointer = slice.as_mut_ptr();
+        MemRefDescriptor {
+            allocated: pointer,
+            aligned: pointer,
+            offset: 0,
+            sizes: [slice.len() as i64],
+            strides: [1],
+            buffer: PhantomData,
+        }
+    }
+
+    /// Describes a contiguous slice. The called function must not write to
+    /// it.
+    pub fn from_slice(slice: &'a [T]) -> Self {
+        let pointer = slice.as_ptr().cast_mut();
+        MemRefDescriptor {
+            allocated: pointer,
+            aligned: pointer,
+            offset: 0,
+            sizes: [slice.len() as i64],
+            strides: [1],
+            buffer: PhantomData,
+        }
+    }
+}
+
+impl<T, const N: usize> MemRefDescriptor<'_, T, N> {
+    /// Returns pointers to every field, in the order of the arguments of the
+    /// lowered function, as expected by `ExecutionEngine::invoke_packed`.
+    ///
+    /// Unlike the `_mlir_ciface_` wrappers, which take a pointer to the
+    /// descriptor, the lowered function takes each field as a separate
+    /// argument.
+    pub fn packed_arguments(&mut self) -> Vec<*mut ()> {
+        [
+            &mut self.allocated as *mut *mut T as *mut (),
+            &mut self.aligned as *mut *mut T as *mut (),
+            &mut self.offset as *mut i64 as *mut (),
+        ]
+        .into_iter()
+        .chain(
+            self.sizes
+                .iter_mut()
+                .chain(&mut self.strides)
+                .map(|value| value as *mut i64 as *mut ()),
+        )
+        .collect()
+    }
+}
@@ -0,0 +1,1153 @@
+//! Montgomery form lowering of the felt dialect.
+//!
+//! In Montgomery form, a felt `a` is represented by `a * R mod p`, with
+//! `R = 2^32`. Additions, subtractions and negations are unchanged, while
+//! multiplications use Montgomery reduction, which replaces the division by
+//! `p` with multiplications, a shift and a conditional subtraction. See
+//! `pdl::Reduction::Montgomery`.
+//!
+//! Values are converted into Montgomery form when they enter a function, and
+//! back when they leave it, with `convert_boundaries`. The Montgomery product
+//! of `x` and `y` is `x * y / R`, so the conversions are `felt.mul`
+//! operations themselves: by `R^2 mod p` into Montgomery form, and by 1 out of
+//! it.
+//!
+//! Single-limb felts are `i32` values, and the product is reduced on 64 bits,
+//! so the modulus must be odd and below 2^31.
+//!
+//! Multi-limb felts are `i256` values, made of four 64-bit limbs, in a `Field`
+//! like the scalar field of BN254, with `R = 2^256`. They are converted with
+//! `convert_multi_limb_boundaries`, and the felt operations over them are
+//! lowered by `lower_multi_limb` rather than by patterns: the Montgomery
+//! product uses the coarsely integrated operand scanning (CIOS) method, which
+//! reduces the product one limb at a time, and division raises the divisor to
+//! the power `p - 2` in an `scf.for` loop.
+//!
+//! Only the field arithmetic is preserved, so operations that see the
+//! representatives of felts, like ordering comparisons, and casts to indices
+//! (like the bounds of `FeltBuilder::for_range`), are refused.
+
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+};
+
+use melior::{
+    Context,
+    dialect::arith,
+    ir::{
+        Attribute, Block, BlockLike, BlockRef, Location, Module, Region, RegionLike, Type, Value,
+        ValueLike,
+        attribute::IntegerAttribute,
+        operation::{Operation, OperationBuilder, OperationLike, OperationRef},
+        r#type::IntegerType,
+    },
+};
+use mlir_sys::{
+    MlirBlock, MlirOperation, MlirValue, mlirOpOperandGetNextUse, mlirOpOperandGetOperandNumber,
+    mlirOpOperandGetOwner, mlirOpOperandIsNull, mlirOperationDestroy, mlirOperationSetOperand,
+    mlirValueGetFirstUse, mlirValueReplaceAllUsesOfWith,
+};
+
+use crate::{error::Error, pdl::MODULUS, walk::walk};
+
+/// The number of bits of the Montgomery radix `R`.
+pub const RADIX_BITS: u32 = 32;
+
+/// `-p^-1 mod R`. Multiplying the low bits of a product by it gives the
+/// multiple of `p` that cancels them.
+pub const MODULUS_INVERSE: u32 = negated_inverse(MODULUS);
+
+/// `R^2 mod p`. The Montgomery product by it converts a felt into Montgomery
+/// form.
+pub const RADIX_SQUARED: u32 = {
+    let radix = (1 << RADIX_BITS) % MODULUS as u64;
+    (radix * radix % MODULUS as u64) as u32
+};
+
+/// Computes `-p^-1 mod 2^32` with Newton's iteration, which doubles the
+/// number of correct bits at every step.
+const fn negated_inverse(modulus: u32) -> u32 {
+    assert!(modulus % 2 == 1 && modulus < 1 << 31);
+
+    let mut inverse: u32 = 1;
+    let mut step = 0;
+    while step < 5 {
+        inverse = inverse.wrapping_mul(2u32.wrapping_sub(modulus.wrapping_mul(inverse)));
+        step += 1;
+    }
+    inverse.wrapping_neg()
+}
+
+/// The number of 64-bit limbs of a multi-limb felt.
+pub const LIMBS: usize = 4;
+
+/// The number of bits of a multi-limb felt, and of its Montgomery radix.
+pub const MULTI_LIMB_BITS: u32 = 64 * LIMBS as u32;
+
+/// The modulus of the scalar field of BN254, the default field of multi-limb
+/// felts.
+pub const BN254_MODULUS: &str =
+    "21888242871839275222246405745257275088548364400416034343698204186575808495617";
+
+/// A prime field of multi-limb felts. Numbers are stored as `LIMBS` 64-bit
+/// limbs, least significant first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Field {
+    /// The modulus `p`.
+    pub modulus: [u64; LIMBS],
+    /// `-p^-1 mod 2^64`. Multiplying the low limb of a product by it gives
+    /// the multiple of `p` that cancels it.
+    pub modulus_inverse: u64,
+    /// `R mod p`, the Montgomery form of 1.
+    pub radix: [u64; LIMBS],
+    /// `R^2 mod p`. The Montgomery product by it converts a felt into
+    /// Montgomery form.
+    pub radix_squared: [u64; LIMBS],
+}
+
+impl Field {
+    /// Returns the field of the given modulus, which must be odd, above 1 and
+    /// below `2^255`, so that the sum of two felts doesn't overflow. The
+    /// modulus must also be prime for division to work, which isn't checked.
+    pub fn new(modulus: [u64; LIMBS]) -> Result<Self, Error> {
+        if modulus[0] % 2 == 0 || modulus == [1, 0, 0, 0] || modulus[LIMBS - 1] >> 63 != 0 {
+            return Err(Error::Montgomery(format!(
+                "the modulus {} must be odd, above 1 and below 2^255",
+                to_decimal(modulus)
+            )));
+        }
+
+        // Newton's iteration doubles the number of correct bits at every
+        // step, like in `negated_inverse`.
+        let mut inverse: u64 = 1;
+        for _ in 0..6 {
+            inverse = inverse.wrapping_mul(2u64.wrapping_sub(modulus[0].wrapping_mul(inverse)));
+        }
+
+        // R and R^2 don't fit in the limbs, so they are reduced by doubling 1
+        // modulo p instead.
+        let mut radix = [1, 0, 0, 0];
+        for _ in 0..MULTI_LIMB_BITS {
+            radix = double_modulo(radix, modulus);
+        }
+        let mut radix_squared = radix;
+        for _ in 0..MULTI_LIMB_BITS {
+            radix_squared = double_modulo(radix_squared, modulus);
+        }
+
+        Ok(Field {
+            modulus,
+            modulus_inverse: inverse.wrapping_neg(),
+            radix,
+            radix_squared,
+        })
+    }
+
+    /// Returns the field of a modulus written in decimal.
+    pub fn parse(modulus: &str) -> Result<Self, Error> {
+        let invalid = || Error::Montgomery(format!("invalid modulus {modulus}"));
+        if modulus.is_empty() {
+            return Err(invalid());
+        }
+
+        let mut limbs = [0u64; LIMBS];
+        for digit in modulus.chars() {
+            let mut carry = u128::from(digit.to_digit(10).ok_or_else(invalid)?);
+            for limb in &mut limbs {
+                let value = u128::from(*limb) * 10 + carry;
+                *limb = value as u64;
+                carry = value >> 64;
+            }
+            if carry != 0 {
+                return Err(invalid());
+            }
+        }
+        Field::new(limbs)
+    }
+
+    /// Returns the scalar field of BN254.
+    pub fn bn254() -> Self {
+        Field::parse(BN254_MODULUS).unwrap()
+    }
+}
+
+impl fmt::Display for Field {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", to_decimal(self.modulus))
+    }
+}
+
+/// Subtracts two multi-limb numbers, and returns whether it wrapped around.
+fn subtract_limbs(lhs: [u64; LIMBS], rhs: [u64; LIMBS]) -> ([u64; LIMBS], bool) {
+    let mut difference = [0; LIMBS];
+    let mut borrow = false;
+    for ((difference, lhs), rhs) in difference.iter_mut().zip(lhs).zip(rhs) {
+        let (value, first_borrow) = lhs.overflowing_sub(rhs);
+        let (value, second_borrow) = value.overflowing_sub(u64::from(borrow));
+        *difference = value;
+        borrow = first_borrow || second_borrow;
+    }
+    (difference, borrow)
+}
+
+/// Computes `2 * value mod p`, for a value below `p`.
+fn double_modulo(value: [u64; LIMBS], modulus: [u64; LIMBS]) -> [u64; LIMBS] {
+    // The modulus is below 2^255, so the double doesn't overflow.
+    let mut double = [0; LIMBS];
+    let mut carry = 0;
+    for (double, limb) in double.iter_mut().zip(value) {
+        *double = (limb << 1) | carry;
+        carry = limb >> 63;
+    }
+    match subtract_limbs(double, modulus) {
+        (difference, false) => difference,
+        (_, true) => double,
+    }
+}
+
+fn to_decimal(mut limbs: [u64; LIMBS]) -> String {
+    let mut digits = Vec::new();
+    loop {
+        let mut remainder = 0u128;
+        for limb in limbs.iter_mut().rev() {
+            let value = (remainder << 64) | u128::from(*limb);
+            *limb = (value / 10) as u64;
+            remainder = value % 10;
+        }
+        digits.push(char::from(b'0' + remainder as u8));
+        if limbs == [0; LIMBS] {
+            break;
+        }
+    }
+    digits.iter().rev().collect()
+}
+
+/// Returns the `i256` attribute of a multi-limb number.
+fn multi_limb_attribute(ctx: &Context, limbs: [u64; LIMBS]) -> Attribute<'_> {
+    Attribute::parse(ctx, &format!("{} : i{MULTI_LIMB_BITS}", to_decimal(limbs))).unwrap()
+}
+
+/// Inserts the conversions into and out of Montgomery form around every felt
+/// value that crosses a function boundary.
+///
+/// The felt values are the operands and results of felt operations (and the
+/// arguments of their bodies), along with the values that `scf.for`,
+/// `scf.while` and `scf.if` carry with them, like the initial values of a loop.
+/// Other `i32` values are left as they are. Felt values enter Montgomery form
+/// when they are:
+///
+/// - arguments of a `func.func`, or elements of a `linalg.generic` payload.
+/// - results of an `arith.constant`, or of a `func.call`.
+///
+/// They leave it when they are operands of a `func.return`, a `func.call` or
+/// a `linalg.yield`. Every function takes and returns canonical
+/// representatives, so callers and callees are converted independently.
+///
+/// Any other operation that uses or defines felt values would see their
+/// Montgomery representatives, so it is refused, apart from `arith.cmpi`
+/// equality comparisons, which don't depend on the representatives.
+///
+/// Only single-limb felts are converted; see `convert_multi_limb_boundaries`.
+/// The module must be converted before the patterns built with
+/// `Reduction::Montgomery` are applied, and only once.
+pub fn convert_boundaries<'c>(ctx: &'c Context, module: &Module<'c>) -> Result<(), Error> {
+    let felt_type: Type<'c> = IntegerType::new(ctx, 32).into();
+    convert_boundaries_of(
+        ctx,
+        module,
+        felt_type,
+        IntegerAttribute::new(felt_type, RADIX_SQUARED.into()).into(),
+    )
+}
+
+/// Like `convert_boundaries`, for the multi-limb felts of the field. The
+/// module must be converted before `lower_multi_limb`, and only once.
+pub fn convert_multi_limb_boundaries<'c>(
+    ctx: &'c Context,
+    module: &Module<'c>,
+    field: &Field,
+) -> Result<(), Error> {
+    convert_boundaries_of(
+        ctx,
+        module,
+        IntegerType::new(ctx, MULTI_LIMB_BITS).into(),
+        multi_limb_attribute(ctx, field.radix_squared),
+    )
+}
+
+/// Converts the felt values of the given type, with the Montgomery product by
+/// `radix_squared` into Montgomery form.
+fn convert_boundaries_of<'c>(
+    ctx: &'c Context,
+    module: &Module<'c>,
+    felt_type: Type<'c>,
+    radix_squared: Attribute<'c>,
+) -> Result<(), Error> {
+    let mut felt_values = FeltValues::default();
+    walk(module.as_operation(), &mut |operation| {
+        felt_values.add_operation(operation)
+    });
+    felt_values.resolve();
+    // Single-limb and multi-limb felts are converted separately.
+    let is_felt = |value: MlirValue| {
+        felt_values.contains(value) && unsafe { Value::from_raw(value) }.r#type() == felt_type
+    };
+
+    // The conversions are inserted after the walk, so that they aren't
+    // converted themselves.
+    let mut entering = Vec::new();
+    let mut leaving = Vec::new();
+    let mut refused = None;
+    walk(module.as_operation(), &mut |operation| {
+        let name = operation.name();
+        let name = name.as_string_ref().as_str().unwrap();
+        let location = operation.location();
+        if name.starts_with("felt.") {
+            return;
+        }
+
+        match name {
+            "func.func" | "linalg.generic" => {
+                let Some(block) = operation.region(0).unwrap().first_block() else {
+                    return;
+                };
+                for index in 0..block.argument_count() {
+                    let argument = block.argument(index).unwrap();
+                    if is_felt(argument.to_raw()) {
+                        entering.push(Entering {
+                            value: argument.to_raw(),
+                            block: block.to_raw(),
+                            after: None,
+                            location,
+                        });
+                    }
+                }
+            }
+            "arith.constant" | "func.call" => {
+                for index in 0..operation.result_count() {
+                    let result = operation.result(index).unwrap();
+                    if is_felt(result.to_raw()) {
+                        entering.push(Entering {
+                            value: result.to_raw(),
+                            block: operation.block().unwrap().to_raw(),
+                            after: Some(operation.to_raw()),
+                            location,
+                        });
+                    }
+                }
+            }
+            "scf.for" | "scf.while" | "scf.if" | "scf.yield" | "scf.condition" | "gpu.yield" => {}
+            "arith.cmpi" if is_equality(operation) => {}
+            "func.return" | "linalg.yield" => {}
+            _ => {
+                let uses_felts = (0..operation.operand_count())
+                    .any(|index| is_felt(operation.operand(index).unwrap().to_raw()));
+                let defines_felts = (0..operation.result_count())
+                    .any(|index| is_felt(operation.result(index).unwrap().to_raw()));
+                if (uses_felts || defines_felts) && refused.is_none() {
+                    refused = Some(Error::Montgomery(format!(
+                        "{name} at {location} would see the Montgomery representatives of felts"
+                    )));
+                }
+            }
+        }
+
+        if let "func.return" | "func.call" | "linalg.yield" = name {
+            for index in 0..operation.operand_count() {
+                if is_felt(operation.operand(index).unwrap().to_raw()) {
+                    leaving.push((operation.to_raw(), index, location));
+                }
+            }
+        }
+    });
+    if let Some(error) = refused {
+        return Err(error);
+    }
+
+    for entering in entering {
+        let uses = uses(entering.value);
+        let block = unsafe { BlockRef::from_raw(entering.block) };
+        let constant = felt_constant(ctx, radix_squared, entering.location);
+        let constant = match entering.after {
+            Some(operation) => {
+                block.insert_operation_after(unsafe { OperationRef::from_raw(operation) }, constant)
+            }
+            None => block.insert_operation(0, constant),
+        };
+        let conversion = block.insert_operation_after(
+            constant,
+            multiply(
+                felt_type,
+                unsafe { Value::from_raw(entering.value) },
+                constant.result(0).unwrap().into(),
+                entering.location,
+            ),
+        );
+
+        for (owner, index) in uses {
+            unsafe {
+                mlirOperationSetOperand(owner, index, conversion.result(0).unwrap().to_raw())
+            };
+        }
+    }
+
+    for (operation, index, location) in leaving {
+        let operation = unsafe { OperationRef::from_raw(operation) };
+        let block = operation.block().unwrap();
+        let constant = block.insert_operation_before(
+            operation,
+            felt_constant(ctx, IntegerAttribute::new(felt_type, 1).into(), location),
+        );
+        let conversion = block.insert_operation_before(
+            operation,
+            multiply(
+                felt_type,
+                operation.operand(index).unwrap(),
+                constant.result(0).unwrap().into(),
+                location,
+            ),
+        );
+
+        unsafe {
+            mlirOperationSetOperand(
+                operation.to_raw(),
+                index as isize,
+                conversion.result(0).unwrap().to_raw(),
+            )
+        };
+    }
+
+    Ok(())
+}
+
+/// The felt values of a module. Values that must be in the same form, like
+/// the initial value of a loop and the argument of its body, are grouped
+/// together (with union-find), and a group holds felts if any of its values
+/// is used or defined by a felt operation.
+#[derive(Default)]
+struct FeltValues {
+    /// The parent of every value that isn't the root of its group.
+    parents: HashMap<usize, usize>,
+    /// The values used or defined by felt operations, and then the roots of
+    /// their groups once resolved.
+    felts: HashSet<usize>,
+}
+
+impl FeltValues {
+    fn add_operation(&mut self, operation: OperationRef) {
+        let name = operation.name();
+        let name = name.as_string_ref().as_str().unwrap();
+
+        if name.starts_with("felt.") {
+            let operands = (0..operation.operand_count())
+                .map(|index| operation.operand(index).unwrap().to_raw());
+            let results = (0..operation.result_count())
+                .map(|index| operation.result(index).unwrap().to_raw());
+            let arguments = (0..operation.region_count())
+                .filter_map(|index| operation.region(index).unwrap().first_block())
+                .flat_map(|block| {
+                    (0..block.argument_count())
+                        .map(move |index| block.argument(index).unwrap().to_raw())
+                });
+            self.felts.extend(
+                operands
+                    .chain(results)
+                    .chain(arguments)
+                    .map(|value| value.ptr as usize),
+            );
+            return;
+        }
+
+        match name {
+            "scf.for" => {
+                let body = operation.region(0).unwrap().first_block().unwrap();
+                let r#yield = body.terminator().unwrap();
+                for index in 0..operation.result_count() {
+                    self.union(&[
+                        operation.operand(3 + index).unwrap().to_raw(),
+                        operation.result(index).unwrap().to_raw(),
+                        body.argument(1 + index).unwrap().to_raw(),
+                        r#yield.operand(index).unwrap().to_raw(),
+                    ]);
+                }
+            }
+            "scf.while" => {
+                let before = operation.region(0).unwrap().first_block().unwrap();
+                let after = operation.region(1).unwrap().first_block().unwrap();
+                let condition = before.terminator().unwrap();
+                let r#yield = after.terminator().unwrap();
+                for index in 0..operation.operand_count() {
+                    self.union(&[
+                        operation.operand(index).unwrap().to_raw(),
+                        before.argument(index).unwrap().to_raw(),
+                        r#yield.operand(index).unwrap().to_raw(),
+                    ]);
+                }
+                for index in 0..operation.result_count() {
+                    self.union(&[
+                        condition.operand(1 + index).unwrap().to_raw(),
+                        after.argument(index).unwrap().to_raw(),
+                        operation.result(index).unwrap().to_raw(),
+                    ]);
+                }
+            }
+            "scf.if" => {
+                for index in 0..operation.result_count() {
+                    let mut values = vec![operation.result(index).unwrap().to_raw()];
+                    for region in 0..operation.region_count() {
+                        if let Some(block) = operation.region(region).unwrap().first_block() {
+                            let r#yield = block.terminator().unwrap();
+                            values.push(r#yield.operand(index).unwrap().to_raw());
+                        }
+                    }
+                    self.union(&values);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn union(&mut self, values: &[MlirValue]) {
+        let root = self.find(values[0].ptr as usize);
+        for value in &values[1..] {
+            let other = self.find(value.ptr as usize);
+            if other != root {
+                self.parents.insert(other, root);
+            }
+        }
+    }
+
+    fn find(&self, mut value: usize) -> usize {
+        while let Some(&parent) = self.parents.get(&value) {
+            value = parent;
+        }
+        value
+    }
+
+    /// Replaces the felt values with the roots of their groups. Must be
+    /// called once every operation was added.
+    fn resolve(&mut self) {
+        self.felts = self.felts.iter().map(|&value| self.find(value)).collect();
+    }
+
+    fn contains(&self, value: MlirValue) -> bool {
+        self.felts.contains(&self.find(value.ptr as usize))
+    }
+}
+
+/// Whether the operation is an `arith.cmpi` with the `eq` or `ne` predicate.
+fn is_equality(operation: OperationRef) -> bool {
+    operation
+        .attribute("predicate")
+        .ok()
+        .and_then(|predicate| IntegerAttribute::try_from(predicate).ok())
+        .is_some_and(|predicate| matches!(predicate.value(), 0 | 1))
+}
+
+/// A felt value entering Montgomery form, and where to insert its
+/// conversion: after the operation that defines it, or at the start of the
+/// block for arguments.
+struct Entering<'c> {
+    value: MlirValue,
+    block: MlirBlock,
+    after: Option<MlirOperation>,
+    location: Location<'c>,
+}
+
+/// Returns the operations using the value, with the position of the operand.
+fn uses(value: MlirValue) -> Vec<(MlirOperation, isize)> {
+    let mut uses = Vec::new();
+    let mut operand = unsafe { mlirValueGetFirstUse(value) };
+    while !unsafe { mlirOpOperandIsNull(operand) } {
+        uses.push(unsafe {
+            (
+                mlirOpOperandGetOwner(operand),
+                mlirOpOperandGetOperandNumber(operand) as isize,
+            )
+        });
+        operand = unsafe { mlirOpOperandGetNextUse(operand) };
+    }
+    uses
+}
+
+fn felt_constant<'c>(
+    ctx: &'c Context,
+    value: Attribute<'c>,
+    location: Location<'c>,
+) -> Operation<'c> {
+    arith::constant(ctx, value, location)
+}
+
+fn multiply<'c>(
+    felt_type: Type<'c>,
+    lhs: Value<'c, '_>,
+    rhs: Value<'c, '_>,
+    location: Location<'c>,
+) -> Operation<'c> {
+    OperationBuilder::new("felt.mul", location)
+        .add_operands(&[lhs, rhs])
+        .add_results(&[felt_type])
+        .build()
+        .unwrap()
+}
+
+/// Lowers the felt operations over multi-limb felts, in Montgomery form, to
+/// `arith` operations on `i256` values and on their 64-bit limbs.
+///
+/// A Montgomery product takes a few hundred operations, and division needs a
+/// loop, so the operations are rewritten here rather than by PDL patterns.
+/// The module must be converted with `convert_multi_limb_boundaries` first,
+/// and lowered before the patterns, which only handle single-limb felts.
+pub fn lower_multi_limb<'c>(ctx: &'c Context, module: &Module<'c>, field: &Field) {
+    let felt_type: Type<'c> = IntegerType::new(ctx, MULTI_LIMB_BITS).into();
+
+    let mut operations = Vec::new();
+    walk(module.as_operation(), &mut |operation| {
+        let name = operation.name();
+        if name.as_string_ref().as_str().unwrap().starts_with("felt.")
+            && operation.result_count() == 1
+            && operation.result(0).unwrap().r#type() == felt_type
+        {
+            operations.push(operation.to_raw());
+        }
+    });
+
+    for operation in operations {
+        let operation = unsafe { OperationRef::from_raw(operation) };
+        let builder = MultiLimbBuilder {
+            ctx,
+            field,
+            block: operation.block().unwrap(),
+            before: Some(operation),
+            location: operation.location(),
+        };
+        let operand = |index| operation.operand(index).unwrap();
+        let name = operation.name();
+        let result = match name.as_string_ref().as_str().unwrap() {
+            "felt.add" => builder.add(operand(0), operand(1)),
+            "felt.sub" => builder.subtract(operand(0), operand(1)),
+            "felt.mul" => builder.multiply(operand(0), operand(1)),
+            "felt.div" => builder.divide(operand(0), operand(1)),
+            "felt.neg" => builder.negate(operand(0)),
+            _ => continue,
+        };
+
+        unsafe {
+            mlirValueReplaceAllUsesOfWith(operation.result(0).unwrap().to_raw(), result.to_raw());
+            mlirOperationDestroy(operation.to_raw());
+        }
+    }
+}
+
+/// Inserts the arithmetic of multi-limb felts in Montgomery form, before an
+/// operation, or at the end of a block.
+struct MultiLimbBuilder<'c, 'a> {
+    ctx: &'c Context,
+    field: &'a Field,
+    block: BlockRef<'c, 'a>,
+    before: Option<OperationRef<'c, 'a>>,
+    location: Location<'c>,
+}
+
+impl<'c, 'a> MultiLimbBuilder<'c, 'a> {
+    /// `lhs + rhs mod p`.
+    fn add(&self, lhs: Value<'c, '_>, rhs: Value<'c, '_>) -> Value<'c, 'a> {
+        let sum = self.binary("arith.addi", lhs, rhs);
+        self.reduce(sum)
+    }
+
+    /// `lhs - rhs mod p`. The difference wraps around if `lhs < rhs`, in
+    /// which case adding `p` wraps it back below `p`.
+    fn subtract(&self, lhs: Value<'c, '_>, rhs: Value<'c, '_>) -> Value<'c, 'a> {
+        let difference = self.binary("arith.subi", lhs, rhs);
+        let modulus = self.felt_constant(self.field.modulus);
+        let corrected = self.binary("arith.addi", difference, modulus);
+        self.binary("arith.minui", difference, corrected)
+    }
+
+    /// `-value mod p`.
+    fn negate(&self, value: Value<'c, '_>) -> Value<'c, 'a> {
+        let zero = self.felt_constant([0; LIMBS]);
+        self.subtract(zero, value)
+    }
+
+    /// The Montgomery product `lhs * rhs / R mod p`, with the coarsely
+    /// integrated operand scanning (CIOS) method: for every limb of `rhs`, the
+    /// product of `lhs` by it is accumulated into `t`, along with the multiple
+    /// of `p` that cancels the low limb of `t`, which is then shifted out. The
+    /// result is below `2p`, so it is reduced once.
+    fn multiply(&self, lhs: Value<'c, '_>, rhs: Value<'c, '_>) -> Value<'c, 'a> {
+        let lhs = self.limbs(lhs);
+        let rhs = self.limbs(rhs);
+        let modulus = self
+            .field
+            .modulus
+            .iter()
+            .map(|&limb| {
+                let limb = self.constant(64, limb as i64);
+                self.cast("arith.extui", limb, 128)
+            })
+            .collect::<Vec<_>>();
+        let modulus_inverse = self.constant(64, self.field.modulus_inverse as i64);
+        let zero = self.constant(64, 0);
+
+        let mut t = vec![zero; LIMBS + 2];
+        for &rhs_limb in &rhs {
+            let mut carry = zero;
+            for (t, &lhs_limb) in t.iter_mut().zip(&lhs) {
+                (*t, carry) = self.multiply_add(*t, lhs_limb, rhs_limb, carry);
+            }
+            (t[LIMBS], t[LIMBS + 1]) = self.add_carry(t[LIMBS], carry);
+
+            let factor = self.binary("arith.muli", t[0], modulus_inverse);
+            let factor = self.cast("arith.extui", factor, 128);
+            // The low limb of the sum is 0.
+            (_, carry) = self.multiply_add(t[0], factor, modulus[0], zero);
+            for (index, &modulus_limb) in modulus.iter().enumerate().skip(1) {
+                (t[index - 1], carry) = self.multiply_add(t[index], factor, modulus_limb, carry);
+            }
+            (t[LIMBS - 1], carry) = self.add_carry(t[LIMBS], carry);
+            t[LIMBS] = self.binary("arith.addi", t[LIMBS + 1], carry);
+        }
+
+        // The modulus is below 2^255, so the result fits in `LIMBS` limbs.
+        let mut result = self.constant(MULTI_LIMB_BITS, 0);
+        for (index, &limb) in t[..LIMBS].iter().enumerate() {
+            let limb = self.cast("arith.extui", limb, MULTI_LIMB_BITS);
+            let shift = self.constant(MULTI_LIMB_BITS, 64 * index as i64);
+            let limb = self.binary("arith.shli", limb, shift);
+            result = self.binary("arith.ori", result, limb);
+        }
+        self.reduce(result)
+    }
+
+    /// `lhs / rhs mod p`, the product of `lhs` by the inverse of `rhs`, which
+    /// is `rhs^(p - 2)` by Fermat's little theorem. The power is computed in
+    /// an `scf.for` loop, by squaring, and multiplying by `rhs` for every set
+    /// bit of the exponent, from the most significant one.
+    fn divide(&self, lhs: Value<'c, '_>, rhs: Value<'c, '_>) -> Value<'c, 'a> {
+        let felt_type = self.integer_type(MULTI_LIMB_BITS);
+        let index_type = Type::index(self.ctx);
+        let index = |value| {
+            self.insert(arith::constant(
+                self.ctx,
+                IntegerAttribute::new(index_type, value).into(),
+                self.location,
+            ))
+        };
+        let (exponent, _) = subtract_limbs(self.field.modulus, [2, 0, 0, 0]);
+        let exponent = self.felt_constant(exponent);
+        let lower = index(0);
+        let upper = index(MULTI_LIMB_BITS.into());
+        let step = index(1);
+        let one = self.felt_constant(self.field.radix);
+
+        let region = Region::new();
+        let body = region.append_block(Block::new(&[
+            (index_type, self.location),
+            (felt_type, self.location),
+        ]));
+        let builder = MultiLimbBuilder {
+            ctx: self.ctx,
+            field: self.field,
+            block: body,
+            before: None,
+            location: self.location,
+        };
+        let power = body.argument(1).unwrap().into();
+        let square = builder.multiply(power, power);
+        let product = builder.multiply(square, rhs);
+        let position = builder.cast(
+            "arith.index_cast",
+            body.argument(0).unwrap().into(),
+            MULTI_LIMB_BITS,
+        );
+        let top = builder.constant(MULTI_LIMB_BITS, (MULTI_LIMB_BITS - 1).into());
+        let shift = builder.binary("arith.subi", top, position);
+        let bit = builder.binary("arith.shrui", exponent, shift);
+        let bit = builder.cast("arith.trunci", bit, 1);
+        let power = builder.insert(
+            OperationBuilder::new("arith.select", self.location)
+                .add_operands(&[bit, product, square])
+                .add_results(&[felt_type])
+                .build()
+                .unwrap(),
+        );
+        body.append_operation(
+            OperationBuilder::new("scf.yield", self.location)
+                .add_operands(&[power])
+                .build()
+                .unwrap(),
+        );
+
+        let inverse = self.insert(
+            OperationBuilder::new("scf.for", self.location)
+                .add_operands(&[lower, upper, step, one])
+                .add_results(&[felt_type])
+                .add_regions([region])
+                .build()
+                .unwrap(),
+        );
+        self.multiply(lhs, inverse)
+    }
+
+    /// Subtracts `p` from a value below `2p`, unless the value is below `p`,
+    /// in which case the difference wraps around above it.
+    fn reduce(&self, value: Value<'c, '_>) -> Value<'c, 'a> {
+        let modulus = self.felt_constant(self.field.modulus);
+        let difference = self.binary("arith.subi", value, modulus);
+        self.binary("arith.minui", value, difference)
+    }
+
+    /// Returns the limbs of a multi-limb felt, zero-extended to 128 bits, so
+    /// that their products don't overflow.
+    fn limbs(&self, value: Value<'c, '_>) -> Vec<Value<'c, 'a>> {
+        (0..LIMBS)
+            .map(|index| {
+                let shift = self.constant(MULTI_LIMB_BITS, 64 * index as i64);
+                let limb = self.binary("arith.shrui", value, shift);
+                let limb = self.cast("arith.trunci", limb, 64);
+                self.cast("arith.extui", limb, 128)
+            })
+            .collect()
+    }
+
+    /// Computes `t + lhs * rhs + carry`, where `lhs` and `rhs` are extended
+    /// limbs, and returns its low limb and its high limb, the next carry. The
+    /// sum is at most `2^128 - 1`, so it doesn't overflow.
+    fn multiply_add(
+        &self,
+        t: Value<'c, '_>,
+        lhs: Value<'c, '_>,
+        rhs: Value<'c, '_>,
+        carry: Value<'c, '_>,
+    ) -> (Value<'c, 'a>, Value<'c, 'a>) {
+        let product = self.binary("arith.muli", lhs, rhs);
+        let t = self.cast("arith.extui", t, 128);
+        let sum = self.binary("arith.addi", product, t);
+        let carry = self.cast("arith.extui", carry, 128);
+        let sum = self.binary("arith.addi", sum, carry);
+        self.split(sum)
+    }
+
+    /// Computes `t + carry`, and returns its low limb and the next carry.
+    fn add_carry(&self, t: Value<'c, '_>, carry: Value<'c, '_>) -> (Value<'c, 'a>, Value<'c, 'a>) {
+        let t = self.cast("arith.extui", t, 128);
+        let carry = self.cast("arith.extui", carry, 128);
+        let sum = self.binary("arith.addi", t, carry);
+        self.split(sum)
+    }
+
+    /// Splits a 128-bit value into its low and high limbs.
+    fn split(&self, value: Value<'c, '_>) -> (Value<'c, 'a>, Value<'c, 'a>) {
+        let low = self.cast("arith.trunci", value, 64);
+        let shift = self.constant(128, 64);
+        let high = self.binary("arith.shrui", value, shift);
+        (low, self.cast("arith.trunci", high, 64))
+    }
+
+    fn integer_type(&self, bits: u32) -> Type<'c> {
+        IntegerType::new(self.ctx, bits).into()
+    }
+
+    fn constant(&self, bits: u32, value: i64) -> Value<'c, 'a> {
+        self.insert(arith::constant(
+            self.ctx,
+            IntegerAttribute::new(self.integer_type(bits), value).into(),
+            self.location,
+        ))
+    }
+
+    fn felt_constant(&self, limbs: [u64; LIMBS]) -> Value<'c, 'a> {
+        self.insert(felt_constant(
+            self.ctx,
+            multi_limb_attribute(self.ctx, limbs),
+            self.location,
+        ))
+    }
+
+    /// Inserts an `arith` operation with two operands, and a result of the
+    /// type of the first one.
+    fn binary(&self, name: &str, lhs: Value<'c, '_>, rhs: Value<'c, '_>) -> Value<'c, 'a> {
+        self.insert(
+            OperationBuilder::new(name, self.location)
+                .add_operands(&[lhs, rhs])
+                .add_results(&[lhs.r#type()])
+                .build()
+                .unwrap(),
+        )
+    }
+
+    /// Inserts a cast, like `arith.extui`, to an integer of the given width.
+    fn cast(&self, name: &str, value: Value<'c, '_>, bits: u32) -> Value<'c, 'a> {
+        self.insert(
+            OperationBuilder::new(name, self.location)
+                .add_operands(&[value])
+                .add_results(&[self.integer_type(bits)])
+                .build()
+                .unwrap(),
+        )
+    }
+
+    fn insert(&self, operation: Operation<'c>) -> Value<'c, 'a> {
+        let operation = match self.before {
+            Some(before) => self.block.insert_operation_before(before, operation),
+            None => self.block.append_operation(operation),
+        };
+        operation.result(0).unwrap().into()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use melior::{
+        ExecutionEngine,
+        ir::{BlockLike, Module, operation::OperationLike},
+    };
+
+    use crate::{
+        caller_location, convert_to_llvm,
+        dsl::FeltFn,
+        error::Error,
+        execute_entrypoint,
+        legality::verify_dialect_lowered,
+        montgomery::{
+            BN254_MODULUS, Field, LIMBS, MODULUS_INVERSE, MULTI_LIMB_BITS, convert_boundaries,
+            convert_multi_limb_boundaries, double_modulo, lower_multi_limb, subtract_limbs,
+        },
+        parse_module,
+        pdl::{MODULUS, Reduction, build_reduction_pattern_module},
+        pipeline::PipelineOptions,
+        testing::{felt_context, lower},
+    };
+
+    /// A multi-limb felt, aligned like an `i256` in memory.
+    #[repr(C, align(32))]
+    struct Limbs([u64; LIMBS]);
+
+    /// JIT compiles the module, and calls a function taking and returning
+    /// multi-limb felts.
+    fn execute_multi_limb(module: &Module, name: &str, arguments: &[[u64; LIMBS]]) -> [u64; LIMBS] {
+        let execution_engine = ExecutionEngine::new(module, 0, &[], false);
+
+        let mut values = arguments
+            .iter()
+            .map(|&limbs| Limbs(limbs))
+            .chain([Limbs([0; LIMBS])])
+            .collect::<Vec<_>>();
+        let mut arguments = values
+            .iter_mut()
+            .map(|value| value as *mut Limbs as *mut ())
+            .collect::<Vec<_>>();
+        unsafe {
+            execution_engine
+                .invoke_packed(name, &mut arguments)
+                .unwrap()
+        }
+
+        values.last().unwrap().0
+    }
+
+    fn add_modulo(lhs: [u64; LIMBS], rhs: [u64; LIMBS], field: &Field) -> [u64; LIMBS] {
+        let mut sum = [0; LIMBS];
+        let mut carry = false;
+        for ((sum, lhs), rhs) in sum.iter_mut().zip(lhs).zip(rhs) {
+            let (value, first_carry) = lhs.overflowing_add(rhs);
+            let (value, second_carry) = value.overflowing_add(u64::from(carry));
+            *sum = value;
+            carry = first_carry || second_carry;
+        }
+        match subtract_limbs(sum, field.modulus) {
+            (difference, false) => difference,
+            (_, true) => sum,
+        }
+    }
+
+    /// Computes `lhs * rhs mod p` by doubling and adding, without Montgomery
+    /// form.
+    fn multiply_modulo(lhs: [u64; LIMBS], rhs: [u64; LIMBS], field: &Field) -> [u64; LIMBS] {
+        let mut product = [0; LIMBS];
+        for bit in (0..MULTI_LIMB_BITS as usize).rev() {
+            product = double_modulo(product, field.modulus);
+            if (rhs[bit / 64] >> (bit % 64)) & 1 == 1 {
+                product = add_modulo(product, lhs, field);
+            }
+        }
+        product
+    }
+
+    #[test]
+    fn modulus_inverse() {
+        assert_eq!(MODULUS.wrapping_mul(MODULUS_INVERSE), u32::MAX);
+    }
+
+    #[test]
+    fn field_constants() {
+        let field = Field::bn254();
+        assert_eq!(field.to_string(), BN254_MODULUS);
+        assert_eq!(
+            field.modulus[0].wrapping_mul(field.modulus_inverse),
+            u64::MAX
+        );
+        assert_eq!(
+            multiply_modulo(field.radix, field.radix, &field),
+            field.radix_squared
+        );
+
+        assert_eq!(Field::parse("13").unwrap().modulus, [13, 0, 0, 0]);
+        // Even, too small, too large, or not decimal.
+        for modulus in ["12", "1", &"9".repeat(78), "0x11", ""] {
+            assert!(matches!(Field::parse(modulus), Err(Error::Montgomery(_))));
+        }
+    }
+
+    #[test]
+    fn execute_multi_limb_felts() {
+        let context = felt_context();
+        let field = Field::bn254();
+
+        let mut module = parse_module(
+            &context,
+            r#"
+            module {
+              func.func @mul(%a: i256, %b: i256) -> i256 {
+                %0 = "felt.mul"(%a, %b) : (i256, i256) -> i256
+                return %0 : i256
+              }
+              func.func @div(%a: i256, %b: i256) -> i256 {
+                %0 = "felt.div"(%a, %b) : (i256, i256) -> i256
+                return %0 : i256
+              }
+              func.func @add_sub_neg(%a: i256, %b: i256) -> i256 {
+                %0 = "felt.add"(%a, %b) : (i256, i256) -> i256
+                %1 = "felt.neg"(%b) : (i256) -> i256
+                %2 = "felt.sub"(%1, %0) : (i256, i256) -> i256
+                return %2 : i256
+              }
+            }"#,
+            "execute_multi_limb_felts",
+        )
+        .unwrap();
+
+        // Single-limb conversions leave multi-limb felts alone.
+        let source = module.as_operation().to_string();
+        convert_boundaries(&context, &module).unwrap();
+        assert_eq!(module.as_operation().to_string(), source);
+
+        convert_multi_limb_boundaries(&context, &module, &field).unwrap();
+        lower_multi_limb(&context, &module, &field);
+        verify_dialect_lowered(&module, "felt").unwrap();
+        assert!(module.as_operation().verify());
+        convert_to_llvm(&context, &mut module, &PipelineOptions::default());
+
+        // p - 1, and a felt with every limb set.
+        let (a, _) = subtract_limbs(field.modulus, [1, 0, 0, 0]);
+        let b = [
+            0x0123_4567_89ab_cdef,
+            0xfedc_ba98_7654_3210,
+            0x0f0f_0f0f_0f0f_0f0f,
+            0x0102_0304_0506_0708,
+        ];
+        assert_eq!(
+            execute_multi_limb(&module, "mul", &[a, b]),
+            multiply_modulo(a, b, &field)
+        );
+        let quotient = execute_multi_limb(&module, "div", &[a, b]);
+        assert_eq!(multiply_modulo(quotient, b, &field), a);
+        // -b - (a + b) + (a + b) + b = 0.
+        let difference = execute_multi_limb(&module, "add_sub_neg", &[a, b]);
+        assert_eq!(
+            add_modulo(
+                add_modulo(difference, add_modulo(a, b, &field), &field),
+                b,
+                &field
+            ),
+            [0; LIMBS]
+        );
+    }
+
+    #[test]
+    fn execute_in_montgomery_form() {
+        let context = felt_context();
+
+        let mut module = Module::new(caller_location(&context));
+        module.body().append_operation(
+            FeltFn::new(&context, "entrypoint", 2, |b, [x, y]| {
+                x * y / (x + b.constant(3)) - y
+            })
+            .into(),
+        );
+        convert_boundaries(&context, &module).unwrap();
+        assert!(module.as_operation().verify());
+
+        lower(
+            &context,
+            &mut module,
+            build_reduction_pattern_module(&context, Reduction::Montgomery),
+            &PipelineOptions::default(),
+        );
+
+        // 4 * 9 = 10, and the inverse of 4 + 3 = 7 is 2, so the quotient is
+        // 20 = 7, and 7 - 9 = 11.
+        assert_eq!(execute_entrypoint(&module, 4, 9), 11);
+    }
+
+    #[test]
+    fn keep_other_values() {
+        let context = felt_context();
+
+        let module = parse_module(
+            &context,
+            r#"
+            module {
+              func.func @increment(%n: i32) -> i32 {
+                %c1 = arith.constant 1 : i32
+                %0 = arith.addi %n, %c1 : i32
+                return %0 : i32
+              }
+              func.func @square(%x: i32) -> i32 {
+                %0 = "felt.mul"(%x, %x) : (i32, i32) -> i32
+                return %0 : i32
+              }
+            }"#,
+            "keep_other_values",
+        )
+        .unwrap();
+        let increment = module.body().first_operation().unwrap().to_string();
+        convert_boundaries(&context, &module).unwrap();
+
+        // Only the argument and the result of @square are converted.
+        assert_eq!(
+            module.body().first_operation().unwrap().to_string(),
+            increment
+        );
+        assert_eq!(
+            module
+                .as_operation()
+                .to_string()
+                .matches("felt.mul")
+                .count(),
+            3
+        );
+    }
+
+    #[test]
+    fn refuse_ordering() {
+        let context = felt_context();
+
+        let module = Module::new(caller_location(&context));
+        module.body().append_operation(
+            FeltFn::new(&context, "entrypoint", 2, |b, [x, y]| {
+                let [result] = b.if_else(x.less_than(y), |_| [x * y], |_| [y]);
+                result
+            })
+            .into(),
+        );
+
+        assert!(matches!(
+            convert_boundaries(&context, &module),
+            Err(Error::Montgomery(_))
+        ));
+    }
+}
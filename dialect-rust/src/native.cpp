@@ -0,0 +1,333 @@
+// Registration of native PDL functions, which the MLIR 19 C API doesn't
+// expose. See `native.rs`.
+//
+// The C API doesn't expose crash reproducers, IR printing, timing or pass
+// statistics either, so they are enabled here too. See `pipeline.rs`.
+//
+// Nor does it expose the dialect conversion driver, which is wrapped here
+// too. See `apply_pdl_conversion` in `lib.rs`.
+
+#include "mlir-c/Rewrite.h"
+#include "mlir/CAPI/IR.h"
+#include "mlir/CAPI/Pass.h"
+#include "mlir/CAPI/Support.h"
+#include "mlir/IR/BuiltinOps.h"
+#include "mlir/IR/Diagnostics.h"
+#include "mlir/IR/PatternMatch.h"
+#include "mlir/Pass/PassInstrumentation.h"
+#include "mlir/Pass/PassManager.h"
+#include "mlir/Rewrite/FrozenRewritePatternSet.h"
+#include "mlir/Support/Timing.h"
+#include "mlir/Transforms/DialectConversion.h"
+#include "llvm/ADT/MapVector.h"
+#include "llvm/Support/CrashRecoveryContext.h"
+#include "llvm/Support/raw_ostream.h"
+
+using namespace mlir;
+
+extern "C" {
+
+/// A PDL value passed to, or returned from, a native function. Ranges are not
+/// supported.
+struct DialectRustPdlValue {
+  enum Kind : uint32_t { Attribute, Operation, Type, Value } kind;
+  const void *ptr;
+};
+
+typedef bool (*DialectRustNativeConstraint)(const void *data,
+                                            const DialectRustPdlValue *arguments,
+                                            size_t count);
+typedef bool (*DialectRustNativeRewrite)(const void *data,
+                                         const DialectRustPdlValue *arguments,
+                                         size_t count, void *results);
+
+typedef void (*DialectRustWriteCallback)(void *data, const char *ptr,
+                                         size_t size);
+
+} // extern "C"
+
+/// Converts the arguments of a native function. Fails if any of them is a
+/// range.
+static LogicalResult
+convertArguments(ArrayRef<PDLValue> values,
+                 SmallVectorImpl<DialectRustPdlValue> &arguments) {
+  for (PDLValue value : values) {
+    switch (value.getKind()) {
+    case PDLValue::Kind::Attribute:
+      arguments.push_back({DialectRustPdlValue::Attribute,
+                           wrap(value.cast<Attribute>()).ptr});
+      break;
+    case PDLValue::Kind::Operation:
+      arguments.push_back({DialectRustPdlValue::Operation,
+                           wrap(value.cast<Operation *>()).ptr});
+      break;
+    case PDLValue::Kind::Type:
+      arguments.push_back(
+          {DialectRustPdlValue::Type, wrap(value.cast<Type>()).ptr});
+      break;
+    case PDLValue::Kind::Value:
+      arguments.push_back(
+          {DialectRustPdlValue::Value, wrap(value.cast<Value>()).ptr});
+      break;
+    default:
+      return failure();
+    }
+  }
+  return success();
+}
+
+namespace {
+
+/// A stream that passes everything written to it to a callback. It is
+/// unbuffered, so nothing is left to flush when it is destroyed.
+///
+/// The callback isn't synchronized, so the passes that write to the stream
+/// must run in a single-threaded context.
+class CallbackStream : public llvm::raw_ostream {
+public:
+  CallbackStream(DialectRustWriteCallback callback, void *data)
+      : raw_ostream(/*unbuffered=*/true), callback(callback), data(data) {}
+
+private:
+  void write_impl(const char *ptr, size_t size) override {
+    callback(data, ptr, size);
+    position += size;
+  }
+
+  uint64_t current_pos() const override { return position; }
+
+  DialectRustWriteCallback callback;
+  void *data;
+  uint64_t position = 0;
+};
+
+/// Records the statistics of every pass after it runs, and prints them,
+/// summed by pass, when the pass manager is destroyed.
+///
+/// `PassManager::enableStatistics` always prints its report to stderr, so
+/// the statistics are collected here instead. In a multithreaded context,
+/// nested passes are cloned for each thread, and each clone has its own
+/// statistics.
+class StatisticsInstrumentation : public PassInstrumentation {
+public:
+  explicit StatisticsInstrumentation(llvm::raw_ostream &os) : os(os) {}
+
+  ~StatisticsInstrumentation() override { print(); }
+
+  void runAfterPass(Pass *pass, Operation *) override { record(pass); }
+
+  void runAfterPassFailed(Pass *pass, Operation *) override { record(pass); }
+
+private:
+  struct Statistic {
+    StringRef name;
+    StringRef description;
+    uint64_t value;
+  };
+
+  void record(Pass *pass) {
+    SmallVector<Statistic> &values = statistics[pass];
+    values.clear();
+    for (Pass::Statistic *statistic : pass->getStatistics())
+      values.push_back(
+          {statistic->getName(), statistic->getDesc(), statistic->getValue()});
+  }
+
+  void print() {
+    llvm::MapVector<StringRef, llvm::MapVector<StringRef, Statistic>> passes;
+    for (auto &entry : statistics) {
+      Pass *pass = entry.first;
+      if (entry.second.empty())
+        continue;
+      StringRef name =
+          pass->getArgument().empty() ? pass->getName() : pass->getArgument();
+      llvm::MapVector<StringRef, Statistic> &sums = passes[name];
+      for (const Statistic &statistic : entry.second) {
+        auto inserted = sums.insert({statistic.name, statistic});
+        if (!inserted.second)
+          inserted.first->second.value += statistic.value;
+      }
+    }
+
+    os << "===" << std::string(73, '-') << "===\n"
+       << "                         ... Pass statistics report ...\n"
+       << "===" << std::string(73, '-') << "===\n";
+    for (auto &pass : passes) {
+      os << pass.first << "\n";
+      for (auto &statistic : pass.second)
+        os << "  (S) " << statistic.second.value << " "
+           << statistic.second.name << " - " << statistic.second.description
+           << "\n";
+    }
+  }
+
+  llvm::raw_ostream &os;
+  llvm::MapVector<Pass *, SmallVector<Statistic>> statistics;
+};
+
+/// Applies the patterns as a partial or full dialect conversion. The given
+/// dialects are legal or illegal, and so is the module operation itself.
+///
+/// If the conversion fails, it is rolled back, and its diagnostics are passed
+/// to the callback, instead of the handlers of the context.
+LogicalResult applyConversion(MlirOperation op,
+                              MlirFrozenRewritePatternSet patterns,
+                              const MlirStringRef *legalDialects,
+                              size_t legalCount,
+                              const MlirStringRef *illegalDialects,
+                              size_t illegalCount,
+                              DialectRustWriteCallback callback, void *data,
+                              bool full) {
+  Operation *operation = unwrap(op);
+  MLIRContext *context = operation->getContext();
+
+  ConversionTarget target(*context);
+  target.addLegalOp<ModuleOp>();
+  for (size_t i = 0; i < legalCount; ++i)
+    target.addLegalDialect(unwrap(legalDialects[i]));
+  for (size_t i = 0; i < illegalCount; ++i)
+    target.addIllegalDialect(unwrap(illegalDialects[i]));
+
+  ScopedDiagnosticHandler handler(context, [&](Diagnostic &diagnostic) {
+    std::string message;
+    llvm::raw_string_ostream os(message);
+    os << diagnostic.getLocation() << ": " << diagnostic;
+    os.flush();
+    callback(data, message.data(), message.size());
+    return success();
+  });
+
+  const FrozenRewritePatternSet &frozenPatterns =
+      *static_cast<FrozenRewritePatternSet *>(patterns.ptr);
+  return full ? applyFullConversion(operation, target, frozenPatterns)
+              : applyPartialConversion(operation, target, frozenPatterns);
+}
+
+} // namespace
+
+extern "C" {
+
+void dialectRustRegisterNativeConstraint(MlirPDLPatternModule module,
+                                         MlirStringRef name,
+                                         DialectRustNativeConstraint callback,
+                                         const void *data) {
+  static_cast<PDLPatternModule *>(module.ptr)
+      ->registerConstraintFunction(
+          unwrap(name),
+          [=](PatternRewriter &, PDLResultList &,
+              ArrayRef<PDLValue> values) -> LogicalResult {
+            SmallVector<DialectRustPdlValue> arguments;
+            if (failed(convertArguments(values, arguments)))
+              return failure();
+            return success(callback(data, arguments.data(), arguments.size()));
+          });
+}
+
+void dialectRustRegisterNativeRewrite(MlirPDLPatternModule module,
+                                      MlirStringRef name,
+                                      DialectRustNativeRewrite callback,
+                                      const void *data) {
+  static_cast<PDLPatternModule *>(module.ptr)
+      ->registerRewriteFunction(
+          unwrap(name),
+          [=](PatternRewriter &, PDLResultList &results,
+              ArrayRef<PDLValue> values) -> LogicalResult {
+            SmallVector<DialectRustPdlValue> arguments;
+            if (failed(convertArguments(values, arguments)))
+              return failure();
+            return success(
+                callback(data, arguments.data(), arguments.size(), &results));
+          });
+}
+
+void dialectRustPushNativeResult(void *results, DialectRustPdlValue value) {
+  PDLResultList &list = *static_cast<PDLResultList *>(results);
+  switch (value.kind) {
+  case DialectRustPdlValue::Attribute:
+    list.push_back(unwrap(MlirAttribute{value.ptr}));
+    break;
+  case DialectRustPdlValue::Operation:
+    list.push_back(unwrap(MlirOperation{const_cast<void *>(value.ptr)}));
+    break;
+  case DialectRustPdlValue::Type:
+    list.push_back(unwrap(MlirType{value.ptr}));
+    break;
+  case DialectRustPdlValue::Value:
+    list.push_back(unwrap(MlirValue{value.ptr}));
+    break;
+  }
+}
+
+void dialectRustEnableCrashReproducer(MlirPassManager passManager,
+                                      MlirStringRef outputFile) {
+  // Without crash recovery, a crashing pass takes the process down before
+  // the reproducer is written.
+  llvm::CrashRecoveryContext::Enable();
+  unwrap(passManager)->enableCrashReproducerGeneration(unwrap(outputFile));
+}
+
+bool dialectRustIsMultithreadingEnabled(MlirContext context) {
+  return unwrap(context)->isMultithreadingEnabled();
+}
+
+void *dialectRustCreateStream(DialectRustWriteCallback callback, void *data) {
+  return new CallbackStream(callback, data);
+}
+
+void dialectRustDestroyStream(void *stream) {
+  delete static_cast<CallbackStream *>(stream);
+}
+
+void dialectRustEnableIRPrinting(MlirPassManager passManager, bool before,
+                                 bool after, bool afterOnlyOnChange,
+                                 void *stream) {
+  auto printBefore = [=](Pass *, Operation *) { return before; };
+  auto printAfter = [=](Pass *, Operation *) {
+    return after || afterOnlyOnChange;
+  };
+  // The stream already requires a single-threaded context, so the whole
+  // module can be printed, and not only the operation that the pass ran on.
+  unwrap(passManager)
+      ->enableIRPrinting(printBefore, printAfter, /*printModuleScope=*/true,
+                         /*printAfterOnlyOnChange=*/!after,
+                         /*printAfterOnlyOnFailure=*/false,
+                         *static_cast<CallbackStream *>(stream));
+}
+
+void dialectRustEnableTiming(MlirPassManager passManager, void *stream) {
+  auto timingManager = std::make_unique<DefaultTimingManager>();
+  timingManager->setEnabled(true);
+  timingManager->setOutput(
+      createOutputStrategy(DefaultTimingManager::OutputFormat::Text,
+                           *static_cast<CallbackStream *>(stream)));
+  // The timing manager prints its report when the pass manager is destroyed.
+  unwrap(passManager)->enableTiming(std::move(timingManager));
+}
+
+void dialectRustEnableStatistics(MlirPassManager passManager, void *stream) {
+  unwrap(passManager)
+      ->addInstrumentation(std::make_unique<StatisticsInstrumentation>(
+          *static_cast<CallbackStream *>(stream)));
+}
+
+MlirLogicalResult dialectRustApplyPartialConversion(
+    MlirOperation op, MlirFrozenRewritePatternSet patterns,
+    const MlirStringRef *legalDialects, size_t legalCount,
+    const MlirStringRef *illegalDialects, size_t illegalCount,
+    DialectRustWriteCallback callback, void *data) {
+  return wrap(applyConversion(op, patterns, legalDialects, legalCount,
+                              illegalDialects, illegalCount, callback, data,
+                              /*full=*/false));
+}
+
+MlirLogicalResult dialectRustApplyFullConversion(
+    MlirOperation op, MlirFrozenRewritePatternSet patterns,
+    const MlirStringRef *legalDialects, size_t legalCount,
+    const MlirStringRef *illegalDialects, size_t illegalCount,
+    DialectRustWriteCallback callback, void *data) {
+  return wrap(applyConversion(op, patterns, legalDialects, legalCount,
+                              illegalDialects, illegalCount, callback, data,
+                              /*full=*/true));
+}
+
+} // extern "C"
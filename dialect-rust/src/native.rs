@@ -0,0 +1,281 @@
+//! Native Rust functions callable from PDL patterns.
+//!
+//! Patterns can call native functions for the logic that PDL cannot express:
+//! constraints with `pdl.apply_native_constraint` in the matcher, and
+//! rewrites with `pdl.apply_native_rewrite` in the rewriter. The functions
+//! are registered by name on the pattern module before it is frozen, with
+//! `apply_pdl_patterns_with`.
+//!
+//! The MLIR 19 C API cannot register native functions, so they go through a
+//! small C++ shim (`native.cpp`), compiled by the build script.
+
+use std::{ffi::c_void, slice};
+
+use melior::{
+    StringRef,
+    ir::{
+        Attribute, Type, Value, ValueLike,
+        attribute::IntegerAttribute,
+        operation::{OperationLike, OperationRef},
+    },
+};
+use mlir_sys::{MlirAttribute, MlirOperation, MlirPDLPatternModule, MlirType, MlirValue};
+
+use crate::{
+    ffi::{self, PdlValueKind},
+    pdl::MODULUS,
+};
+
+/// An argument or a result of a native function. Ranges are not supported.
+#[derive(Clone, Copy)]
+pub enum PdlValue<'c, 'a> {
+    Attribute(Attribute<'c>),
+    Operation(OperationRef<'c, 'a>),
+    Type(Type<'c>),
+    Value(Value<'c, 'a>),
+}
+
+impl PdlValue<'_, '_> {
+    unsafe fn from_raw(value: ffi::PdlValue) -> Self {
+        unsafe {
+            match value.kind {
+                PdlValueKind::Attribute => {
+                    PdlValue::Attribute(Attribute::from_raw(MlirAttribute { ptr: value.ptr }))
+                }
+                PdlValueKind::Operation => {
+                    PdlValue::Operation(OperationRef::from_raw(MlirOperation {
+                        ptr: value.ptr.cast_mut(),
+                    }))
+                }
+                PdlValueKind::Type => PdlValue::Type(Type::from_raw(MlirType { ptr: value.ptr })),
+                PdlValueKind::Value => {
+                    PdlValue::Value(Value::from_raw(MlirValue { ptr: value.ptr }))
+                }
+            }
+        }
+    }
+
+    fn to_raw(self) -> ffi::PdlValue {
+        let (kind, ptr) = match self {
+            PdlValue::Attribute(attribute) => (PdlValueKind::Attribute, attribute.to_raw().ptr),
+            PdlValue::Operation(operation) => {
+                (PdlValueKind::Operation, operation.to_raw().ptr.cast_const())
+            }
+            PdlValue::Type(r#type) => (PdlValueKind::Type, r#type.to_raw().ptr),
+            PdlValue::Value(value) => (PdlValueKind::Value, value.to_raw().ptr),
+        };
+        ffi::PdlValue { kind, ptr }
+    }
+}
+
+type Constraint<'f> = Box<dyn for<'c, 'a> Fn(&[PdlValue<'c, 'a>]) -> bool + 'f>;
+type Rewrite<'f> =
+    Box<dyn for<'c, 'a> Fn(&[PdlValue<'c, 'a>]) -> Option<Vec<PdlValue<'c, 'a>>> + 'f>;
+
+/// A set of native functions, by name.
+#[derive(Default)]
+pub struct NativeFunctions<'f> {
+    constraints: Vec<(String, Constraint<'f>)>,
+    rewrites: Vec<(String, Rewrite<'f>)>,
+}
+
+impl<'f> NativeFunctions<'f> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a constraint. The match fails if it returns false, or if any of
+    /// its arguments is a range.
+    pub fn constraint(
+        mut self,
+        name: &str,
+        constraint: impl for<'c, 'a> Fn(&[PdlValue<'c, 'a>]) -> bool + 'f,
+    ) -> Self {
+        self.constraints
+            .push((name.to_string(), Box::new(constraint)));
+        self
+    }
+
+    /// Adds a rewrite, which returns the results of the
+    /// `pdl.apply_native_rewrite`. The rewrite fails if it returns `None`, or
+    /// if any of its arguments is a range, and the pattern isn't applied.
+    ///
+    /// The functions are called from C++, so they must not panic.
+    pub fn rewrite(
+        mut self,
+        name: &str,
+        rewrite: impl for<'c, 'a> Fn(&[PdlValue<'c, 'a>]) -> Option<Vec<PdlValue<'c, 'a>>> + 'f,
+    ) -> Self {
+        self.rewrites.push((name.to_string(), Box::new(rewrite)));
+        self
+    }
+
+    /// Registers every function on the pattern module.
+    ///
+    /// # Safety
+    ///
+    /// The functions must outlive every use of the patterns built from the
+    /// module.
+    pub(crate) unsafe fn register(&self, module: MlirPDLPatternModule) {
+        for (name, constraint) in &self.constraints {
+            unsafe {
+                ffi::dialectRustRegisterNativeConstraint(
+                    module,
+                    StringRef::new(name).to_raw(),
+                    call_constraint,
+                    constraint as *const Constraint as *const c_void,
+                )
+            };
+        }
+        for (name, rewrite) in &self.rewrites {
+            unsafe {
+                ffi::dialectRustRegisterNativeRewrite(
+                    module,
+                    StringRef::new(name).to_raw(),
+                    call_rewrite,
+                    rewrite as *const Rewrite as *const c_void,
+                )
+            };
+        }
+    }
+}
+
+unsafe extern "C" fn call_constraint(
+    data: *const c_void,
+    arguments: *const ffi::PdlValue,
+    count: usize,
+) -> bool {
+    let constraint = unsafe { &*(data as *const Constraint) };
+    constraint(&unsafe { arguments_from_raw(arguments, count) })
+}
+
+unsafe extern "C" fn call_rewrite(
+    data: *const c_void,
+    arguments: *const ffi::PdlValue,
+    count: usize,
+    results: *mut c_void,
+) -> bool {
+    let rewrite = unsafe { &*(data as *const Rewrite) };
+    let Some(values) = rewrite(&unsafe { arguments_from_raw(arguments, count) }) else {
+        return false;
+    };
+    for value in values {
+        unsafe { ffi::dialectRustPushNativeResult(results, value.to_raw()) };
+    }
+    true
+}
+
+unsafe fn arguments_from_raw<'c, 'a>(
+    arguments: *const ffi::PdlValue,
+    count: usize,
+) -> Vec<PdlValue<'c, 'a>> {
+    if count == 0 {
+        return Vec::new();
+    }
+    unsafe { slice::from_raw_parts(arguments, count) }
+        .iter()
+        .map(|argument| unsafe { PdlValue::from_raw(*argument) })
+        .collect()
+}
+
+/// The native functions used by `pdl::load_native_pattern_module`:
+///
+/// - `is_power_of_two`: whether an integer attribute is a power of two.
+/// - `compute_inverse_constant`: the inverse of an integer attribute modulo
+///   `MODULUS`, or 0 for 0.
+/// - `compute_log2_constant`: the base 2 logarithm of an integer attribute.
+///   It fails if the attribute isn't a power of two.
+///
+/// The rewrites fail if their argument isn't a single integer attribute.
+pub fn felt_functions() -> NativeFunctions<'static> {
+    NativeFunctions::new()
+        .constraint("is_power_of_two", |arguments| {
+            integer_argument(arguments).is_some_and(|(_, value)| value.is_power_of_two())
+        })
+        .rewrite("compute_inverse_constant", |arguments| {
+            let (r#type, value) = integer_argument(arguments)?;
+            // Fermat's little theorem, like the lowering of `felt.div`.
+            let modulus = u64::from(MODULUS);
+            let mut inverse = 1;
+            for _ in 0..modulus - 2 {
+                inverse = inverse * (value % modulus) % modulus;
+            }
+            Some(vec![PdlValue::Attribute(
+                IntegerAttribute::new(r#type, inverse as i64).into(),
+            )])
+        })
+        .rewrite("compute_log2_constant", |arguments| {
+            let (r#type, value) = integer_argument(arguments)?;
+            value.is_power_of_two().then(|| {
+                vec![PdlValue::Attribute(
+                    IntegerAttribute::new(r#type, value.ilog2().into()).into(),
+                )]
+            })
+        })
+}
+
+/// Returns the type and the unsigned value of a single integer attribute
+/// argument.
+fn integer_argument<'c>(arguments: &[PdlValue<'c, '_>]) -> Option<(Type<'c>, u64)> {
+    let [PdlValue::Attribute(attribute)] = arguments else {
+        return None;
+    };
+    let attribute = IntegerAttribute::try_from(*attribute).ok()?;
+    Some((attribute.r#type(), attribute.value() as u64))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        apply_pdl_patterns_with, convert_pdl_to_pdl_interop, execute_entrypoint,
+        native::felt_functions,
+        parse_module,
+        pdl::{build_pattern_module, load_native_pattern_module},
+        pipeline::PipelineOptions,
+        testing::{felt_context, lower},
+    };
+
+    #[test]
+    fn native_patterns() {
+        let context = felt_context();
+        let options = PipelineOptions::default();
+
+        let mut module = parse_module(
+            &context,
+            r#"
+            module {
+              func.func @entrypoint(%a: i32, %b: i32) -> i32 attributes { llvm.emit_c_interface } {
+                %0 = arith.constant 3 : i32
+                %1 = arith.constant 4 : i32
+                %2 = "felt.div"(%a, %0) : (i32, i32) -> i32
+                %3 = "felt.mul"(%2, %1) : (i32, i32) -> i32
+                %4 = "felt.add"(%3, %b) : (i32, i32) -> i32
+                return %4 : i32
+              }
+            }"#,
+            "native_patterns",
+        )
+        .unwrap();
+
+        let mut native_pattern_module = load_native_pattern_module(&context);
+        convert_pdl_to_pdl_interop(&context, &mut native_pattern_module, &options);
+        apply_pdl_patterns_with(&module, &native_pattern_module, &felt_functions());
+
+        // The division by 3 is a multiplication by its inverse, 9, and the
+        // multiplication by 4 is a shift.
+        let source = module.as_operation().to_string();
+        assert!(!source.contains("felt.div"), "{source}");
+        assert!(source.contains("arith.constant 9 : i32"), "{source}");
+        assert!(source.contains("arith.shli"), "{source}");
+
+        lower(
+            &context,
+            &mut module,
+            build_pattern_module(&context),
+            &options,
+        );
+
+        // 6 / 3 * 4 + 1 = 9.
+        assert_eq!(execute_entrypoint(&module, 6, 1), 9);
+    }
+}
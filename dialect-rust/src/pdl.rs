@@ -3,9 +3,12 @@ use melior::{
     dialect::ods::pdl,
     helpers::BuiltinBlockExt,
     ir::{
-        Block, BlockLike, Identifier, Location, Module, Region, Type,
-        attribute::{ArrayAttribute, DenseI32ArrayAttribute, IntegerAttribute, StringAttribute},
-        operation::OperationBuilder,
+        Attribute, Block, BlockLike, Identifier, Module, Region, Type, Value,
+        attribute::{
+            ArrayAttribute, DenseI32ArrayAttribute, IntegerAttribute, StringAttribute,
+            TypeAttribute,
+        },
+        operation::{Operation, OperationBuilder},
         r#type::IntegerType,
     },
 };
@@ -13,8 +16,33 @@ use mlir_sys::{
     mlirPDLAttributeTypeGet, mlirPDLOperationTypeGet, mlirPDLTypeTypeGet, mlirPDLValueTypeGet,
 };
 
+use crate::{
+    caller_location,
+    irdl::OPERATIONS,
+    montgomery::{MODULUS_INVERSE, RADIX_BITS},
+    parse_module,
+};
+
+/// The modulus of the field implemented by the lowering patterns.
+pub const MODULUS: u32 = 13;
+
+/// How the lowering patterns reduce results modulo `MODULUS`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Reduction {
+    /// With `arith.remui`, on the canonical representatives.
+    #[default]
+    Remainder,
+    /// With Montgomery reduction, on representatives in Montgomery form. The
+    /// module must first be converted with `montgomery::convert_boundaries`.
+    Montgomery,
+    /// With Barrett reduction for products, and a conditional subtraction for
+    /// sums and differences, on the canonical representatives. No division
+    /// is left.
+    Barrett,
+}
+
 pub fn load_pattern_module(ctx: &'_ Context) -> Module<'_> {
-    Module::parse(
+    parse_module(
         ctx,
         r#"
         module {
@@ -33,7 +61,176 @@ pub fn load_pattern_module(ctx: &'_ Context) -> Module<'_> {
               replace %3 with %9
             }
           }
+          pdl.pattern : benefit(1) {
+            %0 = type
+            %1 = operand
+            %2 = operand
+            %3 = operation "felt.sub"(%1, %2 : !pdl.value, !pdl.value)  -> (%0 : !pdl.type)
+            rewrite %3 {
+              %4 = attribute = 13 : i32
+              %5 = operation "arith.constant"  {"value" = %4} -> (%0 : !pdl.type)
+              %6 = result 0 of %5
+              %7 = operation "arith.addi"(%1, %6 : !pdl.value, !pdl.value)  -> (%0 : !pdl.type)
+              %8 = result 0 of %7
+              %9 = operation "arith.subi"(%8, %2 : !pdl.value, !pdl.value)  -> (%0 : !pdl.type)
+              %10 = result 0 of %9
+              %11 = operation "arith.remui"(%10, %6 : !pdl.value, !pdl.value)  -> (%0 : !pdl.type)
+              replace %3 with %11
+            }
+          }
+          pdl.pattern : benefit(1) {
+            %0 = type
+            %1 = operand
+            %2 = operand
+            %3 = operation "felt.mul"(%1, %2 : !pdl.value, !pdl.value)  -> (%0 : !pdl.type)
+            rewrite %3 {
+              %4 = attribute = 13 : i32
+              %5 = operation "arith.constant"  {"value" = %4} -> (%0 : !pdl.type)
+              %6 = result 0 of %5
+              %7 = operation "arith.muli"(%1, %2 : !pdl.value, !pdl.value)  -> (%0 : !pdl.type)
+              %8 = result 0 of %7
+              %9 = operation "arith.remui"(%8, %6 : !pdl.value, !pdl.value)  -> (%0 : !pdl.type)
+              replace %3 with %9
+            }
+          }
+          pdl.pattern : benefit(1) {
+            %0 = type
+            %1 = operand
+            %2 = operand
+            %3 = operation "felt.div"(%1, %2 : !pdl.value, !pdl.value)  -> (%0 : !pdl.type)
+            rewrite %3 {
+              %4 = attribute = 13 : i32
+              %5 = operation "arith.constant"  {"value" = %4} -> (%0 : !pdl.type)
+              %6 = result 0 of %5
+              %7 = operation "arith.muli"(%2, %2 : !pdl.value, !pdl.value)  -> (%0 : !pdl.type)
+              %8 = result 0 of %7
+              %9 = operation "arith.remui"(%8, %6 : !pdl.value, !pdl.value)  -> (%0 : !pdl.type)
+              %10 = result 0 of %9
+              %11 = operation "arith.muli"(%10, %10 : !pdl.value, !pdl.value)  -> (%0 : !pdl.type)
+              %12 = result 0 of %11
+              %13 = operation "arith.remui"(%12, %6 : !pdl.value, !pdl.value)  -> (%0 : !pdl.type)
+              %14 = result 0 of %13
+              %15 = operation "arith.muli"(%14, %2 : !pdl.value, !pdl.value)  -> (%0 : !pdl.type)
+              %16 = result 0 of %15
+              %17 = operation "arith.remui"(%16, %6 : !pdl.value, !pdl.value)  -> (%0 : !pdl.type)
+              %18 = result 0 of %17
+              %19 = operation "arith.muli"(%18, %18 : !pdl.value, !pdl.value)  -> (%0 : !pdl.type)
+              %20 = result 0 of %19
+              %21 = operation "arith.remui"(%20, %6 : !pdl.value, !pdl.value)  -> (%0 : !pdl.type)
+              %22 = result 0 of %21
+              %23 = operation "arith.muli"(%22, %2 : !pdl.value, !pdl.value)  -> (%0 : !pdl.type)
+              %24 = result 0 of %23
+              %25 = operation "arith.remui"(%24, %6 : !pdl.value, !pdl.value)  -> (%0 : !pdl.type)
+              %26 = result 0 of %25
+              %27 = operation "arith.muli"(%1, %26 : !pdl.value, !pdl.value)  -> (%0 : !pdl.type)
+              %28 = result 0 of %27
+              %29 = operation "arith.remui"(%28, %6 : !pdl.value, !pdl.value)  -> (%0 : !pdl.type)
+              replace %3 with %29
+            }
+          }
+          pdl.pattern : benefit(1) {
+            %0 = type
+            %1 = operand
+            %2 = operation "felt.neg"(%1 : !pdl.value)  -> (%0 : !pdl.type)
+            rewrite %2 {
+              %3 = attribute = 13 : i32
+              %4 = operation "arith.constant"  {"value" = %3} -> (%0 : !pdl.type)
+              %5 = result 0 of %4
+              %6 = operation "arith.subi"(%5, %1 : !pdl.value, !pdl.value)  -> (%0 : !pdl.type)
+              %7 = result 0 of %6
+              %8 = operation "arith.remui"(%7, %5 : !pdl.value, !pdl.value)  -> (%0 : !pdl.type)
+              replace %2 with %8
+            }
+          }
+          pdl.pattern : benefit(1) {
+            %0 = type : i32
+            %1 = operand
+            %2 = operand
+            %3 = operands
+            %4 = operation "felt.sum"(%1, %2, %3 : !pdl.value, !pdl.value, !pdl.range<value>)  -> (%0 : !pdl.type)
+            rewrite %4 {
+              %5 = operation "felt.add"(%1, %2 : !pdl.value, !pdl.value)  -> (%0 : !pdl.type)
+              %6 = result 0 of %5
+              %7 = operation "felt.sum"(%6, %3 : !pdl.value, !pdl.range<value>)  -> (%0 : !pdl.type)
+              replace %4 with %7
+            }
+          }
+          pdl.pattern : benefit(1) {
+            %0 = type : i32
+            %1 = operand
+            %2 = operation "felt.sum"(%1 : !pdl.value)  -> (%0 : !pdl.type)
+            rewrite %2 {
+              replace %2 with(%1 : !pdl.value)
+            }
+          }
+          pdl.pattern : benefit(1) {
+            %0 = type : i32
+            %1 = operation "felt.sum"  -> (%0 : !pdl.type)
+            rewrite %1 {
+              %2 = attribute = 0 : i32
+              %3 = operation "arith.constant"  {"value" = %2} -> (%0 : !pdl.type)
+              %4 = result 0 of %3
+              replace %1 with(%4 : !pdl.value)
+            }
+          }
+        }"#,
+        "load_pattern_module",
+    )
+    .unwrap()
+}
+
+/// Loads patterns that call the native functions of `native::felt_functions`,
+/// so they must be applied with `apply_pdl_patterns_with`:
+///
+/// - A division by a constant is a multiplication by the inverse of the
+///   constant.
+/// - A multiplication by a constant power of two is a shift. Like the
+///   product in the `felt.mul` lowering, the shifted value fits in 32 bits.
+///
+/// The resulting felt operations are lowered by the other patterns.
+pub fn load_native_pattern_module(ctx: &'_ Context) -> Module<'_> {
+    parse_module(
+        ctx,
+        r#"
+        module {
+          pdl.pattern @div_by_constant : benefit(2) {
+            %0 = type
+            %1 = operand
+            %2 = attribute
+            %3 = operation "arith.constant"  {"value" = %2} -> (%0 : !pdl.type)
+            %4 = result 0 of %3
+            %5 = operation "felt.div"(%1, %4 : !pdl.value, !pdl.value)  -> (%0 : !pdl.type)
+            rewrite %5 {
+              %6 = apply_native_rewrite "compute_inverse_constant"(%2 : !pdl.attribute) : !pdl.attribute
+              %7 = operation "arith.constant"  {"value" = %6} -> (%0 : !pdl.type)
+              %8 = result 0 of %7
+              %9 = operation "felt.mul"(%1, %8 : !pdl.value, !pdl.value)  -> (%0 : !pdl.type)
+              replace %5 with %9
+            }
+          }
+          pdl.pattern @mul_by_power_of_two : benefit(2) {
+            %0 = type
+            %1 = operand
+            %2 = attribute
+            apply_native_constraint "is_power_of_two"(%2 : !pdl.attribute)
+            %3 = operation "arith.constant"  {"value" = %2} -> (%0 : !pdl.type)
+            %4 = result 0 of %3
+            %5 = operation "felt.mul"(%1, %4 : !pdl.value, !pdl.value)  -> (%0 : !pdl.type)
+            rewrite %5 {
+              %6 = apply_native_rewrite "compute_log2_constant"(%2 : !pdl.attribute) : !pdl.attribute
+              %7 = operation "arith.constant"  {"value" = %6} -> (%0 : !pdl.type)
+              %8 = result 0 of %7
+              %9 = operation "arith.shli"(%1, %8 : !pdl.value, !pdl.value)  -> (%0 : !pdl.type)
+              %10 = result 0 of %9
+              %11 = attribute = 13 : i32
+              %12 = operation "arith.constant"  {"value" = %11} -> (%0 : !pdl.type)
+              %13 = result 0 of %12
+              %14 = operation "arith.remui"(%10, %13 : !pdl.value, !pdl.value)  -> (%0 : !pdl.type)
+              replace %5 with %14
+            }
+          }
         }"#,
+        "load_native_pattern_module",
     )
     .unwrap()
 }
@@ -42,8 +239,19 @@ pub fn load_pattern_module(ctx: &'_ Context) -> Module<'_> {
 ///
 /// The built module should be equal to the one in `load_pattern_module`.
 pub fn build_pattern_module(ctx: &'_ Context) -> Module<'_> {
-    let location = Location::unknown(ctx);
-    let module = Module::new(location);
+    build_reduction_pattern_module(ctx, Reduction::Remainder)
+}
+
+/// Builds the pattern module, reducing the results with the given
+/// reduction.
+pub fn build_reduction_pattern_module(ctx: &'_ Context, reduction: Reduction) -> Module<'_> {
+    // Barrett reduction also changes how sums are reduced, so the `felt.add`
+    // pattern below doesn't apply to it.
+    if reduction == Reduction::Barrett {
+        return build_barrett_pattern_module(ctx);
+    }
+
+    let module = Module::new(caller_location(ctx));
 
     // PDL is a meta-dialect (a dialect that reason about MLIR itself). Here,
     // everything is a value. Types are values, values are values, attributes
@@ -66,7 +274,7 @@ pub fn build_pattern_module(ctx: &'_ Context) -> Module<'_> {
     // pattern. See https://mlir.llvm.org/docs/PatternRewriter/#introduction for
     // more information.
     module.body().append_operation(
-        melior::dialect::ods::pdl::PatternOperation::builder(ctx, location)
+        melior::dialect::ods::pdl::PatternOperation::builder(ctx, caller_location(ctx))
             .benefit(IntegerAttribute::new(IntegerType::new(ctx, 16).into(), 1))
             .body_region({
                 let region = Region::new();
@@ -80,13 +288,17 @@ pub fn build_pattern_module(ctx: &'_ Context) -> Module<'_> {
                 // %2 = operand
 
                 let result = block
-                    .append_op_result(pdl::r#type(ctx, pdl_type_type, location).into())
+                    .append_op_result(pdl::r#type(ctx, pdl_type_type, caller_location(ctx)).into())
                     .unwrap();
                 let operand1 = block
-                    .append_op_result(pdl::operand(ctx, pdl_value_type, location).into())
+                    .append_op_result(
+                        pdl::operand(ctx, pdl_value_type, caller_location(ctx)).into(),
+                    )
                     .unwrap();
                 let operand2 = block
-                    .append_op_result(pdl::operand(ctx, pdl_value_type, location).into())
+                    .append_op_result(
+                        pdl::operand(ctx, pdl_value_type, caller_location(ctx)).into(),
+                    )
                     .unwrap();
 
                 // By itself, operands and types don't mean anything. With the
@@ -105,7 +317,7 @@ pub fn build_pattern_module(ctx: &'_ Context) -> Module<'_> {
                 // single return type.
                 let operation = block
                     .append_op_result(
-                        OperationBuilder::new("pdl.operation", location)
+                        OperationBuilder::new("pdl.operation", caller_location(ctx))
                             .add_operands(&[operand1, operand2, result])
                             .add_attributes(&[
                                 (
@@ -156,7 +368,7 @@ pub fn build_pattern_module(ctx: &'_ Context) -> Module<'_> {
                 // `root` operand, and the `externalArgs` operand. I do not yet
                 // know what the `externalArgs` operand means.
                 block.append_operation(
-                    OperationBuilder::new("pdl.rewrite", location)
+                    OperationBuilder::new("pdl.rewrite", caller_location(ctx))
                         .add_operands(&[operation])
                         .add_attributes(&[(
                             Identifier::new(ctx, "operandSegmentSizes"),
@@ -173,7 +385,7 @@ pub fn build_pattern_module(ctx: &'_ Context) -> Module<'_> {
                             // %4 = operation "arith.addi"(%1, %2)  -> (%0)
                             let add_operation = block
                                 .append_op_result(
-                                    OperationBuilder::new("pdl.operation", location)
+                                    OperationBuilder::new("pdl.operation", caller_location(ctx))
                                         .add_operands(&[operand1, operand2, result])
                                         .add_attributes(&[
                                             (
@@ -201,7 +413,7 @@ pub fn build_pattern_module(ctx: &'_ Context) -> Module<'_> {
                             // %5 = result 0 of %4
                             let add_result = block
                                 .append_op_result(
-                                    OperationBuilder::new("pdl.result", location)
+                                    OperationBuilder::new("pdl.result", caller_location(ctx))
                                         .add_operands(&[add_operation])
                                         .add_attributes(&[(
                                             Identifier::new(ctx, "index"),
@@ -229,10 +441,10 @@ pub fn build_pattern_module(ctx: &'_ Context) -> Module<'_> {
                             // %6 = attribute = 13 : i32
                             let k13_attribute = block
                                 .append_op_result(
-                                    OperationBuilder::new("pdl.attribute", location)
+                                    OperationBuilder::new("pdl.attribute", caller_location(ctx))
                                         .add_attributes(&[(
                                             Identifier::new(ctx, "value"),
-                                            IntegerAttribute::new(u32_type, 13).into(),
+                                            IntegerAttribute::new(u32_type, MODULUS.into()).into(),
                                         )])
                                         .add_results(&[pdl_attribute_type])
                                         .build()
@@ -245,7 +457,7 @@ pub fn build_pattern_module(ctx: &'_ Context) -> Module<'_> {
                             // %7 = operation "arith.constant"  {"value" = %6} -> (%0 : !pdl.type)
                             let k13_operation = block
                                 .append_op_result(
-                                    OperationBuilder::new("pdl.operation", location)
+                                    OperationBuilder::new("pdl.operation", caller_location(ctx))
                                         .add_operands(&[k13_attribute, result])
                                         .add_attributes(&[
                                             (
@@ -276,7 +488,7 @@ pub fn build_pattern_module(ctx: &'_ Context) -> Module<'_> {
                             // %8 = result 0 of %7
                             let k13_result = block
                                 .append_op_result(
-                                    OperationBuilder::new("pdl.result", location)
+                                    OperationBuilder::new("pdl.result", caller_location(ctx))
                                         .add_operands(&[k13_operation])
                                         .add_attributes(&[(
                                             Identifier::new(ctx, "index"),
@@ -295,7 +507,7 @@ pub fn build_pattern_module(ctx: &'_ Context) -> Module<'_> {
                             // %9 = operation "arith.remui"(%5, %8)  -> (%0)
                             let modulo_operation = block
                                 .append_op_result(
-                                    OperationBuilder::new("pdl.operation", location)
+                                    OperationBuilder::new("pdl.operation", caller_location(ctx))
                                         .add_operands(&[add_result, k13_result, result])
                                         .add_attributes(&[
                                             (
@@ -323,7 +535,7 @@ pub fn build_pattern_module(ctx: &'_ Context) -> Module<'_> {
                             //
                             // replace %3 with %9
                             block.append_operation(
-                                OperationBuilder::new("pdl.replace", location)
+                                OperationBuilder::new("pdl.replace", caller_location(ctx))
                                     .add_operands(&[operation, modulo_operation])
                                     .add_attributes(&[(
                                         Identifier::new(ctx, "operandSegmentSizes"),
@@ -345,14 +557,808 @@ pub fn build_pattern_module(ctx: &'_ Context) -> Module<'_> {
             .into(),
     );
 
+    // The remaining operations are lowered in the same way, so we build
+    // their patterns with the helpers below.
+    for (name, operand_count) in OPERATIONS {
+        if name != "add" {
+            module.body().append_operation(build_lowering_pattern(
+                ctx,
+                name,
+                operand_count,
+                reduction,
+            ));
+        }
+    }
+
+    // `felt.sum` is rewritten into `felt.add`s, which are then lowered by the
+    // patterns above, so it doesn't depend on the reduction.
+    for pattern in build_sum_patterns(ctx) {
+        module.body().append_operation(pattern);
+    }
+
+    module
+}
+
+/// Builds the pattern module with `Reduction::Barrett`, where every
+/// operation, including `felt.add`, is lowered by `build_lowering_pattern`.
+fn build_barrett_pattern_module(ctx: &'_ Context) -> Module<'_> {
+    let module = Module::new(caller_location(ctx));
+
+    for (name, operand_count) in OPERATIONS {
+        module.body().append_operation(build_lowering_pattern(
+            ctx,
+            name,
+            operand_count,
+            Reduction::Barrett,
+        ));
+    }
+    for pattern in build_sum_patterns(ctx) {
+        module.body().append_operation(pattern);
+    }
+
     module
 }
 
+/// Builds the patterns that rewrite `felt.sum`, whose operands are variadic:
+///
+/// - With two operands or more, the first two are added with a `felt.add`,
+///   and the sum continues with the remaining ones. These are matched as a
+///   `pdl.operands` range, which holds the operands after the first two.
+/// - With a single operand, the sum is that operand.
+/// - With no operands, the sum is 0.
+pub fn build_sum_patterns(ctx: &'_ Context) -> [Operation<'_>; 3] {
+    [
+        build_sum_pattern(ctx, 2),
+        build_sum_pattern(ctx, 1),
+        build_sum_pattern(ctx, 0),
+    ]
+}
+
+/// Builds the `felt.sum` pattern for exactly zero or one operand, or for
+/// two operands or more. Only sums of u32 values are matched, since sums of
+/// `!felt.felt<p>` values have no lowering.
+fn build_sum_pattern(ctx: &'_ Context, operand_count: usize) -> Operation<'_> {
+    pdl::PatternOperation::builder(ctx, caller_location(ctx))
+        .benefit(IntegerAttribute::new(IntegerType::new(ctx, 16).into(), 1))
+        .body_region({
+            let region = Region::new();
+            let block = region.append_block(Block::new(&[]));
+
+            let result = append_type(ctx, &block, IntegerType::new(ctx, 32).into());
+            let mut operands = (0..operand_count)
+                .map(|_| {
+                    block
+                        .append_op_result(
+                            pdl::operand(ctx, pdl_value_type(ctx), caller_location(ctx)).into(),
+                        )
+                        .unwrap()
+                })
+                .collect::<Vec<_>>();
+            if operand_count == 2 {
+                operands.push(
+                    block
+                        .append_op_result(
+                            OperationBuilder::new("pdl.operands", caller_location(ctx))
+                                .add_results(&[Type::parse(ctx, "!pdl.range<value>").unwrap()])
+                                .build()
+                                .unwrap(),
+                        )
+                        .unwrap(),
+                );
+            }
+            let operation = append_operation(ctx, &block, "felt.sum", &operands, &[], result);
+
+            block.append_operation(
+                OperationBuilder::new("pdl.rewrite", caller_location(ctx))
+                    .add_operands(&[operation])
+                    .add_attributes(&[(
+                        Identifier::new(ctx, "operandSegmentSizes"),
+                        DenseI32ArrayAttribute::new(ctx, &[1, 0]).into(),
+                    )])
+                    .add_regions([{
+                        let region = Region::new();
+                        let block = region.append_block(Block::new(&[]));
+
+                        // The root is replaced either by an operation, or
+                        // by values, which are separate operand segments.
+                        let (replacement, segment_sizes) = match operands[..] {
+                            [lhs, rhs, rest] => {
+                                let sum = append_result(
+                                    ctx,
+                                    &block,
+                                    append_operation(
+                                        ctx,
+                                        &block,
+                                        "felt.add",
+                                        &[lhs, rhs],
+                                        &[],
+                                        result,
+                                    ),
+                                );
+                                let operation = append_operation(
+                                    ctx,
+                                    &block,
+                                    "felt.sum",
+                                    &[sum, rest],
+                                    &[],
+                                    result,
+                                );
+                                (operation, [1, 1, 0])
+                            }
+                            [operand] => (operand, [1, 0, 1]),
+                            [] => (
+                                append_integer_constant(ctx, &block, 0, 32, result),
+                                [1, 0, 1],
+                            ),
+                            _ => unreachable!(),
+                        };
+
+                        block.append_operation(
+                            OperationBuilder::new("pdl.replace", caller_location(ctx))
+                                .add_operands(&[operation, replacement])
+                                .add_attributes(&[(
+                                    Identifier::new(ctx, "operandSegmentSizes"),
+                                    DenseI32ArrayAttribute::new(ctx, &segment_sizes).into(),
+                                )])
+                                .build()
+                                .unwrap(),
+                        );
+
+                        region
+                    }])
+                    .build()
+                    .unwrap(),
+            );
+
+            region
+        })
+        .build()
+        .into()
+}
+
+/// Builds the pattern that lowers the felt operation with the given name.
+///
+/// Every operation is computed with the arith dialect, and then reduced
+/// modulo `MODULUS`, assuming that the operands are already reduced:
+///
+/// - `sub` adds the modulus before subtracting, so that it never underflows.
+/// - `neg` subtracts the operand from the modulus.
+/// - `div` multiplies by the inverse of the divisor, computed with Fermat's
+///   little theorem as `b^(p - 2)`. Dividing by zero results in zero.
+///
+/// With `Reduction::Montgomery`, `mul` and `div` use Montgomery products
+/// instead. With `Reduction::Barrett`, the results of `add`, `sub` and `neg`
+/// are in `0..2p`, so they are reduced with a conditional subtraction, and
+/// products use Barrett reduction.
+pub fn build_lowering_pattern<'c>(
+    ctx: &'c Context,
+    name: &str,
+    operand_count: usize,
+    reduction: Reduction,
+) -> Operation<'c> {
+    build_pattern(ctx, name, operand_count, reduction, Operands::Any)
+}
+
+/// Builds a pattern that lowers the felt operation with the given name when
+/// all of its operands are `arith.constant`s. Once canonicalized, the lowered
+/// operations fold into a single constant. See the `fold` module.
+pub fn build_constant_folding_pattern<'c>(
+    ctx: &'c Context,
+    name: &str,
+    operand_count: usize,
+) -> Operation<'c> {
+    build_pattern(
+        ctx,
+        name,
+        operand_count,
+        Reduction::Remainder,
+        Operands::Constant,
+    )
+}
+
+/// Builds a pattern that lowers `felt.add` when its second operand is an
+/// `arith.constant`. The constant is reduced first, with a remainder that
+/// folds away once canonicalized, so the sum is reduced with a conditional
+/// subtraction instead of a remainder.
+pub fn build_constant_addition_pattern(ctx: &'_ Context) -> Operation<'_> {
+    build_pattern(ctx, "add", 2, Reduction::Barrett, Operands::LastConstant)
+}
+
+/// What the operands of a lowering pattern match.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Operands {
+    /// Any value.
+    Any,
+    /// Only results of `arith.constant`s.
+    Constant,
+    /// Any value, except for the last operand, which is the result of an
+    /// `arith.constant`.
+    LastConstant,
+}
+
+fn build_pattern<'c>(
+    ctx: &'c Context,
+    name: &str,
+    operand_count: usize,
+    reduction: Reduction,
+    operands: Operands,
+) -> Operation<'c> {
+    pdl::PatternOperation::builder(ctx, caller_location(ctx))
+        .benefit(IntegerAttribute::new(IntegerType::new(ctx, 16).into(), 1))
+        .body_region({
+            let region = Region::new();
+            let block = region.append_block(Block::new(&[]));
+
+            let result = block
+                .append_op_result(pdl::r#type(ctx, pdl_type_type(ctx), caller_location(ctx)).into())
+                .unwrap();
+            let reduces_constant = operands == Operands::LastConstant;
+            let operands = (0..operand_count)
+                .map(|index| match operands {
+                    Operands::LastConstant if index + 1 < operand_count => block
+                        .append_op_result(
+                            pdl::operand(ctx, pdl_value_type(ctx), caller_location(ctx)).into(),
+                        )
+                        .unwrap(),
+                    Operands::Any => block
+                        .append_op_result(
+                            pdl::operand(ctx, pdl_value_type(ctx), caller_location(ctx)).into(),
+                        )
+                        .unwrap(),
+                    Operands::Constant | Operands::LastConstant => append_result(
+                        ctx,
+                        &block,
+                        append_operation(ctx, &block, "arith.constant", &[], &[], result),
+                    ),
+                })
+                .collect::<Vec<_>>();
+            let operation =
+                append_operation(ctx, &block, &format!("felt.{name}"), &operands, &[], result);
+
+            block.append_operation(
+                OperationBuilder::new("pdl.rewrite", caller_location(ctx))
+                    .add_operands(&[operation])
+                    .add_attributes(&[(
+                        Identifier::new(ctx, "operandSegmentSizes"),
+                        DenseI32ArrayAttribute::new(ctx, &[1, 0]).into(),
+                    )])
+                    .add_regions([{
+                        let region = Region::new();
+                        let block = region.append_block(Block::new(&[]));
+
+                        let modulus_attribute = block
+                            .append_op_result(
+                                OperationBuilder::new("pdl.attribute", caller_location(ctx))
+                                    .add_attributes(&[(
+                                        Identifier::new(ctx, "value"),
+                                        IntegerAttribute::new(
+                                            IntegerType::new(ctx, 32).into(),
+                                            MODULUS.into(),
+                                        )
+                                        .into(),
+                                    )])
+                                    .add_results(&[pdl_attribute_type(ctx)])
+                                    .build()
+                                    .unwrap(),
+                            )
+                            .unwrap();
+                        let modulus = append_result(
+                            ctx,
+                            &block,
+                            append_operation(
+                                ctx,
+                                &block,
+                                "arith.constant",
+                                &[],
+                                &[("value", modulus_attribute)],
+                                result,
+                            ),
+                        );
+
+                        // A constant operand may be any integer, so it is
+                        // reduced before being used.
+                        let mut operands = operands.clone();
+                        if reduces_constant {
+                            let last = operands.len() - 1;
+                            operands[last] = append_result(
+                                ctx,
+                                &block,
+                                append_operation(
+                                    ctx,
+                                    &block,
+                                    "arith.remui",
+                                    &[operands[last], modulus],
+                                    &[],
+                                    result,
+                                ),
+                            );
+                        }
+
+                        // Appends the reduction of a value in `0..2p`, and
+                        // returns the last operation.
+                        let reduce = |value| match reduction {
+                            Reduction::Barrett => {
+                                append_conditional_subtraction(ctx, &block, value, modulus, result)
+                            }
+                            Reduction::Remainder | Reduction::Montgomery => append_operation(
+                                ctx,
+                                &block,
+                                "arith.remui",
+                                &[value, modulus],
+                                &[],
+                                result,
+                            ),
+                        };
+
+                        // Appends `(lhs * rhs) % p`, and returns the remainder
+                        // operation.
+                        let multiply = |lhs, rhs| match reduction {
+                            Reduction::Montgomery => {
+                                append_montgomery_product(ctx, &block, lhs, rhs, modulus, result)
+                            }
+                            Reduction::Barrett => {
+                                append_barrett_product(ctx, &block, lhs, rhs, MODULUS, result)
+                            }
+                            Reduction::Remainder => {
+                                let product = append_result(
+                                    ctx,
+                                    &block,
+                                    append_operation(
+                                        ctx,
+                                        &block,
+                                        "arith.muli",
+                                        &[lhs, rhs],
+                                        &[],
+                                        result,
+                                    ),
+                                );
+                                append_operation(
+                                    ctx,
+                                    &block,
+                                    "arith.remui",
+                                    &[product, modulus],
+                                    &[],
+                                    result,
+                                )
+                            }
+                        };
+
+                        let replacement = match name {
+                            "add" => {
+                                let sum = append_result(
+                                    ctx,
+                                    &block,
+                                    append_operation(
+                                        ctx,
+                                        &block,
+                                        "arith.addi",
+                                        &[operands[0], operands[1]],
+                                        &[],
+                                        result,
+                                    ),
+                                );
+                                reduce(sum)
+                            }
+                            "sub" => {
+                                let sum = append_result(
+                                    ctx,
+                                    &block,
+                                    append_operation(
+                                        ctx,
+                                        &block,
+                                        "arith.addi",
+                                        &[operands[0], modulus],
+                                        &[],
+                                        result,
+                                    ),
+                                );
+                                let difference = append_result(
+                                    ctx,
+                                    &block,
+                                    append_operation(
+                                        ctx,
+                                        &block,
+                                        "arith.subi",
+                                        &[sum, operands[1]],
+                                        &[],
+                                        result,
+                                    ),
+                                );
+                                reduce(difference)
+                            }
+                            "mul" => multiply(operands[0], operands[1]),
+                            "div" => {
+                                // Square and multiply, from the most
+                                // significant bit of the exponent.
+                                let exponent = MODULUS - 2;
+                                let mut power = operands[1];
+                                for bit in (0..exponent.ilog2()).rev() {
+                                    power = append_result(ctx, &block, multiply(power, power));
+                                    if (exponent >> bit) & 1 == 1 {
+                                        power = append_result(
+                                            ctx,
+                                            &block,
+                                            multiply(power, operands[1]),
+                                        );
+                                    }
+                                }
+                                multiply(operands[0], power)
+                            }
+                            "neg" => {
+                                let difference = append_result(
+                                    ctx,
+                                    &block,
+                                    append_operation(
+                                        ctx,
+                                        &block,
+                                        "arith.subi",
+                                        &[modulus, operands[0]],
+                                        &[],
+                                        result,
+                                    ),
+                                );
+                                reduce(difference)
+                            }
+                            _ => unreachable!("no lowering for felt.{name}"),
+                        };
+
+                        block.append_operation(
+                            OperationBuilder::new("pdl.replace", caller_location(ctx))
+                                .add_operands(&[operation, replacement])
+                                .add_attributes(&[(
+                                    Identifier::new(ctx, "operandSegmentSizes"),
+                                    DenseI32ArrayAttribute::new(ctx, &[1, 1, 0]).into(),
+                                )])
+                                .build()
+                                .unwrap(),
+                        );
+
+                        region
+                    }])
+                    .build()
+                    .unwrap(),
+            );
+
+            region
+        })
+        .build()
+        .into()
+}
+
+/// Appends the Montgomery product `lhs * rhs / R mod p` of two felts in
+/// Montgomery form, and returns the last operation. See the `montgomery`
+/// module.
+///
+/// The product is reduced on 64 bits:
+///
+/// ```text
+/// t = lhs * rhs
+/// m = (t mod R) * (-p^-1) mod R
+/// u = (t + m * p) / R
+/// ```
+///
+/// `t + m * p` is divisible by `R`, and `u < 2p`, so a conditional
+/// subtraction finishes the reduction.
+fn append_montgomery_product<'c, 'a>(
+    ctx: &'c Context,
+    block: &'a Block<'c>,
+    lhs: Value<'c, 'a>,
+    rhs: Value<'c, 'a>,
+    modulus: Value<'c, 'a>,
+    felt_type: Value<'c, 'a>,
+) -> Value<'c, 'a> {
+    let wide_type = append_type(ctx, block, IntegerType::new(ctx, 64).into());
+    let operation = |name, operands: &[Value<'c, 'a>], result_type| {
+        append_result(
+            ctx,
+            block,
+            append_operation(ctx, block, name, operands, &[], result_type),
+        )
+    };
+
+    let product = append_wide_product(ctx, block, lhs, rhs, wide_type);
+    let low = operation("arith.trunci", &[product], felt_type);
+    // The inverse is sign extended, so that it fits in a 32-bit attribute.
+    let inverse = append_integer_constant(ctx, block, MODULUS_INVERSE as i32 as i64, 32, felt_type);
+    let multiple = operation("arith.muli", &[low, inverse], felt_type);
+    let multiple = operation("arith.extui", &[multiple], wide_type);
+    let wide_modulus = append_integer_constant(ctx, block, MODULUS.into(), 64, wide_type);
+    let multiple = operation("arith.muli", &[multiple, wide_modulus], wide_type);
+    let sum = operation("arith.addi", &[product, multiple], wide_type);
+    let radix_bits = append_integer_constant(ctx, block, RADIX_BITS.into(), 64, wide_type);
+    let quotient = operation("arith.shrui", &[sum, radix_bits], wide_type);
+    let quotient = operation("arith.trunci", &[quotient], felt_type);
+    append_conditional_subtraction(ctx, block, quotient, modulus, felt_type)
+}
+
+/// `floor(2^64 / p)`, the Barrett factor of the modulus.
+const fn barrett_factor(modulus: u32) -> u64 {
+    ((1 << 64) / modulus as u128) as u64
+}
+
+/// Appends the Barrett reduction of the product of two canonical felts modulo
+/// `modulus`, and returns the last operation. The modulus must be below 2^31,
+/// so that the remainder before the conditional subtraction fits in 32 bits.
+///
+/// The quotient of the 64-bit product by `p` is estimated with a
+/// multiplication by the Barrett factor, on 128 bits:
+///
+/// ```text
+/// t = lhs * rhs
+/// q = t * floor(2^64 / p) / 2^64
+/// r = t - q * p
+/// ```
+///
+/// Since `t < 2^64`, `q` is at most one less than `t / p`, so `r < 2p`, and a
+/// conditional subtraction finishes the reduction.
+fn append_barrett_product<'c, 'a>(
+    ctx: &'c Context,
+    block: &'a Block<'c>,
+    lhs: Value<'c, 'a>,
+    rhs: Value<'c, 'a>,
+    modulus: u32,
+    felt_type: Value<'c, 'a>,
+) -> Value<'c, 'a> {
+    let wide_type = append_type(ctx, block, IntegerType::new(ctx, 64).into());
+    let double_wide_type = append_type(ctx, block, IntegerType::new(ctx, 128).into());
+    let operation = |name, operands: &[Value<'c, 'a>], result_type| {
+        append_result(
+            ctx,
+            block,
+            append_operation(ctx, block, name, operands, &[], result_type),
+        )
+    };
+
+    let product = append_wide_product(ctx, block, lhs, rhs, wide_type);
+    let extended = operation("arith.extui", &[product], double_wide_type);
+    let factor = append_integer_constant(
+        ctx,
+        block,
+        barrett_factor(modulus) as i64,
+        128,
+        double_wide_type,
+    );
+    let estimate = operation("arith.muli", &[extended, factor], double_wide_type);
+    let shift = append_integer_constant(ctx, block, 64, 128, double_wide_type);
+    let quotient = operation("arith.shrui", &[estimate, shift], double_wide_type);
+    let quotient = operation("arith.trunci", &[quotient], wide_type);
+    let wide_modulus = append_integer_constant(ctx, block, modulus.into(), 64, wide_type);
+    let multiple = operation("arith.muli", &[quotient, wide_modulus], wide_type);
+    let remainder = operation("arith.subi", &[product, multiple], wide_type);
+    let remainder = operation("arith.trunci", &[remainder], felt_type);
+    let modulus = append_integer_constant(ctx, block, modulus.into(), 32, felt_type);
+    append_conditional_subtraction(ctx, block, remainder, modulus, felt_type)
+}
+
+/// Appends the 64-bit product of two felts, and returns it.
+fn append_wide_product<'c, 'a>(
+    ctx: &'c Context,
+    block: &'a Block<'c>,
+    lhs: Value<'c, 'a>,
+    rhs: Value<'c, 'a>,
+    wide_type: Value<'c, 'a>,
+) -> Value<'c, 'a> {
+    let [lhs, rhs] = [lhs, rhs].map(|value| {
+        append_result(
+            ctx,
+            block,
+            append_operation(ctx, block, "arith.extui", &[value], &[], wide_type),
+        )
+    });
+    append_result(
+        ctx,
+        block,
+        append_operation(ctx, block, "arith.muli", &[lhs, rhs], &[], wide_type),
+    )
+}
+
+/// Appends the reduction of a value in `0..2p`, as `min(x, x - p)`, and
+/// returns the last operation. If `x < p`, `x - p` wraps around, and is
+/// larger than `x`.
+fn append_conditional_subtraction<'c, 'a>(
+    ctx: &'c Context,
+    block: &'a Block<'c>,
+    value: Value<'c, 'a>,
+    modulus: Value<'c, 'a>,
+    felt_type: Value<'c, 'a>,
+) -> Value<'c, 'a> {
+    let difference = append_result(
+        ctx,
+        block,
+        append_operation(ctx, block, "arith.subi", &[value, modulus], &[], felt_type),
+    );
+    append_operation(
+        ctx,
+        block,
+        "arith.minui",
+        &[value, difference],
+        &[],
+        felt_type,
+    )
+}
+
+/// Appends an integer `arith.constant` with the given bit width.
+#[track_caller]
+fn append_integer_constant<'c, 'a>(
+    ctx: &'c Context,
+    block: &'a Block<'c>,
+    value: i64,
+    bits: u32,
+    result_type: Value<'c, 'a>,
+) -> Value<'c, 'a> {
+    append_constant(
+        ctx,
+        block,
+        IntegerAttribute::new(IntegerType::new(ctx, bits).into(), value).into(),
+        result_type,
+    )
+}
+
+/// Appends an `arith.constant` with the given value, and returns its result.
+#[track_caller]
+fn append_constant<'c, 'a>(
+    ctx: &'c Context,
+    block: &'a Block<'c>,
+    value: Attribute<'c>,
+    result_type: Value<'c, 'a>,
+) -> Value<'c, 'a> {
+    let attribute = block
+        .append_op_result(
+            OperationBuilder::new("pdl.attribute", caller_location(ctx))
+                .add_attributes(&[(Identifier::new(ctx, "value"), value)])
+                .add_results(&[pdl_attribute_type(ctx)])
+                .build()
+                .unwrap(),
+        )
+        .unwrap();
+    append_result(
+        ctx,
+        block,
+        append_operation(
+            ctx,
+            block,
+            "arith.constant",
+            &[],
+            &[("value", attribute)],
+            result_type,
+        ),
+    )
+}
+
+/// Appends a `pdl.type` bound to the given type.
+#[track_caller]
+fn append_type<'c, 'a>(ctx: &'c Context, block: &'a Block<'c>, r#type: Type<'c>) -> Value<'c, 'a> {
+    block
+        .append_op_result(
+            OperationBuilder::new("pdl.type", caller_location(ctx))
+                .add_attributes(&[(
+                    Identifier::new(ctx, "constantType"),
+                    TypeAttribute::new(r#type).into(),
+                )])
+                .add_results(&[pdl_type_type(ctx)])
+                .build()
+                .unwrap(),
+        )
+        .unwrap()
+}
+
+/// Appends a `pdl.operation` with the given operands and attributes, and a
+/// single result of the given type.
+#[track_caller]
+fn append_operation<'c, 'a>(
+    ctx: &'c Context,
+    block: &'a Block<'c>,
+    name: &str,
+    operands: &[Value<'c, 'a>],
+    attributes: &[(&str, Value<'c, 'a>)],
+    result_type: Value<'c, 'a>,
+) -> Value<'c, 'a> {
+    let attribute_values = attributes.iter().map(|(_, value)| *value);
+    let attribute_names = attributes
+        .iter()
+        .map(|(name, _)| StringAttribute::new(ctx, name).into())
+        .collect::<Vec<_>>();
+
+    block
+        .append_op_result(
+            OperationBuilder::new("pdl.operation", caller_location(ctx))
+                .add_operands(
+                    &operands
+                        .iter()
+                        .copied()
+                        .chain(attribute_values)
+                        .chain([result_type])
+                        .collect::<Vec<_>>(),
+                )
+                .add_attributes(&[
+                    (
+                        Identifier::new(ctx, "opName"),
+                        StringAttribute::new(ctx, name).into(),
+                    ),
+                    (
+                        Identifier::new(ctx, "operandSegmentSizes"),
+                        DenseI32ArrayAttribute::new(
+                            ctx,
+                            &[operands.len() as i32, attributes.len() as i32, 1],
+                        )
+                        .into(),
+                    ),
+                    (
+                        Identifier::new(ctx, "attributeValueNames"),
+                        ArrayAttribute::new(ctx, &attribute_names).into(),
+                    ),
+                ])
+                .add_results(&[pdl_operation_type(ctx)])
+                .build()
+                .unwrap(),
+        )
+        .unwrap()
+}
+
+/// Appends a `pdl.result`, binding the first result of the operation.
+#[track_caller]
+fn append_result<'c, 'a>(
+    ctx: &'c Context,
+    block: &'a Block<'c>,
+    operation: Value<'c, 'a>,
+) -> Value<'c, 'a> {
+    block
+        .append_op_result(
+            OperationBuilder::new("pdl.result", caller_location(ctx))
+                .add_operands(&[operation])
+                .add_attributes(&[(
+                    Identifier::new(ctx, "index"),
+                    IntegerAttribute::new(IntegerType::new(ctx, 32).into(), 0).into(),
+                )])
+                .add_results(&[pdl_value_type(ctx)])
+                .build()
+                .unwrap(),
+        )
+        .unwrap()
+}
+
+fn pdl_type_type(ctx: &Context) -> Type<'_> {
+    unsafe { Type::from_raw(mlirPDLTypeTypeGet(ctx.to_raw())) }
+}
+
+fn pdl_value_type(ctx: &Context) -> Type<'_> {
+    unsafe { Type::from_raw(mlirPDLValueTypeGet(ctx.to_raw())) }
+}
+
+pub(crate) fn pdl_attribute_type(ctx: &Context) -> Type<'_> {
+    unsafe { Type::from_raw(mlirPDLAttributeTypeGet(ctx.to_raw())) }
+}
+
+fn pdl_operation_type(ctx: &Context) -> Type<'_> {
+    unsafe { Type::from_raw(mlirPDLOperationTypeGet(ctx.to_raw())) }
+}
+
 #[cfg(test)]
 mod test {
+    use melior::{
+        Context,
+        dialect::ods::pdl,
+        helpers::BuiltinBlockExt,
+        ir::{
+            Block, BlockLike, Identifier, Module, Region,
+            attribute::{DenseI32ArrayAttribute, IntegerAttribute},
+            operation::{Operation, OperationBuilder},
+            r#type::IntegerType,
+        },
+    };
+
     use crate::{
-        initialize_context,
-        pdl::{build_pattern_module, load_pattern_module},
+        caller_location,
+        dsl::FeltFn,
+        execute_entrypoint, execute_map, initialize_context,
+        montgomery::convert_boundaries,
+        pdl::{
+            MODULUS, Reduction, append_barrett_product, append_operation, barrett_factor,
+            build_pattern_module, build_reduction_pattern_module, load_pattern_module,
+            pdl_type_type, pdl_value_type,
+        },
+        pipeline::PipelineOptions,
+        testing::{felt_context, lower, lower_source},
     };
 
     #[test]
@@ -365,4 +1371,191 @@ mod test {
             loaded_module.as_operation().to_string()
         )
     }
+
+    #[test]
+    fn lower_variadic_sum() {
+        let context = felt_context();
+
+        let module = lower_source(
+            &context,
+            r#"
+            module {
+              func.func @entrypoint(%a: i32, %b: i32) -> i32 attributes { llvm.emit_c_interface } {
+                %0 = "felt.sum"(%a, %b, %a, %b) : (i32, i32, i32, i32) -> i32
+                %1 = "felt.sum"(%a) : (i32) -> i32
+                %2 = "felt.sum"() : () -> i32
+                %3 = "felt.sum"(%0, %1, %2) : (i32, i32, i32) -> i32
+                return %3 : i32
+              }
+            }"#,
+            "lower_variadic_sum",
+        );
+
+        // 4 + 9 + 4 + 9 = 26 = 0, and 0 + 4 + 0 = 4.
+        assert_eq!(execute_entrypoint(&module, 4, 9), 4);
+    }
+
+    #[test]
+    fn reductions_agree() {
+        let context = felt_context();
+
+        // Every pair of felts.
+        let (xs, ys): (Vec<u32>, Vec<u32>) = (0..MODULUS)
+            .flat_map(|x| (0..MODULUS).map(move |y| (x, y)))
+            .unzip();
+
+        let outputs = [
+            Reduction::Remainder,
+            Reduction::Montgomery,
+            Reduction::Barrett,
+        ]
+        .map(|reduction| {
+            let mut module = Module::new(caller_location(&context));
+            module.body().append_operation(
+                FeltFn::map(&context, "map", 2, |b, [x, y]| {
+                    (x * y + x - y) / -y + b.constant(7)
+                })
+                .into(),
+            );
+            if reduction == Reduction::Montgomery {
+                convert_boundaries(&context, &module).unwrap();
+            }
+
+            lower(
+                &context,
+                &mut module,
+                build_reduction_pattern_module(&context, reduction),
+                &PipelineOptions::default(),
+            );
+
+            let mut output = vec![0; xs.len()];
+            execute_map(&module, "map", &[&xs, &ys], &mut output);
+            output
+        });
+
+        // Dividing by zero results in zero, and the inverse is computed with
+        // Fermat's little theorem.
+        let expected = xs
+            .iter()
+            .zip(&ys)
+            .map(|(&x, &y)| {
+                let dividend = (x * y + x + MODULUS - y) % MODULUS;
+                let divisor = (MODULUS - y) % MODULUS;
+                let inverse = (0..MODULUS - 2).fold(1, |power, _| power * divisor % MODULUS);
+                (dividend * inverse + 7) % MODULUS
+            })
+            .collect::<Vec<_>>();
+        for output in outputs {
+            assert_eq!(output, expected);
+        }
+    }
+
+    #[test]
+    fn barrett_estimate_error() {
+        let context = felt_context();
+
+        // With a modulus close to 2^31, the products are close to 2^62, and
+        // the estimated quotient is sometimes one less than the exact one, so
+        // the remainder needs the conditional subtraction.
+        let modulus = (1 << 31) - 19;
+        let (xs, ys): (Vec<u32>, Vec<u32>) = (modulus - 64..modulus)
+            .flat_map(|x| (modulus - 64..modulus).map(move |y| (x, y)))
+            .unzip();
+        assert!(xs.iter().zip(&ys).any(|(&x, &y)| {
+            let product = x as u64 * y as u64;
+            let quotient = (product as u128 * barrett_factor(modulus) as u128 >> 64) as u64;
+            quotient < product / modulus as u64
+        }));
+
+        let mut module = Module::new(caller_location(&context));
+        module
+            .body()
+            .append_operation(FeltFn::map(&context, "map", 2, |_, [x, y]| x * y).into());
+
+        let pattern_module = Module::new(caller_location(&context));
+        pattern_module
+            .body()
+            .append_operation(build_barrett_product_pattern(&context, modulus));
+        lower(
+            &context,
+            &mut module,
+            pattern_module,
+            &PipelineOptions::default(),
+        );
+
+        let mut output = vec![0; xs.len()];
+        execute_map(&module, "map", &[&xs, &ys], &mut output);
+        for ((x, y), output) in xs.into_iter().zip(ys).zip(output) {
+            assert_eq!(output as u64, x as u64 * y as u64 % modulus as u64);
+        }
+    }
+
+    /// Builds a pattern that lowers `felt.mul` into a Barrett product modulo
+    /// `modulus`.
+    fn build_barrett_product_pattern(context: &Context, modulus: u32) -> Operation<'_> {
+        pdl::PatternOperation::builder(context, caller_location(context))
+            .benefit(IntegerAttribute::new(
+                IntegerType::new(context, 16).into(),
+                1,
+            ))
+            .body_region({
+                let region = Region::new();
+                let block = region.append_block(Block::new(&[]));
+
+                let result = block
+                    .append_op_result(
+                        pdl::r#type(context, pdl_type_type(context), caller_location(context))
+                            .into(),
+                    )
+                    .unwrap();
+                let [lhs, rhs] = [(); 2].map(|_| {
+                    block
+                        .append_op_result(
+                            pdl::operand(
+                                context,
+                                pdl_value_type(context),
+                                caller_location(context),
+                            )
+                            .into(),
+                        )
+                        .unwrap()
+                });
+                let operation =
+                    append_operation(context, &block, "felt.mul", &[lhs, rhs], &[], result);
+
+                block.append_operation(
+                    OperationBuilder::new("pdl.rewrite", caller_location(context))
+                        .add_operands(&[operation])
+                        .add_attributes(&[(
+                            Identifier::new(context, "operandSegmentSizes"),
+                            DenseI32ArrayAttribute::new(context, &[1, 0]).into(),
+                        )])
+                        .add_regions([{
+                            let region = Region::new();
+                            let block = region.append_block(Block::new(&[]));
+
+                            let product =
+                                append_barrett_product(context, &block, lhs, rhs, modulus, result);
+                            block.append_operation(
+                                OperationBuilder::new("pdl.replace", caller_location(context))
+                                    .add_operands(&[operation, product])
+                                    .add_attributes(&[(
+                                        Identifier::new(context, "operandSegmentSizes"),
+                                        DenseI32ArrayAttribute::new(context, &[1, 1, 0]).into(),
+                                    )])
+                                    .build()
+                                    .unwrap(),
+                            );
+
+                            region
+                        }])
+                        .build()
+                        .unwrap(),
+                );
+
+                region
+            })
+            .build()
+            .into()
+    }
 }
@@ -0,0 +1,212 @@
+//! A frontend for a subset of PDLL, the pattern language of MLIR.
+//!
+//! MLIR's PDLL compiler is only available from C++, so this module
+//! implements the subset we need in Rust, and translates it into PDL, which
+//! can then be converted with `convert_pdl_to_pdl_interop` and applied with
+//! `apply_pdl_patterns`:
+//!
+//! ```text
+//! Pattern NegNeg with benefit(2)
+//!     => replace op<felt.neg>(op<felt.neg>(x: Value)) with x;
+//!
+//! Pattern Add {
+//!   let root = op<felt.add>(a: Value, b: Value) -> (t: Type);
+//!   rewrite root with {
+//!     let modulus = op<arith.constant> {value = attr<"13 : i32">} -> (t);
+//!     replace root with op<arith.remui>(op<arith.addi>(a, b) -> (t), modulus) -> (t);
+//!   };
+//! }
+//! ```
+//!
+//! The subset has patterns with a name and a benefit (0 by default),
+//! variables constrained to `Value`, `Type`, `Attr` or `Op<name>`, operation
+//! expressions, and attribute and type literals. The last statement of a
+//! pattern rewrites its root, with `replace`, `erase`, or a `rewrite` block.
+//! Operations used as operands stand for their first result. Result types are
+//! unconstrained in the matcher when they are omitted, and inferred in the
+//! rewriter.
+//!
+//! Native constraints and rewrites, user defined constraints, tuples,
+//! `Constraint` and `Rewrite` declarations, and includes are not supported.
+
+use std::{fs, path::Path};
+
+use melior::{Context, ir::Module};
+
+use crate::{error::Error, lang::Diagnostic, parse_module};
+
+pub mod ast;
+mod codegen;
+mod lexer;
+mod parser;
+
+/// Parses a PDLL module. Only the first syntax error is reported.
+pub fn parse(source: &str) -> Result<ast::Module, Diagnostic> {
+    parser::parse(&lexer::tokenize(source)?)
+}
+
+/// Translates a PDLL module into PDL, in the textual format. The first error
+/// of every pattern is reported.
+pub fn translate(module: &ast::Module) -> Result<String, Vec<Diagnostic>> {
+    codegen::translate_module(module)
+}
+
+/// Compiles PDLL patterns into a PDL pattern module, using `source_name` in
+/// the diagnostics. The locations of the patterns point to their PDL
+/// translation.
+pub fn compile<'c>(ctx: &'c Context, source: &str, source_name: &str) -> Result<Module<'c>, Error> {
+    let error = |diagnostics| Error::Compile {
+        source_name: source_name.to_string(),
+        diagnostics,
+    };
+
+    let module = parse(source).map_err(|diagnostic| error(vec![diagnostic]))?;
+    let source = translate(&module).map_err(error)?;
+
+    parse_module(ctx, &source, source_name).ok_or_else(|| Error::Parse(source_name.to_string()))
+}
+
+/// Compiles the PDLL patterns in the given file.
+pub fn load_patterns<'c>(ctx: &'c Context, path: impl AsRef<Path>) -> Result<Module<'c>, Error> {
+    let path = path.as_ref();
+    compile(ctx, &fs::read_to_string(path)?, &path.display().to_string())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        convert_to_llvm,
+        error::Error,
+        execute_entrypoint, initialize_context, parse_module,
+        pdll::{compile, parse, translate},
+        pipeline::PipelineOptions,
+        testing::{apply_patterns, felt_context},
+    };
+
+    const PATTERNS: &str = r#"
+// The lowering of `pdl::build_pattern_module`, for the operations used below.
+Pattern Add with benefit(1) {
+  let root = op<felt.add>(a: Value, b: Value) -> (t: Type);
+  rewrite root with {
+    let modulus = op<arith.constant> {value = attr<"13 : i32">} -> (t);
+    replace root with op<arith.remui>(op<arith.addi>(a, b) -> (t), modulus) -> (t);
+  };
+}
+
+Pattern Mul with benefit(1) {
+  let root = op<felt.mul>(a: Value, b: Value) -> (t: Type);
+  rewrite root with {
+    let modulus = op<arith.constant> {value = attr<"13 : i32">} -> (t);
+    replace root with op<arith.remui>(op<arith.muli>(a, b) -> (t), modulus) -> (t);
+  };
+}
+
+Pattern Neg with benefit(1) {
+  let root = op<felt.neg>(x: Value) -> (t: Type);
+  rewrite root with {
+    let modulus = op<arith.constant> {value = attr<"13 : i32">} -> (t);
+    replace root with op<arith.remui>(op<arith.subi>(modulus, x) -> (t), modulus) -> (t);
+  };
+}
+
+Pattern AddZero with benefit(2)
+    => replace op<felt.add>(x: Value, op<arith.constant> {value = attr<"0 : i32">}) with x;
+"#;
+
+    #[test]
+    fn translate_pattern() {
+        let module =
+            parse("Pattern NegNeg => replace op<felt.neg>(op<felt.neg>(x: Value)) with x;")
+                .unwrap();
+
+        assert_eq!(
+            translate(&module).unwrap(),
+            r#"module {
+  pdl.pattern @NegNeg : benefit(0) {
+    %0 = operand
+    %1 = types
+    %2 = operation "felt.neg"(%0 : !pdl.value) -> (%1 : !pdl.range<type>)
+    %3 = result 0 of %2
+    %4 = types
+    %5 = operation "felt.neg"(%3 : !pdl.value) -> (%4 : !pdl.range<type>)
+    rewrite %5 {
+      replace %5 with (%0 : !pdl.value)
+    }
+  }
+}"#
+        );
+    }
+
+    #[test]
+    fn compile_and_apply() {
+        let context = felt_context();
+        let options = PipelineOptions::default();
+
+        let mut module = parse_module(
+            &context,
+            r#"
+            module {
+              func.func @entrypoint(%a: i32, %b: i32) -> i32 attributes { llvm.emit_c_interface } {
+                %0 = arith.constant 0 : i32
+                %1 = "felt.mul"(%a, %b) : (i32, i32) -> i32
+                %2 = "felt.neg"(%1) : (i32) -> i32
+                %3 = "felt.add"(%b, %0) : (i32, i32) -> i32
+                %4 = "felt.add"(%2, %3) : (i32, i32) -> i32
+                return %4 : i32
+              }
+            }"#,
+            "compile_and_apply",
+        )
+        .unwrap();
+
+        let pattern_module = compile(&context, PATTERNS, "felt.pdll").unwrap();
+        apply_patterns(&context, &module, pattern_module, &options);
+
+        // The sum with 0 is removed by `AddZero`, which has a higher benefit
+        // than `Add`.
+        let source = module.as_operation().to_string();
+        assert_eq!(source.matches("arith.addi").count(), 1, "{source}");
+
+        convert_to_llvm(&context, &mut module, &options);
+
+        // -(4 * 9) = -10 = 3, and 3 + 9 + 0 = 12.
+        assert_eq!(execute_entrypoint(&module, 4, 9), 12);
+    }
+
+    #[test]
+    fn errors() {
+        let context = initialize_context();
+
+        let Err(Error::Compile { diagnostics, .. }) = compile(
+            &context,
+            "\
+Pattern {
+  let x: Value;
+  replace x with x;
+}
+Pattern Twice {
+  let root = op<felt.neg>(x: Value);
+  let x: Type;
+  erase root;
+}
+Pattern Unknown => erase op<felt.neg>(y);
+Pattern Empty {}",
+            "errors.pdll",
+        ) else {
+            panic!("expected compile errors");
+        };
+        let diagnostics = diagnostics
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            diagnostics,
+            [
+                "3:11: expected an operation, found a value",
+                "7:3: variable `x` is defined more than once",
+                "10:39: unknown variable `y`",
+                "11:1: a pattern must end with `replace`, `erase` or `rewrite`",
+            ]
+        );
+    }
+}
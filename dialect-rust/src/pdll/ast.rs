@@ -0,0 +1,91 @@
+use crate::lang::Span;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Module {
+    pub patterns: Vec<Pattern>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pattern {
+    pub name: Option<String>,
+    pub benefit: u16,
+    /// The statements that match the root operation, followed by the one
+    /// that rewrites it.
+    pub body: Vec<Statement>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Statement {
+    /// `let name: Constraint = value;`, where either the constraint or the
+    /// value can be omitted.
+    Let {
+        name: String,
+        constraint: Option<Constraint>,
+        value: Option<Expression>,
+        span: Span,
+    },
+    Replace {
+        root: Expression,
+        replacement: Expression,
+        span: Span,
+    },
+    Erase {
+        root: Expression,
+        span: Span,
+    },
+    /// `rewrite root with { ... };`, with rewrite statements in the body.
+    Rewrite {
+        root: Expression,
+        body: Vec<Statement>,
+        span: Span,
+    },
+}
+
+impl Statement {
+    pub fn span(&self) -> Span {
+        match self {
+            Statement::Let { span, .. }
+            | Statement::Replace { span, .. }
+            | Statement::Erase { span, .. }
+            | Statement::Rewrite { span, .. } => *span,
+        }
+    }
+}
+
+/// The kind of an entity, as written in the source code.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Constraint {
+    Value,
+    Type,
+    Attr,
+    /// `Op`, or `Op<dialect.name>`.
+    Op(Option<String>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Expression {
+    pub kind: ExpressionKind,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExpressionKind {
+    Variable(String),
+    /// An inline variable declaration, like `x: Value`. The variable is not
+    /// bound if its name is `_`.
+    Declaration(String, Constraint),
+    /// `op<name>(operands) {attributes} -> (results)`. Without results, the
+    /// result types are unconstrained when matching, and inferred when
+    /// rewriting.
+    Operation {
+        name: String,
+        operands: Vec<Expression>,
+        attributes: Vec<(String, Expression)>,
+        results: Option<Vec<Expression>>,
+    },
+    /// `attr<"13 : i32">`.
+    Attribute(String),
+    /// `type<"i32">`.
+    Type(String),
+}
@@ -0,0 +1,368 @@
+use std::{collections::HashMap, fmt, fmt::Write};
+
+use crate::{
+    lang::{Diagnostic, Span},
+    pdll::ast::{Constraint, Expression, ExpressionKind, Module, Pattern, Statement},
+};
+
+/// Translates a module into PDL, in the textual format. Every pattern is
+/// translated, and the first error of each pattern is reported.
+pub fn translate_module(module: &Module) -> Result<String, Vec<Diagnostic>> {
+    let mut source = String::from("module {\n");
+    let mut diagnostics = Vec::new();
+    for pattern in &module.patterns {
+        match translate_pattern(pattern) {
+            Ok(lines) => {
+                for line in lines {
+                    writeln!(source, "  {line}").unwrap();
+                }
+            }
+            Err(diagnostic) => diagnostics.push(diagnostic),
+        }
+    }
+    source.push('}');
+
+    if diagnostics.is_empty() {
+        Ok(source)
+    } else {
+        Err(diagnostics)
+    }
+}
+
+fn translate_pattern(pattern: &Pattern) -> Result<Vec<String>, Diagnostic> {
+    let mut emitter = PatternEmitter {
+        variables: HashMap::new(),
+        lines: Vec::new(),
+        next: 0,
+        indent: 1,
+    };
+    emitter.lines.push(match &pattern.name {
+        Some(name) => format!("pdl.pattern @{name} : benefit({}) {{", pattern.benefit),
+        None => format!("pdl.pattern : benefit({}) {{", pattern.benefit),
+    });
+
+    let ends_with_rewrite = matches!(
+        pattern.body.last(),
+        Some(Statement::Replace { .. } | Statement::Erase { .. } | Statement::Rewrite { .. })
+    );
+    if !ends_with_rewrite {
+        return Err(Diagnostic::new(
+            pattern.body.last().map_or(pattern.span, Statement::span),
+            "a pattern must end with `replace`, `erase` or `rewrite`",
+        ));
+    }
+    let (rewrite, matcher) = pattern.body.split_last().unwrap();
+
+    for statement in matcher {
+        match statement {
+            Statement::Let { .. } => emitter.statement(statement, Context::Match)?,
+            _ => {
+                return Err(Diagnostic::new(
+                    statement.span(),
+                    "`replace`, `erase` and `rewrite` must be the last statement of a pattern",
+                ));
+            }
+        }
+    }
+
+    let (Statement::Replace { root, .. }
+    | Statement::Erase { root, .. }
+    | Statement::Rewrite { root, .. }) = rewrite
+    else {
+        unreachable!();
+    };
+    let root = emitter.expect(root, Context::Match, Kind::Operation)?;
+    emitter.line(format!("rewrite {root} {{"));
+    emitter.indent += 1;
+    match rewrite {
+        Statement::Rewrite { body, .. } => {
+            for statement in body {
+                emitter.statement(statement, Context::Rewrite)?;
+            }
+        }
+        _ => emitter.rewrite(rewrite, &root)?,
+    }
+    emitter.indent -= 1;
+    emitter.line("}".to_string());
+
+    emitter.lines.push("}".to_string());
+    Ok(emitter.lines)
+}
+
+/// Whether expressions match existing IR, or build new IR.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Context {
+    Match,
+    Rewrite,
+}
+
+/// The PDL type of a translated expression.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Value,
+    Operation,
+    Attribute,
+    Type,
+}
+
+impl Kind {
+    fn of(constraint: &Constraint) -> Self {
+        match constraint {
+            Constraint::Value => Kind::Value,
+            Constraint::Type => Kind::Type,
+            Constraint::Attr => Kind::Attribute,
+            Constraint::Op(_) => Kind::Operation,
+        }
+    }
+}
+
+impl fmt::Display for Kind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Kind::Value => write!(f, "a value"),
+            Kind::Operation => write!(f, "an operation"),
+            Kind::Attribute => write!(f, "an attribute"),
+            Kind::Type => write!(f, "a type"),
+        }
+    }
+}
+
+struct PatternEmitter<'a> {
+    variables: HashMap<&'a str, (String, Kind)>,
+    lines: Vec<String>,
+    next: usize,
+    indent: usize,
+}
+
+impl<'a> PatternEmitter<'a> {
+    fn statement(&mut self, statement: &'a Statement, context: Context) -> Result<(), Diagnostic> {
+        match statement {
+            Statement::Let {
+                name,
+                constraint,
+                value,
+                span,
+            } => {
+                let (value, kind) = match (constraint, value) {
+                    (_, Some(value)) => {
+                        let (value, kind) = self.expression(value, context)?;
+                        if let Some(expected) = constraint
+                            .as_ref()
+                            .map(Kind::of)
+                            .filter(|expected| *expected != kind)
+                        {
+                            return Err(Diagnostic::new(
+                                *span,
+                                format!("expected {expected}, found {kind}"),
+                            ));
+                        }
+                        (value, kind)
+                    }
+                    (_, None) if context == Context::Rewrite => {
+                        return Err(Diagnostic::new(
+                            *span,
+                            "variables of a rewrite must be initialized",
+                        ));
+                    }
+                    (Some(constraint), None) => (self.declare(constraint), Kind::of(constraint)),
+                    (None, None) => {
+                        return Err(Diagnostic::new(
+                            *span,
+                            format!("variable `{name}` needs a constraint or a value"),
+                        ));
+                    }
+                };
+                self.bind(name, value, kind, *span)
+            }
+            Statement::Replace { root, .. } | Statement::Erase { root, .. } => {
+                let root = self.expect(root, context, Kind::Operation)?;
+                self.rewrite(statement, &root)
+            }
+            Statement::Rewrite { span, .. } => Err(Diagnostic::new(
+                *span,
+                "`rewrite` must be the last statement of a pattern",
+            )),
+        }
+    }
+
+    /// Emits a `replace` or an `erase` of the root.
+    fn rewrite(&mut self, statement: &'a Statement, root: &str) -> Result<(), Diagnostic> {
+        match statement {
+            Statement::Replace { replacement, .. } => {
+                let (replacement, kind) = self.expression(replacement, Context::Rewrite)?;
+                match kind {
+                    Kind::Operation => self.line(format!("replace {root} with {replacement}")),
+                    Kind::Value => {
+                        self.line(format!("replace {root} with ({replacement} : !pdl.value)"))
+                    }
+                    _ => {
+                        return Err(Diagnostic::new(
+                            statement.span(),
+                            format!("cannot replace an operation with {kind}"),
+                        ));
+                    }
+                }
+            }
+            Statement::Erase { .. } => self.line(format!("erase {root}")),
+            _ => unreachable!(),
+        }
+        Ok(())
+    }
+
+    fn expression(
+        &mut self,
+        expression: &'a Expression,
+        context: Context,
+    ) -> Result<(String, Kind), Diagnostic> {
+        match &expression.kind {
+            ExpressionKind::Variable(name) => {
+                self.variables.get(name.as_str()).cloned().ok_or_else(|| {
+                    Diagnostic::new(expression.span, format!("unknown variable `{name}`"))
+                })
+            }
+            ExpressionKind::Declaration(name, constraint) => {
+                if context == Context::Rewrite {
+                    return Err(Diagnostic::new(
+                        expression.span,
+                        "variables of a rewrite must be initialized",
+                    ));
+                }
+                let value = self.declare(constraint);
+                let kind = Kind::of(constraint);
+                self.bind(name, value.clone(), kind, expression.span)?;
+                Ok((value, kind))
+            }
+            ExpressionKind::Operation {
+                name,
+                operands,
+                attributes,
+                results,
+            } => {
+                let operands = operands
+                    .iter()
+                    .map(|operand| self.expect(operand, context, Kind::Value))
+                    .collect::<Result<Vec<_>, _>>()?;
+                let attributes = attributes
+                    .iter()
+                    .map(|(name, value)| Ok((name, self.expect(value, context, Kind::Attribute)?)))
+                    .collect::<Result<Vec<_>, Diagnostic>>()?;
+                let results = match results {
+                    Some(results) => {
+                        let results = results
+                            .iter()
+                            .map(|result| self.expect(result, context, Kind::Type))
+                            .collect::<Result<Vec<_>, _>>()?;
+                        if results.is_empty() {
+                            String::new()
+                        } else {
+                            typed_list(&results, "!pdl.type")
+                        }
+                    }
+                    // The result types are unconstrained.
+                    None if context == Context::Match => {
+                        format!("({} : !pdl.range<type>)", self.emit("types".to_string()))
+                    }
+                    // The result types are inferred.
+                    None => String::new(),
+                };
+
+                let mut operation = format!("operation \"{name}\"");
+                if !operands.is_empty() {
+                    operation.push_str(&typed_list(&operands, "!pdl.value"));
+                }
+                if !attributes.is_empty() {
+                    let attributes = attributes
+                        .iter()
+                        .map(|(name, value)| format!("\"{name}\" = {value}"))
+                        .collect::<Vec<_>>();
+                    write!(operation, " {{{}}}", attributes.join(", ")).unwrap();
+                }
+                if !results.is_empty() {
+                    write!(operation, " -> {results}").unwrap();
+                }
+                Ok((self.emit(operation), Kind::Operation))
+            }
+            ExpressionKind::Attribute(value) => {
+                Ok((self.emit(format!("attribute = {value}")), Kind::Attribute))
+            }
+            ExpressionKind::Type(value) => Ok((self.emit(format!("type : {value}")), Kind::Type)),
+        }
+    }
+
+    /// Translates an expression of the given kind. Operations stand for their
+    /// first result where a value is expected.
+    fn expect(
+        &mut self,
+        expression: &'a Expression,
+        context: Context,
+        expected: Kind,
+    ) -> Result<String, Diagnostic> {
+        match self.expression(expression, context)? {
+            (value, kind) if kind == expected => Ok(value),
+            (operation, Kind::Operation) if expected == Kind::Value => {
+                Ok(self.emit(format!("result 0 of {operation}")))
+            }
+            (_, kind) => Err(Diagnostic::new(
+                expression.span,
+                format!("expected {expected}, found {kind}"),
+            )),
+        }
+    }
+
+    /// Emits an unconstrained entity, matching anything of its kind.
+    fn declare(&mut self, constraint: &Constraint) -> String {
+        match constraint {
+            Constraint::Value => self.emit("operand".to_string()),
+            Constraint::Type => self.emit("type".to_string()),
+            Constraint::Attr => self.emit("attribute".to_string()),
+            Constraint::Op(name) => {
+                let operands = self.emit("operands".to_string());
+                let results = self.emit("types".to_string());
+                let name = name
+                    .as_ref()
+                    .map_or(String::new(), |name| format!(" \"{name}\""));
+                self.emit(format!(
+                    "operation{name}({operands} : !pdl.range<value>) -> ({results} : !pdl.range<type>)"
+                ))
+            }
+        }
+    }
+
+    fn bind(
+        &mut self,
+        name: &'a str,
+        value: String,
+        kind: Kind,
+        span: Span,
+    ) -> Result<(), Diagnostic> {
+        if name != "_" && self.variables.insert(name, (value, kind)).is_some() {
+            return Err(Diagnostic::new(
+                span,
+                format!("variable `{name}` is defined more than once"),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Emits an operation with a single result, and returns the result.
+    fn emit(&mut self, operation: String) -> String {
+        let value = format!("%{}", self.next);
+        self.next += 1;
+        self.line(format!("{value} = {operation}"));
+        value
+    }
+
+    fn line(&mut self, line: String) {
+        self.lines
+            .push(format!("{}{line}", "  ".repeat(self.indent)));
+    }
+}
+
+/// Formats `(%0, %1 : type, type)`.
+fn typed_list(values: &[String], r#type: &str) -> String {
+    format!(
+        "({} : {})",
+        values.join(", "),
+        vec![r#type; values.len()].join(", ")
+    )
+}
@@ -0,0 +1,225 @@
+use std::{fmt, iter::Peekable, str::Chars};
+
+use crate::lang::{Diagnostic, Span};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TokenKind {
+    Pattern,
+    Let,
+    With,
+    Benefit,
+    Replace,
+    Erase,
+    Rewrite,
+    Op,
+    Attr,
+    Type,
+    Identifier(String),
+    Integer(u64),
+    String(String),
+    LeftParen,
+    RightParen,
+    LeftBrace,
+    RightBrace,
+    Less,
+    Greater,
+    Comma,
+    Colon,
+    Semicolon,
+    Dot,
+    Arrow,
+    FatArrow,
+    Equal,
+    Eof,
+}
+
+impl fmt::Display for TokenKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TokenKind::Pattern => write!(f, "`Pattern`"),
+            TokenKind::Let => write!(f, "`let`"),
+            TokenKind::With => write!(f, "`with`"),
+            TokenKind::Benefit => write!(f, "`benefit`"),
+            TokenKind::Replace => write!(f, "`replace`"),
+            TokenKind::Erase => write!(f, "`erase`"),
+            TokenKind::Rewrite => write!(f, "`rewrite`"),
+            TokenKind::Op => write!(f, "`op`"),
+            TokenKind::Attr => write!(f, "`attr`"),
+            TokenKind::Type => write!(f, "`type`"),
+            TokenKind::Identifier(name) => write!(f, "`{name}`"),
+            TokenKind::Integer(value) => write!(f, "`{value}`"),
+            TokenKind::String(value) => write!(f, "{value:?}"),
+            TokenKind::LeftParen => write!(f, "`(`"),
+            TokenKind::RightParen => write!(f, "`)`"),
+            TokenKind::LeftBrace => write!(f, "`{{`"),
+            TokenKind::RightBrace => write!(f, "`}}`"),
+            TokenKind::Less => write!(f, "`<`"),
+            TokenKind::Greater => write!(f, "`>`"),
+            TokenKind::Comma => write!(f, "`,`"),
+            TokenKind::Colon => write!(f, "`:`"),
+            TokenKind::Semicolon => write!(f, "`;`"),
+            TokenKind::Dot => write!(f, "`.`"),
+            TokenKind::Arrow => write!(f, "`->`"),
+            TokenKind::FatArrow => write!(f, "`=>`"),
+            TokenKind::Equal => write!(f, "`=`"),
+            TokenKind::Eof => write!(f, "end of file"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub span: Span,
+}
+
+/// Splits the source code into tokens. The last token is always `Eof`.
+///
+/// Whitespace and `//` comments are skipped. Strings can escape `"` and `\`
+/// with a backslash.
+pub fn tokenize(source: &str) -> Result<Vec<Token>, Diagnostic> {
+    let mut tokens = Vec::new();
+    let mut cursor = Cursor {
+        characters: source.chars().peekable(),
+        span: Span { line: 1, column: 1 },
+    };
+
+    while let Some(character) = cursor.peek() {
+        let start = cursor.span;
+
+        let kind = match character {
+            _ if character.is_whitespace() => {
+                cursor.advance();
+                continue;
+            }
+            '/' => {
+                cursor.advance();
+                if cursor.peek() != Some('/') {
+                    return Err(Diagnostic::new(start, "unexpected character `/`"));
+                }
+                while cursor.peek().is_some_and(|c| c != '\n') {
+                    cursor.advance();
+                }
+                continue;
+            }
+            '-' => {
+                cursor.advance();
+                if cursor.peek() != Some('>') {
+                    return Err(Diagnostic::new(start, "unexpected character `-`"));
+                }
+                cursor.advance();
+                TokenKind::Arrow
+            }
+            '=' => {
+                cursor.advance();
+                if cursor.peek() == Some('>') {
+                    cursor.advance();
+                    TokenKind::FatArrow
+                } else {
+                    TokenKind::Equal
+                }
+            }
+            '"' => {
+                cursor.advance();
+                let mut string = String::new();
+                loop {
+                    match cursor.advance() {
+                        Some('"') => break,
+                        Some('\\') => match cursor.advance() {
+                            Some(character @ ('"' | '\\')) => string.push(character),
+                            _ => return Err(Diagnostic::new(start, "invalid escape in string")),
+                        },
+                        Some(character) => string.push(character),
+                        None => return Err(Diagnostic::new(start, "unterminated string")),
+                    }
+                }
+                TokenKind::String(string)
+            }
+            '0'..='9' => {
+                let digits = cursor.take_while(|c| c.is_ascii_digit());
+                let value = digits.parse().map_err(|_| {
+                    Diagnostic::new(start, format!("integer literal `{digits}` is too large"))
+                })?;
+                TokenKind::Integer(value)
+            }
+            _ if character.is_alphabetic() || character == '_' => {
+                let word = cursor.take_while(|c| c.is_alphanumeric() || c == '_');
+                match word.as_str() {
+                    "Pattern" => TokenKind::Pattern,
+                    "let" => TokenKind::Let,
+                    "with" => TokenKind::With,
+                    "benefit" => TokenKind::Benefit,
+                    "replace" => TokenKind::Replace,
+                    "erase" => TokenKind::Erase,
+                    "rewrite" => TokenKind::Rewrite,
+                    "op" => TokenKind::Op,
+                    "attr" => TokenKind::Attr,
+                    "type" => TokenKind::Type,
+                    _ => TokenKind::Identifier(word),
+                }
+            }
+            _ => {
+                cursor.advance();
+                match character {
+                    '(' => TokenKind::LeftParen,
+                    ')' => TokenKind::RightParen,
+                    '{' => TokenKind::LeftBrace,
+                    '}' => TokenKind::RightBrace,
+                    '<' => TokenKind::Less,
+                    '>' => TokenKind::Greater,
+                    ',' => TokenKind::Comma,
+                    ':' => TokenKind::Colon,
+                    ';' => TokenKind::Semicolon,
+                    '.' => TokenKind::Dot,
+                    _ => {
+                        return Err(Diagnostic::new(
+                            start,
+                            format!("unexpected character `{character}`"),
+                        ));
+                    }
+                }
+            }
+        };
+
+        tokens.push(Token { kind, span: start });
+    }
+
+    tokens.push(Token {
+        kind: TokenKind::Eof,
+        span: cursor.span,
+    });
+    Ok(tokens)
+}
+
+/// Iterates over the characters of the source code, keeping track of the
+/// position of the next one.
+struct Cursor<'a> {
+    characters: Peekable<Chars<'a>>,
+    span: Span,
+}
+
+impl Cursor<'_> {
+    fn peek(&mut self) -> Option<char> {
+        self.characters.peek().copied()
+    }
+
+    fn advance(&mut self) -> Option<char> {
+        let character = self.characters.next()?;
+        if character == '\n' {
+            self.span.line += 1;
+            self.span.column = 1;
+        } else {
+            self.span.column += 1;
+        }
+        Some(character)
+    }
+
+    fn take_while(&mut self, predicate: impl Fn(char) -> bool) -> String {
+        let mut string = String::new();
+        while let Some(character) = self.peek().filter(|&c| predicate(c)) {
+            string.push(character);
+            self.advance();
+        }
+        string
+    }
+}
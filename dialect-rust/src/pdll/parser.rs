@@ -0,0 +1,311 @@
+use crate::{
+    lang::{Diagnostic, Span},
+    pdll::{
+        ast::{Constraint, Expression, ExpressionKind, Module, Pattern, Statement},
+        lexer::{Token, TokenKind},
+    },
+};
+
+/// Parses a PDLL module with a recursive descent parser. The grammar is:
+///
+/// ```text
+/// module     := pattern*
+/// pattern    := "Pattern" identifier? ("with" "benefit" "(" integer ")")?
+///               ("{" statement* "}" | "=>" statement)
+/// statement  := "let" identifier (":" constraint)? ("=" expression)? ";"
+///             | "replace" expression "with" expression ";"
+///             | "erase" expression ";"
+///             | "rewrite" expression "with" "{" statement* "}" ";"?
+/// constraint := "Value" | "Type" | "Attr" | "Op" ("<" name ">")?
+/// expression := identifier (":" constraint)?
+///             | "op" "<" name ">" ("(" expressions ")")?
+///               ("{" (identifier "=" expression),* "}")?
+///               ("->" "(" expressions ")")?
+///             | "attr" "<" string ">" | "type" "<" string ">"
+/// name       := identifier ("." identifier)*
+/// ```
+pub fn parse(tokens: &[Token]) -> Result<Module, Diagnostic> {
+    let mut parser = Parser {
+        tokens,
+        position: 0,
+    };
+
+    let mut patterns = Vec::new();
+    while parser.peek().kind != TokenKind::Eof {
+        patterns.push(parser.pattern()?);
+    }
+
+    Ok(Module { patterns })
+}
+
+struct Parser<'a> {
+    tokens: &'a [Token],
+    position: usize,
+}
+
+impl Parser<'_> {
+    fn pattern(&mut self) -> Result<Pattern, Diagnostic> {
+        let span = self.expect(TokenKind::Pattern)?;
+        let name = match self.peek().kind {
+            TokenKind::Identifier(_) => Some(self.identifier()?.0),
+            _ => None,
+        };
+
+        let mut benefit = 0;
+        if self.eat(&TokenKind::With) {
+            self.expect(TokenKind::Benefit)?;
+            self.expect(TokenKind::LeftParen)?;
+            let token = self.peek().clone();
+            benefit = match token.kind {
+                TokenKind::Integer(value) => u16::try_from(value).map_err(|_| {
+                    Diagnostic::new(token.span, format!("benefit `{value}` is too large"))
+                })?,
+                _ => return Err(self.unexpected("a benefit")),
+            };
+            self.bump();
+            self.expect(TokenKind::RightParen)?;
+        }
+
+        let body = if self.eat(&TokenKind::FatArrow) {
+            vec![self.statement()?]
+        } else {
+            self.block()?
+        };
+
+        Ok(Pattern {
+            name,
+            benefit,
+            body,
+            span,
+        })
+    }
+
+    fn block(&mut self) -> Result<Vec<Statement>, Diagnostic> {
+        self.expect(TokenKind::LeftBrace)?;
+        let mut body = Vec::new();
+        while !self.eat(&TokenKind::RightBrace) {
+            body.push(self.statement()?);
+        }
+        Ok(body)
+    }
+
+    fn statement(&mut self) -> Result<Statement, Diagnostic> {
+        let span = self.peek().span;
+        let statement = if self.eat(&TokenKind::Let) {
+            let (name, _) = self.identifier()?;
+            let constraint = if self.eat(&TokenKind::Colon) {
+                Some(self.constraint()?)
+            } else {
+                None
+            };
+            let value = if self.eat(&TokenKind::Equal) {
+                Some(self.expression()?)
+            } else {
+                None
+            };
+            Statement::Let {
+                name,
+                constraint,
+                value,
+                span,
+            }
+        } else if self.eat(&TokenKind::Replace) {
+            let root = self.expression()?;
+            self.expect(TokenKind::With)?;
+            let replacement = self.expression()?;
+            Statement::Replace {
+                root,
+                replacement,
+                span,
+            }
+        } else if self.eat(&TokenKind::Erase) {
+            let root = self.expression()?;
+            Statement::Erase { root, span }
+        } else if self.eat(&TokenKind::Rewrite) {
+            let root = self.expression()?;
+            self.expect(TokenKind::With)?;
+            let body = self.block()?;
+            // Like in PDLL, the semicolon after the block is optional.
+            self.eat(&TokenKind::Semicolon);
+            return Ok(Statement::Rewrite { root, body, span });
+        } else {
+            return Err(self.unexpected("a statement"));
+        };
+        self.expect(TokenKind::Semicolon)?;
+
+        Ok(statement)
+    }
+
+    fn constraint(&mut self) -> Result<Constraint, Diagnostic> {
+        let (name, span) = self.identifier()?;
+        match name.as_str() {
+            "Value" => Ok(Constraint::Value),
+            "Type" => Ok(Constraint::Type),
+            "Attr" => Ok(Constraint::Attr),
+            "Op" => {
+                if self.eat(&TokenKind::Less) {
+                    let name = self.name()?;
+                    self.expect(TokenKind::Greater)?;
+                    Ok(Constraint::Op(Some(name)))
+                } else {
+                    Ok(Constraint::Op(None))
+                }
+            }
+            _ => Err(Diagnostic::new(
+                span,
+                format!("unknown constraint `{name}`"),
+            )),
+        }
+    }
+
+    fn expression(&mut self) -> Result<Expression, Diagnostic> {
+        let token = self.peek().clone();
+        let kind = match token.kind {
+            TokenKind::Identifier(name) => {
+                self.bump();
+                if self.eat(&TokenKind::Colon) {
+                    ExpressionKind::Declaration(name, self.constraint()?)
+                } else {
+                    ExpressionKind::Variable(name)
+                }
+            }
+            TokenKind::Op => {
+                self.bump();
+                self.expect(TokenKind::Less)?;
+                let name = self.name()?;
+                self.expect(TokenKind::Greater)?;
+
+                let operands = if self.eat(&TokenKind::LeftParen) {
+                    self.list(TokenKind::RightParen, Self::expression)?
+                } else {
+                    Vec::new()
+                };
+                let attributes = if self.eat(&TokenKind::LeftBrace) {
+                    self.list(TokenKind::RightBrace, |parser| {
+                        let (name, _) = parser.identifier()?;
+                        parser.expect(TokenKind::Equal)?;
+                        Ok((name, parser.expression()?))
+                    })?
+                } else {
+                    Vec::new()
+                };
+                let results = if self.eat(&TokenKind::Arrow) {
+                    self.expect(TokenKind::LeftParen)?;
+                    Some(self.list(TokenKind::RightParen, Self::expression)?)
+                } else {
+                    None
+                };
+
+                ExpressionKind::Operation {
+                    name,
+                    operands,
+                    attributes,
+                    results,
+                }
+            }
+            TokenKind::Attr => {
+                self.bump();
+                ExpressionKind::Attribute(self.literal()?)
+            }
+            TokenKind::Type => {
+                self.bump();
+                ExpressionKind::Type(self.literal()?)
+            }
+            _ => return Err(self.unexpected("an expression")),
+        };
+
+        Ok(Expression {
+            kind,
+            span: token.span,
+        })
+    }
+
+    /// Parses the `<"...">` of an attribute or a type literal.
+    fn literal(&mut self) -> Result<String, Diagnostic> {
+        self.expect(TokenKind::Less)?;
+        let TokenKind::String(value) = self.peek().kind.clone() else {
+            return Err(self.unexpected("a string"));
+        };
+        self.bump();
+        self.expect(TokenKind::Greater)?;
+        Ok(value)
+    }
+
+    /// Parses a dotted operation name, like `felt.add`.
+    fn name(&mut self) -> Result<String, Diagnostic> {
+        let (mut name, _) = self.identifier()?;
+        while self.eat(&TokenKind::Dot) {
+            name.push('.');
+            name.push_str(&self.identifier()?.0);
+        }
+        Ok(name)
+    }
+
+    /// Parses a comma separated list, with an optional trailing comma, up to
+    /// the closing token.
+    fn list<T>(
+        &mut self,
+        close: TokenKind,
+        mut element: impl FnMut(&mut Self) -> Result<T, Diagnostic>,
+    ) -> Result<Vec<T>, Diagnostic> {
+        let mut elements = Vec::new();
+        while !self.eat(&close) {
+            elements.push(element(self)?);
+            if !self.eat(&TokenKind::Comma) {
+                if self.eat(&close) {
+                    break;
+                }
+                return Err(self.unexpected(&format!("`,` or {close}")));
+            }
+        }
+        Ok(elements)
+    }
+
+    fn identifier(&mut self) -> Result<(String, Span), Diagnostic> {
+        match &self.peek().kind {
+            TokenKind::Identifier(name) => {
+                let name = name.clone();
+                Ok((name, self.bump().span))
+            }
+            _ => Err(self.unexpected("an identifier")),
+        }
+    }
+
+    fn peek(&self) -> &Token {
+        &self.tokens[self.position]
+    }
+
+    /// Consumes the current token. The `Eof` token is never consumed.
+    fn bump(&mut self) -> &Token {
+        let token = &self.tokens[self.position];
+        if token.kind != TokenKind::Eof {
+            self.position += 1;
+        }
+        token
+    }
+
+    /// Consumes the current token if it is of the given kind.
+    fn eat(&mut self, kind: &TokenKind) -> bool {
+        let matches = &self.peek().kind == kind;
+        if matches {
+            self.bump();
+        }
+        matches
+    }
+
+    fn expect(&mut self, kind: TokenKind) -> Result<Span, Diagnostic> {
+        if self.peek().kind == kind {
+            Ok(self.bump().span)
+        } else {
+            Err(self.unexpected(&kind.to_string()))
+        }
+    }
+
+    fn unexpected(&self, expected: &str) -> Diagnostic {
+        let token = self.peek();
+        Diagnostic::new(
+            token.span,
+            format!("expected {expected}, found {}", token.kind),
+        )
+    }
+}
@@ -0,0 +1,277 @@
+use std::{
+    cell::RefCell,
+    ffi::c_void,
+    io::{self, Write},
+    path::PathBuf,
+    slice,
+};
+
+use melior::{Context, StringRef, ir::Module, pass::PassManager, utility::parse_pass_pipeline};
+
+use crate::{error::Error, ffi};
+
+/// Debugging options for the pass-running functions.
+///
+/// The options instrument every pass of the pipeline, including nested
+/// passes like `cse` in `func.func(cse)`. An instrumented pipeline runs on a
+/// single thread, because the sink isn't thread-safe.
+pub struct PipelineOptions {
+    /// Print the module before every pass.
+    pub print_ir_before: bool,
+    /// Print the module after every pass.
+    pub print_ir_after: bool,
+    /// Print the module after every pass that changed it.
+    pub print_ir_after_change: bool,
+    /// Report the time spent on every pass.
+    pub timing: bool,
+    /// Report MLIR's pass statistics, like the number of operations that
+    /// `cse` eliminated.
+    pub statistics: bool,
+    /// Where the IR dumps and the reports are written. Defaults to stderr.
+    pub sink: RefCell<Box<dyn Write>>,
+    /// If a pass fails or crashes, write a crash reproducer to this path. See
+    /// the `reproducer` module.
+    pub reproducer: Option<PathBuf>,
+    /// Attach DWARF debug info, derived from the operation locations, when
+    /// lowering to LLVM. See `convert_to_llvm`.
+    pub debug_info: bool,
+}
+
+impl PipelineOptions {
+    pub fn is_instrumented(&self) -> bool {
+        self.print_ir_before
+            || self.print_ir_after
+            || self.print_ir_after_change
+            || self.timing
+            || self.statistics
+    }
+}
+
+impl Default for PipelineOptions {
+    fn default() -> Self {
+        PipelineOptions {
+            print_ir_before: false,
+            print_ir_after: false,
+            print_ir_after_change: false,
+            timing: false,
+            statistics: false,
+            sink: RefCell::new(Box::new(io::stderr())),
+            reproducer: None,
+            debug_info: false,
+        }
+    }
+}
+
+/// Runs the pipeline with a single pass manager, instrumented as requested by
+/// the options. If it fails or crashes, and a reproducer path is given, MLIR
+/// writes a crash reproducer to it.
+pub(crate) fn run_passes(
+    context: &Context,
+    module: &mut Module<'_>,
+    pipeline: &str,
+    options: &PipelineOptions,
+) -> Result<(), Error> {
+    if !options.is_instrumented() {
+        return run_pass_manager(context, module, pipeline, options, None);
+    }
+
+    // MLIR writes the IR dumps and the reports to a C++ stream, which passes
+    // them on to the sink. The sink isn't thread-safe, so the passes run on
+    // this thread.
+    let multithreaded = unsafe { ffi::dialectRustIsMultithreadingEnabled(context.to_raw()) };
+    context.enable_multi_threading(false);
+    let mut state = SinkState {
+        sink: &options.sink,
+        error: None,
+    };
+    let stream = unsafe {
+        ffi::dialectRustCreateStream(write_to_sink, &mut state as *mut SinkState as *mut c_void)
+    };
+    let result = run_pass_manager(context, module, pipeline, options, Some(stream));
+    unsafe { ffi::dialectRustDestroyStream(stream) };
+    context.enable_multi_threading(multithreaded);
+
+    result?;
+    match state.error {
+        Some(error) => Err(error.into()),
+        None => Ok(()),
+    }
+}
+
+fn run_pass_manager(
+    context: &Context,
+    module: &mut Module<'_>,
+    pipeline: &str,
+    options: &PipelineOptions,
+    stream: Option<*mut c_void>,
+) -> Result<(), Error> {
+    let pass_manager = PassManager::new(context);
+    pass_manager.enable_verifier(true);
+    parse_pass_pipeline(pass_manager.as_operation_pass_manager(), pipeline)?;
+
+    if let Some(path) = &options.reproducer {
+        let path = path.to_string_lossy();
+        unsafe {
+            ffi::dialectRustEnableCrashReproducer(
+                pass_manager.to_raw(),
+                StringRef::new(&path).to_raw(),
+            )
+        };
+    }
+
+    if let Some(stream) = stream {
+        let raw = pass_manager.to_raw();
+        if options.print_ir_before || options.print_ir_after || options.print_ir_after_change {
+            unsafe {
+                ffi::dialectRustEnableIRPrinting(
+                    raw,
+                    options.print_ir_before,
+                    options.print_ir_after,
+                    options.print_ir_after_change,
+                    stream,
+                )
+            };
+        }
+        if options.timing {
+            unsafe { ffi::dialectRustEnableTiming(raw, stream) };
+        }
+        if options.statistics {
+            unsafe { ffi::dialectRustEnableStatistics(raw, stream) };
+        }
+    }
+
+    // The timing and statistics reports are written when the pass manager is
+    // dropped, before the stream is destroyed.
+    pass_manager.run(module)?;
+    Ok(())
+}
+
+/// The data of the stream created by `run_passes`.
+struct SinkState<'a> {
+    sink: &'a RefCell<Box<dyn Write>>,
+    error: Option<io::Error>,
+}
+
+/// Writes the output of the instrumentation to the sink.
+///
+/// It is called from C++, so it must not panic. The first error is kept, and
+/// returned once the pipeline has run.
+unsafe extern "C" fn write_to_sink(data: *mut c_void, ptr: *const u8, size: usize) {
+    let state = unsafe { &mut *(data as *mut SinkState) };
+    if state.error.is_some() || size == 0 {
+        return;
+    }
+    let bytes = unsafe { slice::from_raw_parts(ptr, size) };
+    let result = match state.sink.try_borrow_mut() {
+        Ok(mut sink) => sink.write_all(bytes),
+        Err(_) => Err(io::Error::other(
+            "the instrumentation sink is already borrowed",
+        )),
+    };
+    if let Err(error) = result {
+        state.error = Some(error);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{cell::RefCell, io::Write, rc::Rc};
+
+    use melior::ir::Module;
+
+    use crate::{initialize_context, pipeline::PipelineOptions, run_pipeline};
+
+    /// A sink that can be inspected after being moved into the options.
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn print_ir_after_change() {
+        let context = initialize_context();
+        let mut module = Module::parse(
+            &context,
+            r#"
+            module {
+              func.func @double(%arg0: i32) -> i32 {
+                %c2 = arith.constant 2 : i32
+                %0 = arith.muli %arg0, %c2 : i32
+                return %0 : i32
+              }
+            }"#,
+        )
+        .unwrap();
+
+        let buffer = SharedBuffer::default();
+        let options = PipelineOptions {
+            print_ir_after_change: true,
+            timing: true,
+            sink: RefCell::new(Box::new(buffer.clone())),
+            ..Default::default()
+        };
+        run_pipeline(
+            &context,
+            &mut module,
+            "builtin.module(func.func(cse),convert-to-llvm)",
+            &options,
+        )
+        .unwrap();
+
+        let output = String::from_utf8(buffer.0.borrow().clone()).unwrap();
+        // CSE has nothing to do in this module, so only the conversion is
+        // printed.
+        assert!(!output.contains("(cse)"), "{output}");
+        assert!(output.contains("(convert-to-llvm)"), "{output}");
+        assert!(output.contains("Execution time report"), "{output}");
+    }
+
+    #[test]
+    fn nested_pass_statistics() {
+        let context = initialize_context();
+        let mut module = Module::parse(
+            &context,
+            r#"
+            module {
+              func.func @quadruple(%arg0: i32) -> i32 {
+                %c2 = arith.constant 2 : i32
+                %0 = arith.muli %arg0, %c2 : i32
+                %1 = arith.muli %arg0, %c2 : i32
+                %2 = arith.addi %0, %1 : i32
+                return %2 : i32
+              }
+            }"#,
+        )
+        .unwrap();
+
+        let buffer = SharedBuffer::default();
+        let options = PipelineOptions {
+            print_ir_before: true,
+            statistics: true,
+            sink: RefCell::new(Box::new(buffer.clone())),
+            ..Default::default()
+        };
+        run_pipeline(
+            &context,
+            &mut module,
+            "builtin.module(func.func(cse))",
+            &options,
+        )
+        .unwrap();
+
+        let output = String::from_utf8(buffer.0.borrow().clone()).unwrap();
+        // The nested pass is instrumented too, and eliminated the second
+        // multiplication.
+        assert!(output.contains("IR Dump Before CSE (cse)"), "{output}");
+        assert!(output.contains("Pass statistics report"), "{output}");
+        assert!(output.contains("(S) 1 num-cse'd"), "{output}");
+    }
+}
@@ -0,0 +1,133 @@
+//! Crash reproducers for failing pipelines.
+//!
+//! The reproducers are written by MLIR itself, when a pass fails or crashes
+//! (see `PipelineOptions::reproducer`). A reproducer contains the module as it
+//! was before running the failing pipeline, followed by the pipeline itself,
+//! stored as an external resource, so they can also be replayed with
+//! `mlir-opt --run-reproducer`.
+
+use std::{fs, path::Path};
+
+use melior::{Context, ir::Module};
+
+use crate::{error::Error, pipeline::PipelineOptions, run_pipeline};
+
+/// A module, together with the pipeline that failed on it.
+pub struct Reproducer<'c> {
+    pub module: Module<'c>,
+    pub pipeline: String,
+}
+
+impl<'c> Reproducer<'c> {
+    /// Loads a reproducer written by a failing pipeline. The dialects used by
+    /// the module must already be loaded in the context.
+    pub fn load(context: &'c Context, path: impl AsRef<Path>) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let source = fs::read_to_string(path)?;
+        let invalid = || Error::Parse(path.display().to_string());
+
+        let (module_source, resources) = source.split_once("{-#").ok_or_else(invalid)?;
+        let pipeline = resources
+            .split_once("pipeline: \"")
+            .and_then(|(_, rest)| parse_string(rest))
+            .ok_or_else(invalid)?;
+        let module = Module::parse(context, module_source).ok_or_else(invalid)?;
+
+        Ok(Reproducer { module, pipeline })
+    }
+
+    /// Runs the failing pipeline on the module again.
+    pub fn run(&mut self, context: &Context, options: &PipelineOptions) -> Result<(), Error> {
+        run_pipeline(context, &mut self.module, &self.pipeline, options)
+    }
+}
+
+/// Parses the contents of an escaped string, up to the closing quote.
+fn parse_string(source: &str) -> Option<String> {
+    let mut string = String::new();
+    let mut characters = source.chars();
+    while let Some(character) = characters.next() {
+        match character {
+            '"' => return Some(string),
+            '\\' => string.push(characters.next()?),
+            _ => string.push(character),
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod test {
+    use std::{env, fs, process};
+
+    use melior::ir::Module;
+
+    use crate::{
+        initialize_context,
+        pipeline::PipelineOptions,
+        reproducer::{Reproducer, parse_string},
+        run_pipeline,
+    };
+
+    #[test]
+    fn write_and_replay() {
+        let context = initialize_context();
+        let source = r#"
+            module {
+              func.func @entrypoint(%arg0: i32) -> i64 {
+                %0 = builtin.unrealized_conversion_cast %arg0 : i32 to i64
+                return %0 : i64
+              }
+            }"#;
+
+        let path = env::temp_dir().join(format!("dialect-rust-reproducer-{}.mlir", process::id()));
+        let options = PipelineOptions {
+            reproducer: Some(path.clone()),
+            ..Default::default()
+        };
+
+        // The cast can't be reconciled, as nothing converts the value back
+        // to an i32, so the pipeline fails.
+        let mut module = Module::parse(&context, source).unwrap();
+        let pipeline = "builtin.module(canonicalize,reconcile-unrealized-casts)";
+        assert!(run_pipeline(&context, &mut module, pipeline, &options).is_err());
+
+        // MLIR prints the pipeline with the options of every pass.
+        let mut reproducer = Reproducer::load(&context, &path).unwrap();
+        assert!(
+            reproducer
+                .pipeline
+                .starts_with("builtin.module(canonicalize{"),
+            "{}",
+            reproducer.pipeline
+        );
+        assert!(
+            reproducer
+                .pipeline
+                .ends_with(",reconcile-unrealized-casts)")
+        );
+        assert_eq!(
+            reproducer.module.as_operation().to_string(),
+            Module::parse(&context, source)
+                .unwrap()
+                .as_operation()
+                .to_string()
+        );
+        assert!(
+            reproducer
+                .run(&context, &PipelineOptions::default())
+                .is_err()
+        );
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn parse_escaped_string() {
+        assert_eq!(
+            parse_string(r#"a{b=\"c\"}", rest"#).as_deref(),
+            Some(r#"a{b="c"}"#)
+        );
+        assert_eq!(parse_string("unterminated"), None);
+    }
+}
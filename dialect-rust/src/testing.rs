@@ -0,0 +1,52 @@
+use melior::{Context, ir::Module, utility::load_irdl_dialects};
+
+use crate::{
+    apply_pdl_patterns, canonicalize, convert_pdl_to_pdl_interop, convert_to_llvm,
+    initialize_context, irdl::build_dialect_module, legality::verify_dialect_lowered, parse_module,
+    pdl::build_pattern_module, pipeline::PipelineOptions,
+};
+
+/// Returns a context in which the felt dialect is loaded.
+pub fn felt_context() -> Context {
+    let context = initialize_context();
+    load_irdl_dialects(&build_dialect_module(&context));
+    context
+}
+
+/// Parses a module, and lowers it to the LLVM dialect with the patterns of
+/// `pdl::build_pattern_module`.
+pub fn lower_source<'c>(context: &'c Context, source: &str, name: &str) -> Module<'c> {
+    let mut module = parse_module(context, source, name).unwrap();
+    lower(
+        context,
+        &mut module,
+        build_pattern_module(context),
+        &PipelineOptions::default(),
+    );
+    module
+}
+
+/// Applies a pattern module, and lowers the module to the LLVM dialect.
+pub fn lower<'c>(
+    context: &'c Context,
+    module: &mut Module<'c>,
+    pattern_module: Module<'c>,
+    options: &PipelineOptions,
+) {
+    apply_patterns(context, module, pattern_module, options);
+    convert_to_llvm(context, module, options);
+}
+
+/// Applies a pattern module, converted to PDL interp, and checks that no felt
+/// operations are left.
+pub fn apply_patterns<'c>(
+    context: &'c Context,
+    module: &Module<'c>,
+    mut pattern_module: Module<'c>,
+    options: &PipelineOptions,
+) {
+    canonicalize(context, &mut pattern_module, options);
+    convert_pdl_to_pdl_interop(context, &mut pattern_module, options);
+    apply_pdl_patterns(module, &pattern_module);
+    verify_dialect_lowered(module, "felt").unwrap();
+}
@@ -0,0 +1,238 @@
+//! Lowering with transform dialect scripts.
+//!
+//! The transform dialect describes transformations of a payload module as IR
+//! of its own, so a lowering recipe can be shipped as data instead of code. A
+//! script is a module with the `transform.with_named_sequence` attribute,
+//! whose `@__transform_main` named sequence is applied to the payload module.
+//! The payload module is bound to its only argument.
+//!
+//! The transform dialect of MLIR 19 can match operations with PDL, but not
+//! rewrite them. A script can instead contain `pdl.pattern`s at its top
+//! level, which are applied to the payload module before the sequence, like
+//! with `apply_pdl_patterns`.
+//!
+//! The patterns are applied by `apply_transform_script` itself, not by the
+//! interpreter, so they always run before the whole sequence: a script can't
+//! run a pass (like `canonicalize`) before them, or apply them between two of
+//! its steps. Such scripts must be split, with the passes that come first run
+//! separately.
+
+use melior::{
+    Context, LogicalResult,
+    ir::{
+        BlockLike, Module,
+        attribute::StringAttribute,
+        operation::{Operation, OperationLike, OperationRef},
+    },
+};
+use mlir_sys::mlirOperationClone;
+
+use crate::{
+    apply_pdl_patterns, canonicalize, convert_pdl_to_pdl_interop,
+    error::Error,
+    ffi::{
+        mlirTransformApplyNamedSequence, mlirTransformOptionsCreate, mlirTransformOptionsDestroy,
+        mlirTransformOptionsEnableExpensiveChecks,
+    },
+    irdl::OPERATIONS,
+    parse_module,
+    pdl::{Reduction, build_lowering_pattern, build_sum_patterns},
+    pipeline::PipelineOptions,
+};
+
+/// The name of the named sequence applied by `apply_transform_script`.
+pub const ENTRY_POINT: &str = "__transform_main";
+
+/// Builds a script that lowers the felt dialect to LLVM: the lowering
+/// patterns of `pdl::build_pattern_module`, including those of `felt.sum`,
+/// followed by a sequence that
+/// canonicalizes the module, and runs the passes of the default LLVM
+/// pipeline.
+pub fn build_lowering_script(ctx: &'_ Context) -> Module<'_> {
+    let script = parse_module(
+        ctx,
+        r#"
+        module attributes {transform.with_named_sequence} {
+          transform.named_sequence @__transform_main(%module: !transform.any_op {transform.consumed}) {
+            transform.apply_patterns to %module {
+              transform.apply_patterns.canonicalization
+            } : !transform.any_op
+            %0 = transform.apply_registered_pass "convert-linalg-to-loops" to %module : (!transform.any_op) -> !transform.any_op
+            %1 = transform.apply_registered_pass "convert-scf-to-cf" to %0 : (!transform.any_op) -> !transform.any_op
+            %2 = transform.apply_registered_pass "convert-to-llvm" to %1 : (!transform.any_op) -> !transform.any_op
+            %3 = transform.apply_registered_pass "reconcile-unrealized-casts" to %2 : (!transform.any_op) -> !transform.any_op
+            transform.yield
+          }
+        }"#,
+        "build_lowering_script",
+    )
+    .unwrap();
+
+    for (name, operand_count) in OPERATIONS {
+        script.body().append_operation(build_lowering_pattern(
+            ctx,
+            name,
+            operand_count,
+            Reduction::Remainder,
+        ));
+    }
+    for pattern in build_sum_patterns(ctx) {
+        script.body().append_operation(pattern);
+    }
+
+    script
+}
+
+/// Applies the script to the payload module: first its PDL patterns, if any,
+/// and then its `@__transform_main` named sequence.
+pub fn apply_transform_script(
+    ctx: &Context,
+    payload: &Module,
+    script: &Module,
+    options: &PipelineOptions,
+) -> Result<(), Error> {
+    let mut pattern_module = Module::new(script.as_operation().location());
+    let mut entry_point = None;
+
+    let mut operation = script.body().first_operation();
+    while let Some(current) = operation {
+        match current.name().as_string_ref().as_str().unwrap() {
+            "pdl.pattern" => {
+                // The patterns are converted to PDL interp, which would
+                // modify the script, so we convert copies of them instead.
+                pattern_module.body().append_operation(unsafe {
+                    Operation::from_raw(mlirOperationClone(current.to_raw()))
+                });
+            }
+            "transform.named_sequence" if is_entry_point(current) => entry_point = Some(current),
+            _ => {}
+        }
+        operation = current.next_in_block();
+    }
+    let entry_point =
+        entry_point.ok_or_else(|| Error::Transform(format!("no @{ENTRY_POINT} named sequence")))?;
+
+    if pattern_module.body().first_operation().is_some() {
+        canonicalize(ctx, &mut pattern_module, options);
+        convert_pdl_to_pdl_interop(ctx, &mut pattern_module, options);
+        apply_pdl_patterns(payload, &pattern_module);
+    }
+
+    let result = unsafe {
+        let transform_options = mlirTransformOptionsCreate();
+        mlirTransformOptionsEnableExpensiveChecks(transform_options, cfg!(debug_assertions));
+        let result = mlirTransformApplyNamedSequence(
+            payload.as_operation().to_raw(),
+            entry_point.to_raw(),
+            script.as_operation().to_raw(),
+            transform_options,
+        );
+        mlirTransformOptionsDestroy(transform_options);
+        LogicalResult::from_raw(result)
+    };
+    if result.is_failure() {
+        return Err(Error::Transform(format!(
+            "@{ENTRY_POINT} failed on the payload module"
+        )));
+    }
+
+    Ok(())
+}
+
+fn is_entry_point(operation: OperationRef) -> bool {
+    operation
+        .attribute("sym_name")
+        .ok()
+        .and_then(|name| StringAttribute::try_from(name).ok())
+        .is_some_and(|name| name.value() == ENTRY_POINT)
+}
+
+#[cfg(test)]
+mod test {
+    use melior::ir::Module;
+
+    use crate::{
+        caller_location,
+        dsl::FeltFn,
+        error::Error,
+        execute_entrypoint, initialize_context, parse_module,
+        pipeline::PipelineOptions,
+        testing::felt_context,
+        transform::{apply_transform_script, build_lowering_script},
+    };
+
+    #[test]
+    fn lower_with_script() {
+        let context = felt_context();
+        let options = PipelineOptions::default();
+
+        let module = Module::new(caller_location(&context));
+        module.body().append_operation(
+            FeltFn::new(&context, "entrypoint", 2, |b, [x, y]| {
+                (x + b.constant(1)) * y - x / y
+            })
+            .into(),
+        );
+
+        let script = build_lowering_script(&context);
+        apply_transform_script(&context, &module, &script, &options).unwrap();
+        let source = module.as_operation().to_string();
+        assert!(source.contains("llvm.func @entrypoint"), "{source}");
+
+        // (4 + 1) * 3 = 2, and the inverse of 3 is 9, so 4 / 3 = 36 = 10, and
+        // 2 - 10 = 5.
+        assert_eq!(execute_entrypoint(&module, 4, 3), 5);
+    }
+
+    #[test]
+    fn lower_sums_with_script() {
+        let context = felt_context();
+        let options = PipelineOptions::default();
+
+        let module = parse_module(
+            &context,
+            r#"
+            module {
+              func.func @entrypoint(%a: i32, %b: i32) -> i32 attributes { llvm.emit_c_interface } {
+                %0 = "felt.sum"(%a, %b, %a) : (i32, i32, i32) -> i32
+                %1 = "felt.sum"() : () -> i32
+                %2 = "felt.sum"(%b) : (i32) -> i32
+                %3 = "felt.sum"(%0, %1, %2) : (i32, i32, i32) -> i32
+                return %3 : i32
+              }
+            }"#,
+            "lower_sums_with_script",
+        )
+        .unwrap();
+
+        let script = build_lowering_script(&context);
+        apply_transform_script(&context, &module, &script, &options).unwrap();
+
+        // (3 + 4 + 3) + 0 + 4 = 14 = 1.
+        assert_eq!(execute_entrypoint(&module, 3, 4), 1);
+    }
+
+    #[test]
+    fn missing_entry_point() {
+        let context = initialize_context();
+        let options = PipelineOptions::default();
+
+        let module = Module::new(caller_location(&context));
+        let script = parse_module(
+            &context,
+            r#"
+            module attributes {transform.with_named_sequence} {
+              transform.named_sequence @lower(%module: !transform.any_op {transform.readonly}) {
+                transform.yield
+              }
+            }"#,
+            "missing_entry_point",
+        )
+        .unwrap();
+
+        assert!(matches!(
+            apply_transform_script(&context, &module, &script, &options),
+            Err(Error::Transform(_))
+        ));
+    }
+}
@@ -0,0 +1,24 @@
+use melior::ir::{
+    BlockLike, RegionLike,
+    operation::{OperationLike, OperationRef},
+};
+
+/// Calls `f` on the operation, and then on every operation nested in its
+/// regions, in pre-order.
+pub fn walk<'c>(operation: OperationRef<'c, '_>, f: &mut impl FnMut(OperationRef<'c, '_>)) {
+    f(operation);
+
+    for index in 0..operation.region_count() {
+        let region = operation.region(index).unwrap();
+
+        let mut block = region.first_block();
+        while let Some(current_block) = block {
+            let mut nested = current_block.first_operation();
+            while let Some(current) = nested {
+                walk(current, f);
+                nested = current.next_in_block();
+            }
+            block = current_block.next_in_region();
+        }
+    }
+}